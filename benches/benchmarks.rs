@@ -1,4 +1,4 @@
-use anchors::singlethread::{Engine, MultiAnchor, Var};
+use anchors::singlethread::{Engine, Var};
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 
 fn stabilize_linear_nodes_simple(c: &mut Criterion) {
@@ -15,9 +15,9 @@ fn stabilize_linear_nodes_simple(c: &mut Criterion) {
                 ),
                 &(*node_count, *observed),
                 |b, (node_count, observed)| {
-                    let mut engine = Engine::new_with_max_height(1003);
-                    let (first_num, set_first_num) = Var::new(0u64);
-                    let mut node = first_num;
+                    let mut engine = Engine::builder().max_height(1003).build();
+                    let first_num = Var::new(0u64);
+                    let mut node = first_num.watch();
                     for _ in 0..*node_count {
                         node = node.map(|val| val + black_box(1));
                     }
@@ -28,7 +28,7 @@ fn stabilize_linear_nodes_simple(c: &mut Criterion) {
                     let mut update_number = 0;
                     b.iter(|| {
                         update_number += 1;
-                        set_first_num.set(update_number);
+                        first_num.set(update_number);
                         assert_eq!(engine.get(&node), update_number + *node_count);
                     });
                 },
@@ -51,9 +51,9 @@ fn stabilize_linear_nodes_cutoff(c: &mut Criterion) {
                 ),
                 &(*node_count, *observed),
                 |b, (node_count, observed)| {
-                    let mut engine = Engine::new_with_max_height(1003);
-                    let (first_num, set_first_num) = Var::new(0u64);
-                    let node = first_num;
+                    let mut engine = Engine::builder().max_height(1003).build();
+                    let first_num = Var::new(0u64);
+                    let node = first_num.watch();
                     let node = node.map(|val| black_box(val) - black_box(val) + 1);
                     let mut node = {
                         let mut old_val = None;
@@ -76,7 +76,7 @@ fn stabilize_linear_nodes_cutoff(c: &mut Criterion) {
                     let mut update_number = 0;
                     b.iter(|| {
                         update_number += 1;
-                        set_first_num.set(update_number);
+                        first_num.set(update_number);
                         assert_eq!(engine.get(&node), *node_count - 1);
                     });
                 },
@@ -85,9 +85,37 @@ fn stabilize_linear_nodes_cutoff(c: &mut Criterion) {
     }
 }
 
+fn read_small_output(c: &mut Criterion) {
+    for copy in &[false, true] {
+        c.bench_with_input(
+            BenchmarkId::new("read_small_output", if *copy { "get_copy" } else { "get" }),
+            copy,
+            |b, copy| {
+                let mut engine = Engine::new();
+                let first_num = Var::new(0u64);
+                let node = first_num.watch().map(|val| (val + black_box(1), black_box(1u8)));
+                engine.mark_observed(&node);
+                let mut update_number = 0;
+                b.iter(|| {
+                    update_number += 1;
+                    first_num.set(update_number);
+                    for _ in 0..100 {
+                        let val = if *copy {
+                            engine.get_copy(&node)
+                        } else {
+                            engine.get(&node)
+                        };
+                        assert_eq!(val, (update_number + 1, 1));
+                    }
+                });
+            },
+        );
+    }
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default();
-    targets = stabilize_linear_nodes_cutoff, stabilize_linear_nodes_simple
+    targets = stabilize_linear_nodes_cutoff, stabilize_linear_nodes_simple, read_small_output
 }
 criterion_main!(benches);
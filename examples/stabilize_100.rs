@@ -6,7 +6,7 @@ const ITER_COUNT: u64 = 500000;
 const OBSERVED: bool = true;
 
 fn main() {
-    let mut engine = Engine::new_with_max_height(128);
+    let mut engine = Engine::builder().max_height(128).build();
     let first_num = Var::new(0u64);
     let mut node = first_num.watch();
     for _ in 0..NODE_COUNT {
@@ -0,0 +1,37 @@
+//! Counts the characters typed into a text box. Build for `wasm32-unknown-unknown` with the `web`
+//! feature, and serve it with a tool like `trunk` or `wasm-bindgen`.
+
+use anchors::singlethread::web::{animation_frame_loop, input_value};
+use anchors::singlethread::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+use wasm_bindgen::JsCast;
+
+fn main() {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let body = document.body().unwrap();
+    let input: web_sys::HtmlInputElement = document
+        .create_element("input")
+        .unwrap()
+        .dyn_into()
+        .unwrap();
+    let output = document.create_element("p").unwrap();
+    body.append_child(&input).unwrap();
+    body.append_child(&output).unwrap();
+
+    let (text, listener) = input_value(&input);
+    let summary = text
+        .watch()
+        .map(|text| format!("{} characters", text.chars().count()));
+
+    let engine = Rc::new(RefCell::new(Engine::new()));
+    engine.borrow_mut().mark_observed(&summary);
+    let frames = animation_frame_loop(engine, Duration::from_millis(8), move |engine, _report| {
+        output.set_text_content(Some(&engine.get(&summary)));
+    });
+
+    // keep the page wired up for as long as it's open
+    std::mem::forget(listener);
+    std::mem::forget(frames);
+}
@@ -0,0 +1,377 @@
+//! Incremental windows and axis reductions over 2D ndarray Anchors. Requires the `ndarray`
+//! feature.
+//!
+//! Each combinator is paired with an Anchor of a `RegionLog`, which records the `Region` of the
+//! array changed by each update. A combinator remembers the last version of the log it saw, and
+//! only recomputes the slices of its output that overlap the regions pushed since then, even if
+//! it wasn't recalculated for some of them. The log must be updated whenever the array is, in the
+//! same stabilization, and cover every cell that changed. When in doubt, pushing `Region::all()`
+//! is always correct.
+//!
+//! ```
+//! use anchors::array::{Region, RegionLog};
+//! use anchors::singlethread::*;
+//! use ndarray::{array, Axis};
+//!
+//! let mut engine = Engine::new();
+//! let data = Var::new(array![[1., 2.], [3., 4.], [5., 6.]]);
+//! let changes = Var::new(RegionLog::new());
+//! let row_sums = data.watch().sum_axis(&changes.watch(), Axis(1));
+//! assert_eq!(engine.get(&row_sums), array![3., 7., 11.]);
+//!
+//! // only the last row's sum is recomputed
+//! data.modify(|data| data[[2, 0]] = 0.);
+//! changes.modify(|log| log.push(Region::rows(2..3)));
+//! assert_eq!(engine.get(&row_sums), array![3., 7., 6.]);
+//! ```
+
+use crate::expert::{Anchor, Engine, MultiAnchor};
+use ndarray::{s, Array1, Array2, ArrayView1, Axis};
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/// A rectangle of a 2D array's cells, by row and column range. Ranges may extend past the edges
+/// of the array; only the cells inside it count.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Region {
+    pub rows: Range<usize>,
+    pub cols: Range<usize>,
+}
+
+impl Region {
+    /// Every cell of an array of any shape.
+    pub fn all() -> Self {
+        Self {
+            rows: 0..usize::MAX,
+            cols: 0..usize::MAX,
+        }
+    }
+
+    /// No cells.
+    pub fn empty() -> Self {
+        Self {
+            rows: 0..0,
+            cols: 0..0,
+        }
+    }
+
+    /// Every column of `rows`.
+    pub fn rows(rows: Range<usize>) -> Self {
+        Self {
+            rows,
+            cols: 0..usize::MAX,
+        }
+    }
+
+    /// Every row of `cols`.
+    pub fn cols(cols: Range<usize>) -> Self {
+        Self {
+            rows: 0..usize::MAX,
+            cols,
+        }
+    }
+
+    /// Returns the cells in both `self` and `other`, which may be empty.
+    pub fn intersect(&self, other: &Region) -> Region {
+        Region {
+            rows: intersect(&self.rows, &other.rows),
+            cols: intersect(&self.cols, &other.cols),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty() || self.cols.is_empty()
+    }
+
+    /// Returns the smallest region containing both `self` and `other`.
+    pub fn union(&self, other: &Region) -> Region {
+        if self.is_empty() {
+            return other.clone();
+        }
+        if other.is_empty() {
+            return self.clone();
+        }
+        Region {
+            rows: self.rows.start.min(other.rows.start)..self.rows.end.max(other.rows.end),
+            cols: self.cols.start.min(other.cols.start)..self.cols.end.max(other.cols.end),
+        }
+    }
+
+    /// Returns the part of `self` inside an array with `shape`.
+    fn clamp(&self, shape: (usize, usize)) -> Region {
+        self.intersect(&Region {
+            rows: 0..shape.0,
+            cols: 0..shape.1,
+        })
+    }
+}
+
+fn intersect(a: &Range<usize>, b: &Range<usize>) -> Range<usize> {
+    let start = a.start.max(b.start);
+    start..a.end.min(b.end).max(start)
+}
+
+/// The regions of an array changed by its most recent updates, each numbered with the version of
+/// the log it was pushed at. Only the latest `RegionLog::CAPACITY` regions are kept; a combinator
+/// that fell further behind recomputes its whole output.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RegionLog {
+    version: u64,
+    /// recent regions, oldest first, with the version each one was pushed at
+    regions: VecDeque<(u64, Region)>,
+}
+
+impl RegionLog {
+    /// How many regions are kept.
+    pub const CAPACITY: usize = 64;
+
+    /// Creates an empty log at version 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that the cells in `region` changed, advancing to the next version.
+    pub fn push(&mut self, region: Region) {
+        self.version += 1;
+        if self.regions.len() == Self::CAPACITY {
+            self.regions.pop_front();
+        }
+        self.regions.push_back((self.version, region));
+    }
+
+    /// Returns the current version, which is the number of regions ever pushed.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns a region covering every change pushed after `version`, or `None` if some of them
+    /// are no longer kept.
+    pub fn since(&self, version: u64) -> Option<Region> {
+        if version >= self.version {
+            return Some(Region::empty());
+        }
+        let first_kept = self.regions.front().map_or(self.version + 1, |(v, _)| *v);
+        if version + 1 < first_kept {
+            return None;
+        }
+        Some(
+            self.regions
+                .iter()
+                .filter(|(v, _)| *v > version)
+                .fold(Region::empty(), |acc, (_, region)| acc.union(region)),
+        )
+    }
+}
+
+impl<E: Engine> Anchor<Array2<f64>, E> {
+    /// Returns an Anchor of the cells in `window`, or the part of it inside the array. After the
+    /// first calculation, only cells in both `window` and a region changed since the last one are
+    /// copied, and dependents aren't recalculated if none of them differ.
+    #[track_caller]
+    pub fn window(&self, changes: &Anchor<RegionLog, E>, window: Region) -> Anchor<Array2<f64>, E> {
+        let mut seen = None;
+        (self, changes).map_mut(
+            Array2::zeros((0, 0)),
+            move |out: &mut Array2<f64>, data: &Array2<f64>, changes: &RegionLog| {
+                let bounds = window.clamp(data.dim());
+                let changed = seen.and_then(|seen| changes.since(seen));
+                seen = Some(changes.version());
+                // a new shape, including the first calculation, or missing changes mean copying
+                // in full
+                let changed = match changed {
+                    Some(changed) if out.dim() == (bounds.rows.len(), bounds.cols.len()) => changed,
+                    _ => {
+                        *out = data.slice(s![bounds.rows, bounds.cols]).to_owned();
+                        return true;
+                    }
+                };
+                let stale = bounds.intersect(&changed);
+                if stale.is_empty() {
+                    return false;
+                }
+                let src = data.slice(s![stale.rows.clone(), stale.cols.clone()]);
+                let mut dst = out.slice_mut(s![
+                    stale.rows.start - bounds.rows.start..stale.rows.end - bounds.rows.start,
+                    stale.cols.start - bounds.cols.start..stale.cols.end - bounds.cols.start
+                ]);
+                if dst == src {
+                    false
+                } else {
+                    dst.assign(&src);
+                    true
+                }
+            },
+        )
+    }
+
+    /// Returns an Anchor reducing each lane along `axis` with `f`: each column for `Axis(0)`, or
+    /// each row for `Axis(1)`. After the first calculation, only lanes crossing a region changed
+    /// since the last one are reduced again, and dependents aren't recalculated if none of their
+    /// results differ.
+    #[track_caller]
+    pub fn reduce_axis<F>(
+        &self,
+        changes: &Anchor<RegionLog, E>,
+        axis: Axis,
+        f: F,
+    ) -> Anchor<Array1<f64>, E>
+    where
+        F: Fn(ArrayView1<f64>) -> f64 + 'static,
+    {
+        assert!(
+            axis.index() < 2,
+            "axis {} out of bounds for 2D array",
+            axis.index()
+        );
+        let mut seen = None;
+        (self, changes).map_mut(
+            Array1::zeros(0),
+            move |out: &mut Array1<f64>, data: &Array2<f64>, changes: &RegionLog| {
+                let changed = seen.and_then(|seen| changes.since(seen));
+                seen = Some(changes.version());
+                // a new shape, including the first calculation, or missing changes mean reducing
+                // in full
+                let changed = match changed {
+                    Some(changed) if out.len() == data.len_of(Axis(1 - axis.index())) => changed,
+                    _ => {
+                        *out = data.map_axis(axis, &f);
+                        return true;
+                    }
+                };
+                let changed = changed.clamp(data.dim());
+                if changed.is_empty() {
+                    return false;
+                }
+                // lanes along rows are indexed by column, and vice versa
+                let lanes = if axis == Axis(0) {
+                    changed.cols
+                } else {
+                    changed.rows
+                };
+                let mut updated = false;
+                for i in lanes {
+                    let val = f(data.index_axis(Axis(1 - axis.index()), i));
+                    // compare bitwise, so a NaN result doesn't count as a change every time
+                    if val.to_bits() != out[i].to_bits() {
+                        out[i] = val;
+                        updated = true;
+                    }
+                }
+                updated
+            },
+        )
+    }
+
+    /// Returns an Anchor of the sum of each lane along `axis`. See `reduce_axis`.
+    #[track_caller]
+    pub fn sum_axis(&self, changes: &Anchor<RegionLog, E>, axis: Axis) -> Anchor<Array1<f64>, E> {
+        self.reduce_axis(changes, axis, |lane| lane.sum())
+    }
+
+    /// Returns an Anchor of the mean of each lane along `axis`, which is NaN for empty lanes. See
+    /// `reduce_axis`.
+    #[track_caller]
+    pub fn mean_axis(&self, changes: &Anchor<RegionLog, E>, axis: Axis) -> Anchor<Array1<f64>, E> {
+        self.reduce_axis(changes, axis, |lane| lane.sum() / lane.len() as f64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Region, RegionLog};
+    use crate::singlethread::*;
+    use ndarray::{array, Axis};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn reductions_only_recompute_changed_lanes() {
+        let mut engine = Engine::new();
+        let data = Var::new(array![[1., 2., 3.], [4., 5., 6.]]);
+        let changes = Var::new(RegionLog::new());
+        let lanes = Rc::new(Cell::new(0));
+        let col_maxes = {
+            let lanes = lanes.clone();
+            data.watch()
+                .reduce_axis(&changes.watch(), Axis(0), move |col| {
+                    lanes.set(lanes.get() + 1);
+                    col.fold(f64::MIN, |a, b| a.max(*b))
+                })
+        };
+        let col_means = data.watch().mean_axis(&changes.watch(), Axis(0));
+        assert_eq!(engine.get(&col_maxes), array![4., 5., 6.]);
+        assert_eq!(engine.get(&col_means), array![2.5, 3.5, 4.5]);
+        assert_eq!(lanes.get(), 3);
+
+        data.modify(|data| data[[0, 1]] = 9.);
+        changes.modify(|log| log.push(Region::cols(1..2)));
+        assert_eq!(engine.get(&col_maxes), array![4., 9., 6.]);
+        assert_eq!(engine.get(&col_means), array![2.5, 7., 4.5]);
+        assert_eq!(lanes.get(), 4);
+
+        // a new shape is reduced in full
+        data.set(array![[1., 2.], [3., 4.]]);
+        changes.modify(|log| log.push(Region::all()));
+        assert_eq!(engine.get(&col_maxes), array![3., 4.]);
+        assert_eq!(lanes.get(), 6);
+    }
+
+    #[test]
+    fn window_skips_changes_outside_it() {
+        let mut engine = Engine::new();
+        let data = Var::new(array![[1., 2., 3.], [4., 5., 6.], [7., 8., 9.]]);
+        let changes = Var::new(RegionLog::new());
+        let updates = Rc::new(Cell::new(0));
+        let window = data.watch().window(
+            &changes.watch(),
+            Region {
+                rows: 1..5,
+                cols: 0..2,
+            },
+        );
+        let watched = {
+            let updates = updates.clone();
+            window.map(move |window| {
+                updates.set(updates.get() + 1);
+                window.clone()
+            })
+        };
+        engine.mark_observed(&watched);
+        assert_eq!(engine.get(&watched), array![[4., 5.], [7., 8.]]);
+
+        data.modify(|data| data[[0, 0]] = 0.);
+        changes.modify(|log| log.push(Region::rows(0..1)));
+        engine.stabilize();
+        assert_eq!(updates.get(), 1);
+
+        data.modify(|data| data[[2, 1]] = 0.);
+        changes.modify(|log| log.push(Region::rows(2..3)));
+        assert_eq!(engine.get(&watched), array![[4., 5.], [7., 0.]]);
+        assert_eq!(updates.get(), 2);
+    }
+
+    #[test]
+    fn reductions_see_changes_made_while_unobserved() {
+        let mut engine = Engine::new();
+        let data = Var::new(array![[4., 6.]]);
+        let changes = Var::new(RegionLog::new());
+        let sums = data.watch().sum_axis(&changes.watch(), Axis(0));
+        assert_eq!(engine.get(&sums), array![4., 6.]);
+
+        data.modify(|data| data[[0, 1]] = 104.);
+        changes.modify(|log| log.push(Region::cols(1..2)));
+        engine.stabilize();
+        data.modify(|data| data[[0, 0]] = 13.);
+        changes.modify(|log| log.push(Region::cols(0..1)));
+        assert_eq!(engine.get(&sums), array![13., 104.]);
+
+        // falling behind the log's capacity reduces in full
+        for _ in 0..RegionLog::CAPACITY {
+            changes.modify(|log| log.push(Region::empty()));
+        }
+        data.modify(|data| data[[0, 1]] = 0.);
+        changes.modify(|log| log.push(Region::cols(1..2)));
+        assert_eq!(engine.get(&sums), array![13., 0.]);
+        assert_eq!(changes.get().since(0), None);
+    }
+}
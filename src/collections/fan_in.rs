@@ -0,0 +1,189 @@
+use std::collections::{HashMap, HashSet};
+use std::panic::Location;
+use crate::expert::{Engine, AnchorInner, UpdateContext, AnchorHandle, OutputContext, Anchor, Poll};
+
+type Token<E> = <<E as Engine>::AnchorHandle as AnchorHandle>::Token;
+
+impl<Out: 'static, E: Engine> Anchor<Out, E> {
+    /// Creates an Anchor folding many inputs of the same type into one output, without revisiting
+    /// the inputs that didn't change. `f` is called with `(output, index, new_value)` for each
+    /// input that updated since the last poll, in order of index, and returns whether it changed
+    /// the output, like `map_mut`. The first poll calls it for every input.
+    ///
+    /// ```
+    /// use anchors::singlethread::*;
+    /// let mut engine = Engine::new();
+    /// let vars: Vec<_> = (0..100).map(Var::new).collect();
+    /// // keeps each input's last value, so only the changed ones need to be added to the sum
+    /// let sum = Anchor::fan_in(
+    ///     vars.iter().map(|var| var.watch()),
+    ///     (vec![0; 100], 0),
+    ///     |(vals, sum): &mut (Vec<i32>, i32), i, val: &i32| {
+    ///         *sum += val - vals[i];
+    ///         vals[i] = *val;
+    ///         true
+    ///     },
+    /// )
+    /// .map(|(_, sum)| *sum);
+    /// assert_eq!(engine.get(&sum), 4950);
+    ///
+    /// vars[10].set(1000);
+    /// assert_eq!(engine.get(&sum), 4950 - 10 + 1000);
+    /// ```
+    #[track_caller]
+    pub fn fan_in<I, A, F>(anchors: A, initial: Out, f: F) -> Self
+    where
+        I: 'static,
+        A: IntoIterator<Item = Anchor<I, E>>,
+        F: FnMut(&mut Out, usize, &I) -> bool + 'static,
+    {
+        let anchors: Vec<_> = anchors.into_iter().collect();
+        let mut indexes: HashMap<Token<E>, Vec<usize>> = HashMap::new();
+        for (i, anchor) in anchors.iter().enumerate() {
+            indexes.entry(anchor.token()).or_default().push(i);
+        }
+        E::mount(FanIn {
+            anchors,
+            indexes,
+            dirtied: HashSet::new(),
+            updated: Vec::new(),
+            f,
+            output: initial,
+            initialized: false,
+            location: Location::caller(),
+        })
+    }
+}
+
+struct FanIn<I, Out, F, E: Engine> {
+    anchors: Vec<Anchor<I, E>>,
+    /// the positions of each input in `anchors`, which may include the same input more than once
+    indexes: HashMap<Token<E>, Vec<usize>>,
+    /// inputs that may have changed, and haven't been requested since
+    dirtied: HashSet<Token<E>>,
+    /// indexes of inputs that changed, waiting for every dirtied input to be ready
+    updated: Vec<usize>,
+    f: F,
+    output: Out,
+    initialized: bool,
+    location: &'static Location<'static>,
+}
+
+impl<I, Out, F, E> AnchorInner<E> for FanIn<I, Out, F, E>
+where
+    I: 'static,
+    Out: 'static,
+    F: FnMut(&mut Out, usize, &I) -> bool,
+    E: Engine,
+{
+    type Output = Out;
+    fn dirty(&mut self, edge: &Token<E>) {
+        self.dirtied.insert(*edge);
+    }
+
+    fn poll_updated<G: UpdateContext<Engine = E>>(
+        &mut self,
+        ctx: &mut G,
+    ) -> Poll {
+        if !self.initialized {
+            if ctx.request_many(&self.anchors, true) == Poll::Pending {
+                return Poll::Pending;
+            }
+            self.initialized = true;
+            self.dirtied.clear();
+            for (i, anchor) in self.anchors.iter().enumerate() {
+                (self.f)(&mut self.output, i, ctx.get(anchor));
+            }
+            return Poll::Updated;
+        }
+
+        // request each dirtied input once, even if it appears at several indexes, and remember
+        // which ones changed in case another is still pending
+        let anchors = &self.anchors;
+        let indexes = &self.indexes;
+        let updated = &mut self.updated;
+        self.dirtied.retain(|token| {
+            let positions = &indexes[token];
+            match ctx.request(&anchors[positions[0]], true) {
+                Poll::Pending => true,
+                Poll::Updated => {
+                    updated.extend_from_slice(positions);
+                    false
+                }
+                Poll::Unchanged => false,
+            }
+        });
+        if !self.dirtied.is_empty() {
+            return Poll::Pending;
+        }
+
+        self.updated.sort_unstable();
+        let mut changed = false;
+        for i in self.updated.drain(..) {
+            changed |= (self.f)(&mut self.output, i, ctx.get(&self.anchors[i]));
+        }
+        if changed {
+            Poll::Updated
+        } else {
+            Poll::Unchanged
+        }
+    }
+
+    fn output<'slf, 'out, G: OutputContext<'out, Engine = E>>(
+        &'slf self,
+        _ctx: &mut G,
+    ) -> &'out Self::Output
+    where
+        'slf: 'out,
+    {
+        &self.output
+    }
+
+    fn debug_location(&self) -> Option<(&'static str, &'static Location<'static>)> {
+        Some(("fan_in", self.location))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::singlethread::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn fan_in_only_sees_changed_inputs() {
+        let mut engine = Engine::new();
+        let vars: Vec<_> = (0..10).map(Var::new).collect();
+        let doubled: Vec<_> = vars.iter().map(|var| var.watch().map(|n| *n / 2 * 2)).collect();
+        let seen = Rc::new(RefCell::new(vec![]));
+        let latest = {
+            let seen = seen.clone();
+            Anchor::fan_in(
+                doubled.iter().cloned().chain(Some(doubled[3].clone())),
+                vec![0; 11],
+                move |out: &mut Vec<i32>, i, val: &i32| {
+                    seen.borrow_mut().push(i);
+                    out[i] = *val;
+                    true
+                },
+            )
+        };
+        assert_eq!(engine.get(&latest), vec![0, 0, 2, 2, 4, 4, 6, 6, 8, 8, 2]);
+        assert_eq!(seen.borrow().len(), 11);
+        seen.borrow_mut().clear();
+
+        // observing after the first calculation still sees later changes
+        engine.mark_observed(&latest);
+        vars[3].set(7);
+        vars[8].set(9);
+        engine.stabilize();
+        assert_eq!(*seen.borrow(), vec![3, 10]);
+        assert_eq!(engine.get(&latest), vec![0, 0, 2, 6, 4, 4, 6, 6, 8, 8, 6]);
+        seen.borrow_mut().clear();
+
+        // inputs cut off by `map` aren't passed along
+        vars[9].set(8);
+        engine.stabilize();
+        assert!(seen.borrow().is_empty());
+    }
+}
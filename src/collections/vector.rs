@@ -50,11 +50,7 @@ impl<T: 'static + Clone, E: Engine> AnchorInner<E> for VectorCollect<T, E> {
 
     fn poll_updated<G: UpdateContext<Engine = E>>(&mut self, ctx: &mut G) -> Poll {
         if self.vals.is_none() {
-            let pending_exists = self
-                .anchors
-                .iter()
-                .any(|anchor| ctx.request(anchor, true) == Poll::Pending);
-            if pending_exists {
+            if ctx.request_many(&self.anchors, true) == Poll::Pending {
                 return Poll::Pending;
             }
             self.vals = Some(
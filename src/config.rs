@@ -0,0 +1,140 @@
+//! Anchors for parsing configuration files into typed structs. Requires the `config` feature.
+//!
+//! ```
+//! use anchors::config::ConfigAnchor;
+//! use anchors::singlethread::*;
+//!
+//! #[derive(serde::Deserialize, PartialEq, Default)]
+//! struct Settings {
+//!     port: u16,
+//! }
+//!
+//! let mut engine = Engine::new();
+//! let source = Var::new(r#"{"port": 8080}"#.to_string());
+//! let config: ConfigAnchor<Settings, _> = ConfigAnchor::json(&source.watch(), Settings::default());
+//! let port = config.field(|settings| &settings.port).map(|port| *port);
+//! assert_eq!(engine.get(&port), 8080);
+//!
+//! // invalid configs leave the last valid value in place, and report an error
+//! source.set("{".to_string());
+//! assert_eq!(engine.get(&port), 8080);
+//! assert!(engine.get(&config.error()).is_some());
+//! ```
+
+use crate::expert::{Anchor, Engine};
+use serde::de::DeserializeOwned;
+
+/// Parses some source text Anchor into a `T`. The text is only reparsed when the source Anchor
+/// changes. If parsing fails, the last successfully parsed value is kept, and the parse error is
+/// reported by `error`.
+pub struct ConfigAnchor<T, E: Engine> {
+    state: Anchor<(T, Option<String>), E>,
+}
+
+impl<T, E: Engine> Clone for ConfigAnchor<T, E> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T: DeserializeOwned + PartialEq + 'static, E: Engine> ConfigAnchor<T, E> {
+    /// Creates a `ConfigAnchor` that parses `source` as JSON. `default` is used until `source`
+    /// has been successfully parsed for the first time.
+    #[track_caller]
+    pub fn json(source: &Anchor<String, E>, default: T) -> Self {
+        Self::new_with_parser(source, default, |text| {
+            serde_json::from_str(text).map_err(|err| err.to_string())
+        })
+    }
+
+    /// Creates a `ConfigAnchor` that parses `source` as TOML. `default` is used until `source`
+    /// has been successfully parsed for the first time.
+    #[track_caller]
+    pub fn toml(source: &Anchor<String, E>, default: T) -> Self {
+        Self::new_with_parser(source, default, |text| {
+            toml::from_str(text).map_err(|err| err.to_string())
+        })
+    }
+
+    /// Creates a `ConfigAnchor` with a custom parsing function.
+    #[track_caller]
+    pub fn new_with_parser<F>(source: &Anchor<String, E>, default: T, mut parse: F) -> Self
+    where
+        F: FnMut(&str) -> Result<T, String> + 'static,
+    {
+        let state = source.map_mut(
+            (default, None),
+            move |state: &mut (T, Option<String>), text: &String| match parse(text) {
+                Ok(val) => {
+                    let changed = state.0 != val || state.1.is_some();
+                    *state = (val, None);
+                    changed
+                }
+                Err(err) => {
+                    let changed = state.1.as_ref() != Some(&err);
+                    state.1 = Some(err);
+                    changed
+                }
+            },
+        );
+        Self { state }
+    }
+}
+
+impl<T: 'static, E: Engine> ConfigAnchor<T, E> {
+    /// Returns an Anchor of the last successfully parsed value.
+    pub fn value(&self) -> Anchor<T, E> {
+        self.state.refmap(|(val, _)| val)
+    }
+
+    /// Returns an Anchor of some field of the last successfully parsed value.
+    pub fn field<F: 'static>(&self, f: fn(&T) -> &F) -> Anchor<F, E> {
+        self.state.refmap(move |(val, _)| f(val))
+    }
+
+    /// Returns an Anchor containing the error from parsing the current source text, if any.
+    pub fn error(&self) -> Anchor<Option<String>, E> {
+        self.state.refmap(|(_, err)| err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ConfigAnchor;
+    use crate::singlethread::*;
+
+    #[derive(serde::Deserialize, PartialEq, Debug, Default, Clone)]
+    struct Settings {
+        name: String,
+        retries: usize,
+    }
+
+    #[test]
+    fn toml_reparses_on_change() {
+        let mut engine = Engine::new();
+        let source = Var::new("name = \"a\"\nretries = 1".to_string());
+        let config = ConfigAnchor::toml(&source.watch(), Settings::default());
+        let name = config.field(|s| &s.name).map(|name| name.clone());
+        let retries = config.field(|s| &s.retries).map(|r| *r);
+        assert_eq!(engine.get(&name), "a");
+        assert_eq!(engine.get(&retries), 1);
+
+        source.set("name = \"a\"\nretries = 2".to_string());
+        assert_eq!(engine.get(&retries), 2);
+        assert_eq!(engine.get(&config.error()), None);
+
+        source.set("retries = ".to_string());
+        assert_eq!(engine.get(&retries), 2);
+        assert!(engine.get(&config.error()).is_some());
+        assert_eq!(
+            engine.get(&config.value()),
+            Settings {
+                name: "a".to_string(),
+                retries: 2
+            }
+        );
+        assert_eq!(engine.get(&name), "a");
+    }
+}
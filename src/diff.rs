@@ -0,0 +1,193 @@
+//! Line diffs between successive values of a text Anchor. Requires the `diff` feature.
+//!
+//! ```
+//! use anchors::diff::LineDiff;
+//! use anchors::singlethread::*;
+//!
+//! let mut engine = Engine::new();
+//! let text = Var::new("a\nb\nc".to_string());
+//! let diff = text.watch().line_diff();
+//! engine.mark_observed(&diff);
+//! engine.stabilize();
+//!
+//! text.set("a\nB\nc".to_string());
+//! assert_eq!(
+//!     engine.get(&diff),
+//!     vec![
+//!         LineDiff::Equal { old_index: 0, new_index: 0, len: 1 },
+//!         LineDiff::Replace { old_index: 1, old_len: 1, new_index: 1, new_len: 1 },
+//!         LineDiff::Equal { old_index: 2, new_index: 2, len: 1 },
+//!     ]
+//! );
+//! ```
+
+use crate::expert::{Anchor, Engine};
+use similar::{Algorithm, DiffOp};
+
+/// One step of a line diff. Indexes count lines, as split by `str::lines`, in the previous text
+/// (`old_index`) and the new text (`new_index`). Applying the steps in order turns the previous
+/// text's lines into the new text's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineDiff {
+    /// `len` lines are unchanged.
+    Equal {
+        old_index: usize,
+        new_index: usize,
+        len: usize,
+    },
+    /// `old_len` lines were removed.
+    Delete {
+        old_index: usize,
+        old_len: usize,
+        new_index: usize,
+    },
+    /// `new_len` lines were added.
+    Insert {
+        old_index: usize,
+        new_index: usize,
+        new_len: usize,
+    },
+    /// `old_len` lines were replaced with `new_len` different lines.
+    Replace {
+        old_index: usize,
+        old_len: usize,
+        new_index: usize,
+        new_len: usize,
+    },
+}
+
+impl From<DiffOp> for LineDiff {
+    fn from(op: DiffOp) -> Self {
+        match op {
+            DiffOp::Equal {
+                old_index,
+                new_index,
+                len,
+            } => LineDiff::Equal {
+                old_index,
+                new_index,
+                len,
+            },
+            DiffOp::Delete {
+                old_index,
+                old_len,
+                new_index,
+            } => LineDiff::Delete {
+                old_index,
+                old_len,
+                new_index,
+            },
+            DiffOp::Insert {
+                old_index,
+                new_index,
+                new_len,
+            } => LineDiff::Insert {
+                old_index,
+                new_index,
+                new_len,
+            },
+            DiffOp::Replace {
+                old_index,
+                old_len,
+                new_index,
+                new_len,
+            } => LineDiff::Replace {
+                old_index,
+                old_len,
+                new_index,
+                new_len,
+            },
+        }
+    }
+}
+
+/// Diffs the lines of `old` and `new` with Myers' algorithm.
+pub fn line_diff(old: &str, new: &str) -> Vec<LineDiff> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    similar::capture_diff_slices(Algorithm::Myers, &old_lines, &new_lines)
+        .into_iter()
+        .map(LineDiff::from)
+        .collect()
+}
+
+impl<E: Engine> Anchor<String, E> {
+    /// Returns an Anchor of the line diff between this Anchor's previous and current value, for
+    /// driving minimal updates of a view of the text. The diff is computed once per change to the
+    /// text. The first value is diffed against an empty string.
+    #[track_caller]
+    pub fn line_diff(&self) -> Anchor<Vec<LineDiff>, E> {
+        let state = self.map_mut(
+            (String::new(), Vec::new()),
+            |(prev, diff): &mut (String, Vec<LineDiff>), text: &String| {
+                if prev == text {
+                    return false;
+                }
+                *diff = line_diff(prev, text);
+                prev.clone_from(text);
+                true
+            },
+        );
+        state.refmap(|(_, diff)| diff)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LineDiff;
+    use crate::singlethread::*;
+
+    #[test]
+    fn diffs_successive_values() {
+        let mut engine = Engine::new();
+        let text = Var::new("one\ntwo".to_string());
+        let diff = text.watch().line_diff();
+        assert_eq!(
+            engine.get(&diff),
+            vec![LineDiff::Insert {
+                old_index: 0,
+                new_index: 0,
+                new_len: 2
+            }]
+        );
+
+        text.set("zero\none\ntwo".to_string());
+        assert_eq!(
+            engine.get(&diff),
+            vec![
+                LineDiff::Insert {
+                    old_index: 0,
+                    new_index: 0,
+                    new_len: 1
+                },
+                LineDiff::Equal {
+                    old_index: 0,
+                    new_index: 1,
+                    len: 2
+                },
+            ]
+        );
+
+        text.set("zero\ntwo".to_string());
+        assert_eq!(
+            engine.get(&diff),
+            vec![
+                LineDiff::Equal {
+                    old_index: 0,
+                    new_index: 0,
+                    len: 1
+                },
+                LineDiff::Delete {
+                    old_index: 1,
+                    old_len: 1,
+                    new_index: 1
+                },
+                LineDiff::Equal {
+                    old_index: 2,
+                    new_index: 1,
+                    len: 1
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use crate::singlethread::AnchorDebugInfo;
+use std::fmt;
+
+/// An error from an Anchors engine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// An Anchor depends on itself. Each Anchor in `path` depends on the next one, and the last
+    /// depends on the first.
+    CycleDetected { path: Vec<AnchorDebugInfo> },
+
+    /// Some Anchor's height in the graph would exceed the engine's maximum height, set with
+    /// `EngineBuilder::max_height`.
+    MaxHeightExceeded { max_height: usize },
+
+    /// The engine an Anchor belongs to has been dropped or cleared, or the Anchor a token refers
+    /// to has been freed.
+    Freed,
+
+    /// An Anchor was used with an engine other than the one it belongs to.
+    WrongEngine,
+
+    /// `anchor` panicked while recalculating, or read from some other Anchor that did. Only
+    /// returned by engines built with `EngineBuilder::catch_panics`.
+    Poisoned {
+        anchor: AnchorDebugInfo,
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::CycleDetected { path } => {
+                write!(f, "loop detected in anchors!")?;
+                for info in path {
+                    write!(f, "\n  {}", info)?;
+                }
+                Ok(())
+            }
+            Error::MaxHeightExceeded { max_height } => write!(
+                f,
+                "anchor graph exceeded its maximum height of {}",
+                max_height
+            ),
+            Error::Freed => write!(f, "anchor's engine has been dropped or cleared"),
+            Error::WrongEngine => write!(
+                f,
+                "anchor belongs to a different engine; use `Engine::enter` to create anchors in a specific engine"
+            ),
+            Error::Poisoned { anchor, message } => {
+                write!(f, "anchor {} panicked: {}", anchor, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
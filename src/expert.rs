@@ -4,12 +4,14 @@
 //! you should never need to import things from here. `singlethread` should re-export anything
 //! you need to use `anchors`!
 
-use std::marker::PhantomData;
-use std::panic::Location;
+use alloc::boxed::Box;
+use core::any::Any;
+use core::marker::PhantomData;
+use core::panic::Location;
 
 /// Indicates whether a value is ready for reading, and if it is, whether it's changed
 /// since the last read.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Poll {
     /// Indicates the polled value is ready for reading. Either this is the first read,
     /// or the value has changed since the last read.
@@ -24,8 +26,27 @@ pub enum Poll {
     Pending,
 }
 
+impl Poll {
+    /// Merges the results of polling two inputs: `Pending` if either is, otherwise `Updated` if
+    /// either is.
+    pub(crate) fn combine(self, other: Poll) -> Poll {
+        match (self, other) {
+            (Poll::Pending, _) | (_, Poll::Pending) => Poll::Pending,
+            (Poll::Updated, _) | (_, Poll::Updated) => Poll::Updated,
+            _ => Poll::Unchanged,
+        }
+    }
+}
+
 /// The main struct of the Anchors library. Represents a single value on the recomputation graph.
-pub struct Anchor<O, E: Engine + ?Sized> {
+///
+/// The engine defaults to `singlethread::Engine`, so code using the default engine can write
+/// `Anchor<O>` even when working with types from this module.
+pub struct Anchor<
+    O,
+    #[cfg(feature = "std")] E: Engine + ?Sized = crate::singlethread::Engine,
+    #[cfg(not(feature = "std"))] E: Engine + ?Sized,
+> {
     data: E::AnchorHandle,
     phantom: PhantomData<O>,
 }
@@ -43,6 +64,11 @@ impl<O, E: Engine> Anchor<O, E> {
         self.data.token()
     }
 
+    #[cfg(feature = "std")]
+    pub(crate) fn handle(&self) -> &E::AnchorHandle {
+        &self.data
+    }
+
     pub fn new_from_expert(data: E::AnchorHandle) -> Self {
         Self {
             data,
@@ -69,7 +95,7 @@ impl<O, E: Engine> Eq for Anchor<O, E> {}
 
 /// A reference to a particular `AnchorInner`. Each engine implements its own.
 pub trait AnchorHandle: Sized + Clone {
-    type Token: Sized + Clone + Copy + PartialEq + Eq + std::hash::Hash + std::fmt::Debug;
+    type Token: Sized + Clone + Copy + PartialEq + Eq + core::hash::Hash + core::fmt::Debug;
 
     /// Returns a Copyable, comparable, hashable ID corresponding to this AnchorHandle.
     /// Some engines may garbage collect an AnchorInner when no more AnchorHandles pointing
@@ -93,6 +119,19 @@ pub trait DirtyHandle {
     /// Indicates that the Anchor associated with this `DirtyHandle` may have a changed its output, and should
     /// be repolled.
     fn mark_dirty(&self);
+
+    /// Like `mark_dirty`, but also passes `payload` to the Anchor's `AnchorInner::dirty_payload`
+    /// before it's next polled, so it can find out what changed, such as a range of modified rows,
+    /// without recomputing everything.
+    ///
+    /// By default, the payload is dropped and this just calls `mark_dirty`.
+    fn mark_dirty_with<P: Any + Send>(&self, payload: P)
+    where
+        Self: Sized,
+    {
+        drop(payload);
+        self.mark_dirty();
+    }
 }
 
 /// The context passed to an `AnchorInner` when its `output` method is called.
@@ -130,11 +169,41 @@ pub trait UpdateContext {
         necessary: bool,
     ) -> Poll;
 
+    /// Requests every Anchor in `anchors`, as if by calling `request` on each. Unlike a loop that
+    /// stops at the first pending Anchor, all of them are requested, so every out-of-date input is
+    /// queued at once. Returns `Pending` if any of them is pending, otherwise `Updated` if any of
+    /// them changed, and otherwise `Unchanged`. Engines may override this to amortize bookkeeping
+    /// across the whole batch.
+    fn request_many<'a, O: 'static, I: IntoIterator<Item = &'a Anchor<O, Self::Engine>>>(
+        &mut self,
+        anchors: I,
+        necessary: bool,
+    ) -> Poll
+    where
+        Self::Engine: 'a,
+    {
+        let mut poll = Poll::Unchanged;
+        for anchor in anchors {
+            poll = poll.combine(self.request(anchor, necessary));
+        }
+        poll
+    }
+
     /// If `anchor` was previously passed to `request` and you no longer care about its output, you can
     /// pass it to `unrequest` so the engine will stop calling your `dirty` method when `anchor` changes.
     /// If `self` is necessary, this is also critical for ensuring `anchor` is no longer marked as necessary.
     fn unrequest<'out, O: 'static>(&mut self, anchor: &Anchor<O, Self::Engine>);
 
+    /// Returns the tokens of the inputs that have been passed to this `AnchorInner`'s `dirty`
+    /// method since `poll_updated` last returned something other than `Pending`, in no particular
+    /// order and without duplicates. Folds over large collections can use this to recompute only
+    /// the affected inputs. Returns `None` if the engine doesn't track this.
+    fn dirtied_children(
+        &self,
+    ) -> Option<&[<<Self::Engine as Engine>::AnchorHandle as AnchorHandle>::Token]> {
+        None
+    }
+
     /// Returns a new dirty handle, used for marking that `self`'s output may have changed through some
     /// non incremental means. For instance, perhaps this `AnchorInner`s value represents the current time, or
     /// it's a `Var` that has a setter function.
@@ -151,6 +220,11 @@ pub trait AnchorInner<E: Engine + ?Sized> {
     /// it next time `poll_updated` is called.
     fn dirty(&mut self, child: &<E::AnchorHandle as AnchorHandle>::Token);
 
+    /// Called by the engine with each payload passed to `DirtyHandle::mark_dirty_with` for this
+    /// `AnchorInner`'s dirty handle, in the order they were sent, before it's next polled. Payloads
+    /// are ignored by default.
+    fn dirty_payload(&mut self, _payload: Box<dyn Any>) {}
+
     /// Called by the engine when it wants to know if this value has changed or
     /// not. If some requested value from `ctx` is `Pending`, this method should
     /// return `Poll::Pending`; otherwise it must finish recalculation and report
@@ -168,6 +242,20 @@ pub trait AnchorInner<E: Engine + ?Sized> {
     where
         'slf: 'out;
 
+    /// An optional function returning the current output value as an `Rc<Self::Output>`, for
+    /// `AnchorInner`s that already store their output in an `Rc`. Engines may use this to hand
+    /// out the output without cloning it. Follows the same rules as `output`.
+    fn output_rc(&self) -> Option<&dyn Any> {
+        None
+    }
+
+    /// An optional function that drops the cached output, for `AnchorInner`s that can recalculate
+    /// it from their inputs alone. Returns false if nothing was dropped. After this returns true,
+    /// `output` won't be called until `poll_updated` reports the value is ready again.
+    fn evict_output(&mut self) -> bool {
+        false
+    }
+
     /// An optional function to report the track_caller-derived callsite where
     /// this Anchor was created. Useful for debugging purposes.
     fn debug_location(&self) -> Option<(&'static str, &'static Location<'static>)> {
@@ -176,13 +264,17 @@ pub trait AnchorInner<E: Engine + ?Sized> {
 }
 
 mod ext;
+pub mod external;
 pub use ext::cutoff;
+pub use ext::fused;
 pub use ext::map;
 pub use ext::map_mut;
 pub use ext::refmap;
 pub use ext::then;
 pub use ext::MultiAnchor;
 pub(crate) mod constant;
+mod lens;
 mod var;
 pub use constant::Constant;
+pub use lens::Lens;
 pub use var::Var;
@@ -1,7 +1,7 @@
 use crate::expert::{
     Anchor, AnchorHandle, AnchorInner, Engine, OutputContext, Poll, UpdateContext,
 };
-use std::panic::Location;
+use core::panic::Location;
 
 /// An Anchor type for immutable values.
 pub struct Constant<T> {
@@ -1,7 +1,10 @@
 use super::{Anchor, AnchorInner, Engine};
-use std::panic::Location;
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use core::panic::Location;
 
 pub mod cutoff;
+pub mod fused;
 pub mod map;
 pub mod map_mut;
 pub mod refmap;
@@ -88,6 +91,37 @@ where
         })
     }
 
+    /// Like `map`, but wraps the output in an `Rc`, so retrieving it with `Engine::get` doesn't
+    /// clone the value itself. Useful for expensive values.
+    #[track_caller]
+    pub fn map_rc<F, Out>(&self, mut f: F) -> Anchor<Rc<Out>, E>
+    where
+        Out: PartialEq + 'static,
+        F: FnMut(&O1) -> Out + 'static,
+    {
+        self.map(move |val: &O1| Rc::new(f(val)))
+    }
+
+    /// Starts a chain of functions that `FusedMap::build` turns into a single Anchor, rather than
+    /// one Anchor per function like chained calls to `map`. Long chains of cheap maps spend most
+    /// of their time on graph bookkeeping, which fusing them avoids.
+    ///
+    /// ```
+    /// use anchors::singlethread::*;
+    /// let mut engine = Engine::new();
+    /// let a = Anchor::constant(1);
+    /// let b = a.map_fused(|a| a + 1).map(|a| a * 10).build();
+    /// assert_eq!(20, engine.get(&b));
+    /// ```
+    #[track_caller]
+    pub fn map_fused<F, Out>(&self, f: F) -> fused::FusedMap<O1, Out, E>
+    where
+        Out: 'static,
+        F: FnMut(&O1) -> Out + 'static,
+    {
+        fused::FusedMap::new(self, f)
+    }
+
     #[track_caller]
     pub fn map_mut<F, Out>(&self, initial: Out, f: F) -> Anchor<Out, E>
     where
@@ -150,6 +184,41 @@ where
         })
     }
 
+    /// Like `then`, but remembers the Anchor `f` returns for each key that `key` derives from the
+    /// input. When the input changes to one with a key seen before, the Anchor built for that key
+    /// is reused rather than calling `f` again, so its subgraph keeps its cached state. Every
+    /// Anchor built is kept for as long as the returned Anchor is alive.
+    ///
+    /// ```
+    /// use anchors::singlethread::*;
+    /// let mut engine = Engine::new();
+    /// let tab = Var::new("home");
+    /// let mut built = 0;
+    /// let page = tab.watch().then_memo(
+    ///     |tab| *tab,
+    ///     move |tab| {
+    ///         built += 1;
+    ///         Anchor::constant(format!("page {} for {}", built, tab))
+    ///     },
+    /// );
+    /// assert_eq!(engine.get(&page), "page 1 for home");
+    /// tab.set("settings");
+    /// assert_eq!(engine.get(&page), "page 2 for settings");
+    /// tab.set("home");
+    /// assert_eq!(engine.get(&page), "page 1 for home");
+    /// ```
+    #[track_caller]
+    pub fn then_memo<K, KF, F, Out>(&self, mut key: KF, mut f: F) -> Anchor<Out, E>
+    where
+        K: Ord + 'static,
+        KF: FnMut(&O1) -> K + 'static,
+        F: FnMut(&O1) -> Anchor<Out, E> + 'static,
+        Out: 'static,
+    {
+        let mut built = BTreeMap::new();
+        self.then(move |input: &O1| built.entry(key(input)).or_insert_with(|| f(input)).clone())
+    }
+
     /// Creates an Anchor that maps some input reference to some output reference.
     /// Performance is critical here: `f` will always be recalled any time any downstream node
     /// requests the value of this Anchor, *not* just when an input value changes.
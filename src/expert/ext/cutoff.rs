@@ -1,7 +1,7 @@
 use crate::expert::{
     Anchor, AnchorHandle, AnchorInner, Engine, OutputContext, Poll, UpdateContext,
 };
-use std::panic::Location;
+use core::panic::Location;
 
 pub struct Cutoff<A, F> {
     pub(super) f: F,
@@ -0,0 +1,53 @@
+use crate::expert::{map::Map, Anchor, AnchorInner, Engine};
+use alloc::boxed::Box;
+use core::panic::Location;
+
+/// A chain of functions applied to a single Anchor's output, created with `Anchor::map_fused`.
+///
+/// Each call to `map` composes another function onto the chain, and `build` creates a single
+/// Anchor that runs the whole chain. This avoids paying for a graph node per function, at the
+/// cost of always rerunning the whole chain: unlike separate `map`s, intermediate values aren't
+/// stored or compared, so only the final output cuts off recalculation of later Anchors.
+pub struct FusedMap<In, Out, E: Engine> {
+    anchor: Anchor<In, E>,
+    f: Box<dyn FnMut(&In) -> Out>,
+    location: &'static Location<'static>,
+}
+
+impl<In: 'static, Out: 'static, E: Engine> FusedMap<In, Out, E> {
+    #[track_caller]
+    pub(super) fn new<F: FnMut(&In) -> Out + 'static>(anchor: &Anchor<In, E>, f: F) -> Self {
+        Self {
+            anchor: anchor.clone(),
+            f: Box::new(f),
+            location: Location::caller(),
+        }
+    }
+
+    /// Composes `g` onto the end of the chain.
+    pub fn map<G, Next>(self, mut g: G) -> FusedMap<In, Next, E>
+    where
+        G: FnMut(&Out) -> Next + 'static,
+    {
+        let mut f = self.f;
+        FusedMap {
+            anchor: self.anchor,
+            f: Box::new(move |val: &In| g(&f(val))),
+            location: self.location,
+        }
+    }
+
+    /// Creates an Anchor that runs the whole chain whenever the input changes.
+    pub fn build(self) -> Anchor<Out, E>
+    where
+        Map<(Anchor<In, E>,), Box<dyn FnMut(&In) -> Out>, Out>: AnchorInner<E, Output = Out>,
+    {
+        E::mount(Map {
+            anchors: (self.anchor,),
+            f: self.f,
+            output: None,
+            output_stale: true,
+            location: self.location,
+        })
+    }
+}
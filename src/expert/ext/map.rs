@@ -1,7 +1,7 @@
 use crate::expert::{
     Anchor, AnchorHandle, AnchorInner, Engine, OutputContext, Poll, UpdateContext,
 };
-use std::panic::Location;
+use core::panic::Location;
 
 pub struct Map<A, F, Out> {
     pub(super) f: F,
@@ -79,6 +79,10 @@ macro_rules! impl_tuple_map {
                     .expect("output called on Map before value was calculated")
             }
 
+            fn evict_output(&mut self) -> bool {
+                self.output.take().is_some()
+            }
+
             fn debug_location(&self) -> Option<(&'static str, &'static Location<'static>)> {
                 Some(("map", self.location))
             }
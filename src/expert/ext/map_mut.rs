@@ -1,5 +1,5 @@
 use crate::expert::{Anchor, AnchorInner, Engine, OutputContext, Poll, UpdateContext};
-use std::panic::Location;
+use core::panic::Location;
 
 pub struct MapMut<A, F, Out> {
     pub(super) f: F,
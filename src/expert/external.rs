@@ -0,0 +1,106 @@
+//! Helpers for Anchors whose value comes from outside the graph, like clocks, channels, or
+//! device state.
+
+use super::{Anchor, AnchorHandle, AnchorInner, Engine, OutputContext, Poll, UpdateContext};
+use alloc::boxed::Box;
+use core::panic::Location;
+
+/// An `AnchorInner` that reads its value by calling a closure, for state the engine can't see
+/// change on its own. The closure is called on the first poll, and again each time the Anchor is
+/// marked dirty, which the engine does at most once per stabilization. If it returns a value equal
+/// to the previous one, dependent Anchors aren't recalculated.
+///
+/// Something outside the graph has to notice changes and mark the Anchor dirty. Pass a callback
+/// to `on_dirty_handle` to receive the Anchor's `DirtyHandle` when it's first polled, and hand it
+/// to whatever watches the external resource:
+///
+/// ```
+/// use anchors::expert::{external::Polled, DirtyHandle};
+/// use anchors::singlethread::Engine;
+/// use std::cell::{Cell, RefCell};
+/// use std::rc::Rc;
+///
+/// let mut engine = Engine::new();
+/// let temperature = Rc::new(Cell::new(20));
+/// let handle = Rc::new(RefCell::new(None));
+/// let anchor = Polled::new({
+///     let temperature = temperature.clone();
+///     move || temperature.get()
+/// })
+/// .on_dirty_handle({
+///     let handle = handle.clone();
+///     move |dirty_handle| *handle.borrow_mut() = Some(dirty_handle)
+/// })
+/// .mount();
+/// assert_eq!(engine.get(&anchor), 20);
+///
+/// temperature.set(25);
+/// handle.borrow().as_ref().unwrap().mark_dirty();
+/// assert_eq!(engine.get(&anchor), 25);
+/// ```
+pub struct Polled<T, E: Engine + ?Sized> {
+    poll: Box<dyn FnMut() -> T>,
+    register: Option<Box<dyn FnOnce(E::DirtyHandle)>>,
+    output: Option<T>,
+    location: &'static Location<'static>,
+}
+
+impl<T: PartialEq + 'static, E: Engine> Polled<T, E> {
+    #[track_caller]
+    pub fn new<F: FnMut() -> T + 'static>(poll: F) -> Self {
+        Self {
+            poll: Box::new(poll),
+            register: None,
+            output: None,
+            location: Location::caller(),
+        }
+    }
+
+    /// Sets a callback that receives this Anchor's `DirtyHandle` the first time it's polled.
+    pub fn on_dirty_handle<F: FnOnce(E::DirtyHandle) + 'static>(mut self, register: F) -> Self {
+        self.register = Some(Box::new(register));
+        self
+    }
+
+    /// Mounts this into the engine, returning the Anchor.
+    pub fn mount(self) -> Anchor<T, E> {
+        E::mount(self)
+    }
+}
+
+impl<T: PartialEq + 'static, E: Engine> AnchorInner<E> for Polled<T, E> {
+    type Output = T;
+
+    fn dirty(&mut self, _child: &<E::AnchorHandle as AnchorHandle>::Token) {
+        panic!("somehow an input was dirtied on Polled; it never has any inputs to dirty")
+    }
+
+    fn poll_updated<G: UpdateContext<Engine = E>>(&mut self, ctx: &mut G) -> Poll {
+        if let Some(register) = self.register.take() {
+            register(ctx.dirty_handle());
+        }
+        let val = (self.poll)();
+        if self.output.as_ref() == Some(&val) {
+            Poll::Unchanged
+        } else {
+            self.output = Some(val);
+            Poll::Updated
+        }
+    }
+
+    fn output<'slf, 'out, G: OutputContext<'out, Engine = E>>(
+        &'slf self,
+        _ctx: &mut G,
+    ) -> &'out Self::Output
+    where
+        'slf: 'out,
+    {
+        self.output
+            .as_ref()
+            .expect("output called on Polled before value was calculated")
+    }
+
+    fn debug_location(&self) -> Option<(&'static str, &'static Location<'static>)> {
+        Some(("polled", self.location))
+    }
+}
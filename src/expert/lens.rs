@@ -0,0 +1,101 @@
+use super::{Anchor, Engine, Var};
+use alloc::rc::Rc;
+
+/// A two-way binding to part of a `Var`'s value, created with `Var::lens`. Reading goes through
+/// `watch`, an Anchor of the field; writing goes through `set` or `modify`, which update the field
+/// in place and mark the `Var` as changed. Useful for GUI toolkits that bind widgets through
+/// lenses. Like `Anchor`, the engine defaults to `singlethread::Engine`.
+pub struct Lens<
+    T,
+    U,
+    #[cfg(feature = "std")] E: Engine = crate::singlethread::Engine,
+    #[cfg(not(feature = "std"))] E: Engine,
+> {
+    var: Var<T, E>,
+    get: Rc<dyn Fn(&T) -> &U>,
+    get_mut: Rc<dyn Fn(&mut T) -> &mut U>,
+    anchor: Anchor<U, E>,
+}
+
+impl<T, U, E: Engine> Clone for Lens<T, U, E> {
+    fn clone(&self) -> Self {
+        Self {
+            var: self.var.clone(),
+            get: self.get.clone(),
+            get_mut: self.get_mut.clone(),
+            anchor: self.anchor.clone(),
+        }
+    }
+}
+
+impl<T: Clone + 'static, U: 'static, E: Engine> Lens<T, U, E> {
+    pub(super) fn new<G, M>(var: &Var<T, E>, get: G, get_mut: M) -> Self
+    where
+        G: Fn(&T) -> &U + 'static,
+        M: Fn(&mut T) -> &mut U + 'static,
+    {
+        let get: Rc<dyn Fn(&T) -> &U> = Rc::new(get);
+        let anchor = {
+            let get = get.clone();
+            var.watch().refmap(move |val: &T| get(val))
+        };
+        Self {
+            var: var.clone(),
+            get,
+            get_mut: Rc::new(get_mut),
+            anchor,
+        }
+    }
+
+    /// Returns an Anchor of the field. It's recalculated whenever the `Var` changes, even if this
+    /// field didn't.
+    pub fn watch(&self) -> Anchor<U, E> {
+        self.anchor.clone()
+    }
+
+    /// Passes the field's current value to `f`, without going through the engine.
+    pub fn with<R, F: FnOnce(&U) -> R>(&self, f: F) -> R {
+        f((self.get)(&self.var.get()))
+    }
+
+    /// Returns a clone of the field's current value, without going through the engine.
+    pub fn get(&self) -> U
+    where
+        U: Clone,
+    {
+        self.with(U::clone)
+    }
+
+    /// Mutates the field in place, and marks the `Var` as changed, like `Var::modify`. The `Var`
+    /// is borrowed while `f` runs, so `f` mustn't read or set the `Var` or this lens.
+    pub fn modify<F: FnOnce(&mut U)>(&self, f: F) {
+        let get_mut = &self.get_mut;
+        self.var.modify(|val| f(get_mut(val)))
+    }
+
+    /// Replaces the field's value, and marks the `Var` as changed.
+    pub fn set(&self, val: U) {
+        self.modify(|field| *field = val)
+    }
+
+    /// Returns a closure that calls `set`, for toolkits that take a setter callback.
+    pub fn setter(&self) -> impl Fn(U) {
+        let lens = self.clone();
+        move |val| lens.set(val)
+    }
+
+    /// Narrows this lens to part of the field, such as a field of a nested struct.
+    pub fn lens<V: 'static, G, M>(&self, get: G, get_mut: M) -> Lens<T, V, E>
+    where
+        G: Fn(&U) -> &V + 'static,
+        M: Fn(&mut U) -> &mut V + 'static,
+    {
+        let outer_get = self.get.clone();
+        let outer_get_mut = self.get_mut.clone();
+        Lens::new(
+            &self.var,
+            move |val| get(outer_get(val)),
+            move |val| get_mut(outer_get_mut(val)),
+        )
+    }
+}
@@ -1,8 +1,9 @@
 use super::{
-    Anchor, AnchorHandle, AnchorInner, DirtyHandle, Engine, OutputContext, Poll, UpdateContext,
+    Anchor, AnchorHandle, AnchorInner, DirtyHandle, Engine, Lens, OutputContext, Poll,
+    UpdateContext,
 };
-use std::cell::RefCell;
-use std::rc::Rc;
+use alloc::rc::Rc;
+use core::cell::RefCell;
 
 /// An Anchor type for values that are mutated by calling a setter function from outside of the Anchors recomputation graph.
 struct VarAnchor<T, E: Engine> {
@@ -12,13 +13,18 @@ struct VarAnchor<T, E: Engine> {
 
 #[derive(Clone)]
 struct VarShared<T, E: Engine> {
-    dirty_handle: Option<E::DirtyHandle>,
+    dirty_handle: Option<Rc<E::DirtyHandle>>,
     val: Rc<T>,
     value_changed: bool,
 }
 
-/// A setter that can update values inside an associated `VarAnchor`.
-pub struct Var<T, E: Engine> {
+/// A setter that can update values inside an associated `VarAnchor`. Like `Anchor`, the engine
+/// defaults to `singlethread::Engine`.
+pub struct Var<
+    T,
+    #[cfg(feature = "std")] E: Engine = crate::singlethread::Engine,
+    #[cfg(not(feature = "std"))] E: Engine,
+> {
     inner: Rc<RefCell<VarShared<T, E>>>,
     anchor: Anchor<T, E>,
 }
@@ -50,12 +56,19 @@ impl<T: 'static, E: Engine> Var<T, E> {
     /// Updates the value inside the VarAnchor, and indicates to the recomputation graph that
     /// the value has changed.
     pub fn set(&self, val: T) {
-        let mut inner = self.inner.borrow_mut();
-        inner.val = Rc::new(val);
-        if let Some(waker) = &inner.dirty_handle {
-            waker.mark_dirty();
-        }
-        inner.value_changed = true;
+        self.set_rc(Rc::new(val))
+    }
+
+    /// Like `set`, but takes a value that's already been wrapped in an `Rc`, such as one
+    /// previously returned by `get`.
+    pub fn set_rc(&self, val: Rc<T>) {
+        let old = {
+            let mut inner = self.inner.borrow_mut();
+            core::mem::replace(&mut inner.val, val)
+        };
+        self.mark_changed();
+        // dropped outside the borrow too, since its destructor may read this Var
+        drop(old);
     }
 
     /// Retrieves the last value set
@@ -68,6 +81,80 @@ impl<T: 'static, E: Engine> Var<T, E> {
     }
 }
 
+impl<T: Clone + 'static, E: Engine> Var<T, E> {
+    /// Mutates the value inside the VarAnchor in place, then indicates to the recomputation graph
+    /// that the value has changed. The value is only cloned if something else still references
+    /// it, such as the recomputation graph after it has seen the current value, or an `Rc`
+    /// returned by `get`. Repeated modifications between stabilizations only clone it once. The
+    /// Var is borrowed while `f` runs, so `f` mustn't read or set it.
+    pub fn modify<F: FnOnce(&mut T)>(&self, f: F) {
+        {
+            let mut inner = self.inner.borrow_mut();
+            f(Rc::make_mut(&mut inner.val));
+        }
+        self.mark_changed();
+    }
+
+    /// Replaces the value inside the VarAnchor, returning the previous value. It's moved out if
+    /// nothing else still references it, such as when the recomputation graph hasn't seen it yet,
+    /// and cloned otherwise. The Var isn't borrowed while the previous value is cloned, so
+    /// `T::clone` may read it.
+    pub fn replace(&self, val: T) -> T {
+        let old = {
+            let mut inner = self.inner.borrow_mut();
+            core::mem::replace(&mut inner.val, Rc::new(val))
+        };
+        self.mark_changed();
+        Rc::try_unwrap(old).unwrap_or_else(|old| T::clone(&old))
+    }
+
+    /// Replaces the value inside the VarAnchor with its default, returning the previous value,
+    /// like `replace`.
+    pub fn take(&self) -> T
+    where
+        T: Default,
+    {
+        self.replace(T::default())
+    }
+
+    /// Returns a `Lens` reading and writing the part of the value selected by `get` and
+    /// `get_mut`, such as one of its fields.
+    pub fn lens<U: 'static, G, M>(&self, get: G, get_mut: M) -> Lens<T, U, E>
+    where
+        G: Fn(&T) -> &U + 'static,
+        M: Fn(&mut T) -> &mut U + 'static,
+    {
+        Lens::new(self, get, get_mut)
+    }
+}
+
+impl<T: PartialEq + 'static, E: Engine> Var<T, E> {
+    /// Like `set`, but does nothing if `val` is equal to the current value, so dependent Anchors
+    /// aren't recalculated. Returns true if the value was changed.
+    pub fn set_if_changed(&self, val: T) -> bool {
+        if *self.inner.borrow().val == val {
+            return false;
+        }
+        self.set(val);
+        true
+    }
+}
+
+impl<T, E: Engine> Var<T, E> {
+    /// flags the value as changed and wakes the engine. The Var mustn't be borrowed, since waking
+    /// may run callbacks that read it.
+    fn mark_changed(&self) {
+        let waker = {
+            let mut inner = self.inner.borrow_mut();
+            inner.value_changed = true;
+            inner.dirty_handle.clone()
+        };
+        if let Some(waker) = waker {
+            waker.mark_dirty();
+        }
+    }
+}
+
 impl<E: Engine, T: 'static> AnchorInner<E> for VarAnchor<T, E> {
     type Output = T;
     fn dirty(&mut self, _edge: &<E::AnchorHandle as AnchorHandle>::Token) {
@@ -78,7 +165,7 @@ impl<E: Engine, T: 'static> AnchorInner<E> for VarAnchor<T, E> {
         let mut inner = self.inner.borrow_mut();
         let first_update = inner.dirty_handle.is_none();
         if first_update {
-            inner.dirty_handle = Some(ctx.dirty_handle());
+            inner.dirty_handle = Some(Rc::new(ctx.dirty_handle()));
         }
         let res = if inner.value_changed {
             self.val = inner.val.clone();
@@ -99,4 +186,8 @@ impl<E: Engine, T: 'static> AnchorInner<E> for VarAnchor<T, E> {
     {
         &self.val
     }
+
+    fn output_rc(&self) -> Option<&dyn core::any::Any> {
+        Some(&self.val)
+    }
 }
@@ -0,0 +1,92 @@
+//! Anchors projecting parts of a JSON document. Requires the `json` feature.
+//!
+//! ```
+//! use anchors::singlethread::*;
+//! use serde_json::json;
+//!
+//! let mut engine = Engine::new();
+//! let doc = Var::new(json!({"user": {"name": "ada", "logins": 3}, "motd": "hi"}));
+//! let name = doc.watch().json_pointer("/user/name");
+//! let logins = doc.watch().deserialize_at::<u32>("/user/logins");
+//! assert_eq!(engine.get(&name), Some(json!("ada")));
+//! assert_eq!(engine.get(&logins), Ok(3));
+//!
+//! // anything depending on `name` or `logins` isn't recalculated, since their subtrees didn't
+//! // change
+//! doc.set(json!({"user": {"name": "ada", "logins": 3}, "motd": "bye"}));
+//! ```
+
+use crate::expert::{Anchor, Engine};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+impl<E: Engine> Anchor<Value, E> {
+    /// Returns an Anchor of the subtree at `pointer`, a JSON Pointer like `/a/b/0`, or `None` if
+    /// there's nothing there. It's only marked as updated when that subtree changes, so dependents
+    /// aren't recalculated when other parts of the document do.
+    #[track_caller]
+    pub fn json_pointer(&self, pointer: &str) -> Anchor<Option<Value>, E> {
+        let pointer = pointer.to_string();
+        self.map_mut(None, move |out: &mut Option<Value>, doc: &Value| {
+            let subtree = doc.pointer(&pointer);
+            if out.as_ref() == subtree {
+                false
+            } else {
+                *out = subtree.cloned();
+                true
+            }
+        })
+    }
+
+    /// Returns an Anchor deserializing the subtree at `pointer` into a `T`, or an error message
+    /// if that fails. A missing subtree is deserialized from `null`, so `T` can be an `Option` to
+    /// allow it. Like `json_pointer`, it's only recalculated when that subtree changes.
+    #[track_caller]
+    pub fn deserialize_at<T: DeserializeOwned + PartialEq + 'static>(
+        &self,
+        pointer: &str,
+    ) -> Anchor<Result<T, String>, E> {
+        self.json_pointer(pointer).map(|subtree: &Option<Value>| {
+            let subtree = subtree.as_ref().unwrap_or(&Value::Null);
+            T::deserialize(subtree).map_err(|err| err.to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::singlethread::*;
+    use serde_json::json;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn projections_skip_unrelated_changes() {
+        let mut engine = Engine::new();
+        let doc = Var::new(json!({"items": [{"id": 1}, {"id": 2}], "title": "a"}));
+        let calcs = Rc::new(Cell::new(0));
+        let second = {
+            let calcs = calcs.clone();
+            doc.watch().json_pointer("/items/1").map(move |item| {
+                calcs.set(calcs.get() + 1);
+                item.clone()
+            })
+        };
+        let title = doc.watch().deserialize_at::<String>("/title");
+        let missing = doc.watch().deserialize_at::<Option<u32>>("/count");
+        engine.mark_observed(&second);
+        assert_eq!(engine.get(&second), Some(json!({"id": 2})));
+        assert_eq!(engine.get(&title), Ok("a".to_string()));
+        assert_eq!(engine.get(&missing), Ok(None));
+
+        doc.set(json!({"items": [{"id": 1}, {"id": 2}], "title": "b"}));
+        assert_eq!(engine.get(&second), Some(json!({"id": 2})));
+        assert_eq!(engine.get(&title), Ok("b".to_string()));
+        assert_eq!(calcs.get(), 1);
+
+        doc.set(json!({"items": [{"id": 1}], "title": 5}));
+        assert_eq!(engine.get(&second), None);
+        assert_eq!(calcs.get(), 2);
+        assert!(engine.get(&title).is_err());
+    }
+}
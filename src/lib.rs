@@ -1,5 +1,25 @@
-#![feature(negative_impls)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+#[cfg(feature = "ndarray")]
+pub mod array;
+#[cfg(feature = "std")]
 pub mod collections;
+#[cfg(feature = "config")]
+pub mod config;
+#[cfg(feature = "diff")]
+pub mod diff;
+#[cfg(feature = "std")]
+mod error;
 pub mod expert;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "rayon")]
+pub mod par;
+#[cfg(feature = "std")]
 pub mod singlethread;
+pub mod toolkit;
+
+#[cfg(feature = "std")]
+pub use error::Error;
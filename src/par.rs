@@ -0,0 +1,84 @@
+//! Data-parallel maps inside a single Anchor, backed by rayon. Requires the `rayon` feature.
+//!
+//! The graph itself stays single-threaded: the engine polls the Anchor as usual, and only the
+//! body of the map is split across rayon's thread pool.
+//!
+//! ```
+//! use anchors::singlethread::*;
+//!
+//! let mut engine = Engine::new();
+//! let nums = Var::new((0..10_000u64).collect::<Vec<_>>());
+//! let squares = nums.watch().par_map(|n| n * n);
+//! assert_eq!(engine.get(&squares)[100], 10_000);
+//! ```
+
+use crate::expert::{Anchor, Engine};
+use rayon::prelude::*;
+
+impl<T: Sync + 'static, E: Engine> Anchor<Vec<T>, E> {
+    /// Returns an Anchor mapping each element with `f`, in parallel on rayon's global thread pool.
+    /// Worth it when the Vec is large or `f` is expensive; otherwise the overhead of splitting the
+    /// work outweighs it, and `map` is faster. Like `map`, dependents aren't recalculated if the
+    /// output is equal to the previous one.
+    #[track_caller]
+    pub fn par_map<F, Out>(&self, f: F) -> Anchor<Vec<Out>, E>
+    where
+        F: Fn(&T) -> Out + Send + Sync + 'static,
+        Out: Send + PartialEq + 'static,
+    {
+        self.map_mut(Vec::new(), move |out: &mut Vec<Out>, input: &Vec<T>| {
+            let new: Vec<Out> = input.par_iter().map(&f).collect();
+            if *out == new {
+                false
+            } else {
+                *out = new;
+                true
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::singlethread::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn par_map_matches_map() {
+        let mut engine = Engine::new();
+        let nums = Var::new((0..1000u32).collect::<Vec<_>>());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let parity = {
+            let calls = calls.clone();
+            nums.watch().par_map(move |n| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                n % 2
+            })
+        };
+        let sums = Rc::new(Cell::new(0));
+        let ones = {
+            let sums = sums.clone();
+            parity.map(move |parity| {
+                sums.set(sums.get() + 1);
+                parity.iter().sum::<u32>()
+            })
+        };
+        let expected = nums
+            .watch()
+            .map(|nums| nums.iter().map(|n| n % 2).collect::<Vec<_>>());
+        engine.mark_observed(&ones);
+        assert_eq!(engine.get(&parity), engine.get(&expected));
+        assert_eq!(engine.get(&ones), 500);
+        assert_eq!(calls.load(Ordering::SeqCst), 1000);
+
+        // same parities, so `ones` is cut off
+        nums.set((2..1002u32).collect());
+        engine.stabilize();
+        assert_eq!(calls.load(Ordering::SeqCst), 2000);
+        assert_eq!(sums.get(), 1);
+        assert_eq!(engine.get(&parity), engine.get(&expected));
+    }
+}
@@ -6,16 +6,60 @@
 //! Air, likely somewhat more if single node has a significant number of parents or children. Hopefully
 //! this will significantly improve over the coming months.
 
+mod builder;
+mod clock;
+#[cfg(feature = "debug-server")]
+mod debug_server;
+#[cfg(feature = "egui")]
+pub mod egui;
+#[cfg(feature = "feed")]
+pub mod feed;
+#[cfg(feature = "notify")]
+mod fs;
 mod generation;
+mod get_many;
 mod graph2;
+#[cfg(feature = "history")]
+mod history;
+#[cfg(feature = "iced")]
+pub mod iced;
+pub mod kv;
+#[cfg(feature = "snapshot")]
+mod memo;
+mod memo_fn;
+mod record;
+mod shared_var;
+#[cfg(feature = "futures-signals")]
+pub mod signals;
+#[cfg(feature = "snapshot")]
+mod snapshot;
+#[cfg(feature = "tokio")]
+pub mod timer;
+mod undo;
+#[cfg(feature = "web")]
+pub mod web;
 
 #[cfg(test)]
 mod test;
 
-use graph2::{Graph2, Graph2Guard, NodeGuard, NodeKey, RecalcState};
+use builder::EqualityCutoff;
+use graph2::{Graph2, Graph2Guard, InlineOutput, NodeGuard, NodeKey};
 
+pub use builder::{EngineBuilder, GcPolicy, Strategy};
+#[cfg(feature = "debug-server")]
+pub use debug_server::DebugServer;
+pub use get_many::AnchorTuple;
 pub use graph2::AnchorHandle;
 pub use graph2::NodeKey as AnchorToken;
+pub use graph2::RecalcState;
+#[cfg(feature = "snapshot")]
+pub use memo::{MemoSnapshot, PersistentMemo};
+pub use memo_fn::MemoFn;
+pub use record::{RecordedSet, Recorder, Replayer};
+pub use shared_var::{bridge, SharedVar, SharedVarSetter};
+#[cfg(feature = "snapshot")]
+pub use snapshot::VarSnapshot;
+pub use undo::UndoGroup;
 
 /// The main struct of the Anchors library. Represents a single value on the singlthread recomputation graph.
 ///
@@ -26,15 +70,29 @@ pub type Anchor<T> = crate::expert::Anchor<T, Engine>;
 /// An Anchor input that can be mutated by calling a setter function from outside of the Anchors recomputation graph.
 pub type Var<T> = crate::expert::Var<T, Engine>;
 
+pub use crate::expert::Lens;
 pub use crate::expert::MultiAnchor;
 
 use crate::expert::{AnchorInner, OutputContext, Poll, UpdateContext};
+use crate::Error;
 
 use generation::Generation;
-use std::any::Any;
-use std::cell::RefCell;
-use std::panic::Location;
-use std::rc::Rc;
+use std::any::{Any, TypeId};
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::panic::{self, AssertUnwindSafe, Location};
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+// std's `Instant` panics in browsers, so there the time comes from `performance.now()` instead
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::Instant;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use web_time::Instant;
 
 thread_local! {
     static DEFAULT_MOUNTER: RefCell<Option<Mounter>> = RefCell::new(None);
@@ -62,14 +120,95 @@ pub enum ObservedState {
 pub struct Engine {
     // TODO store Nodes on heap directly?? maybe try for Rc<RefCell<SlotMap>> now
     graph: Rc<Graph2>,
-    dirty_marks: Rc<RefCell<Vec<NodeKey>>>,
+    dirty_marks: Rc<DirtyMarks>,
+    send_dirty_marks: Arc<SendDirtyMarks>,
+
+    // callbacks registered with `subscribe`, in registration order
+    subscriptions: Vec<SubscriptionEntry>,
+    next_subscription: u64,
+
+    // hooks registered with `add_pre_stabilize_hook` and `add_post_stabilize_hook`
+    pre_stabilize_hooks: Vec<PreStabilizeHook>,
+    post_stabilize_hooks: Vec<PostStabilizeHook>,
+    // hooks that are removed once their flag is set; see `add_removable_post_stabilize_hook`
+    removable_hooks: Vec<(Arc<AtomicBool>, PostStabilizeHook)>,
+
+    // set when recalculating a node fails, so the stabilization can be stopped
+    failure: RefCell<Option<Error>>,
+
+    // a stabilization started by `stabilize_budgeted` that hasn't completed yet
+    partial_stabilize: Option<StabilizeReport>,
+
+    // observed nodes updated since the last call to `take_updated_observed`
+    updated_observed: Vec<NodeKey>,
+
+    // vars registered with `register_var`, by name
+    #[cfg(feature = "snapshot")]
+    snapshot_vars: Vec<(String, Box<dyn snapshot::SnapshotVar>)>,
+
+    // anchors passed to `track_history`, with their recorded values
+    #[cfg(feature = "history")]
+    histories: history::Histories,
+
+    // clock anchors created with `now`, which are marked dirty on every stabilize
+    clocks: Vec<(Duration, Anchor<Instant>)>,
+
+    // constants created with `constant_interned`
+    interned_constants: RefCell<InternedConstants>,
+
+    // number of stabilizations unobserved nodes stay necessary for; see `set_keep_alive`
+    keep_alive: u64,
+    // unobserved nodes that are being kept necessary until their `keep_alive_until` generation
+    kept_alive: Vec<NodeKey>,
+
+    // maximum number of unnecessary cached outputs; see `set_cached_output_limit`
+    cached_output_limit: Option<usize>,
+    // nodes recalculated since the limit was set, which may hold evictable outputs
+    cached_outputs: RefCell<Vec<NodeKey>>,
+
+    // whether to measure the time taken by each recalculation; see `hotspots`
+    time_recalculations: bool,
+
+    // whether to poison nodes that panic instead of unwinding; see `EngineBuilder::catch_panics`
+    catch_panics: bool,
+
+    // how changes propagate; see `Strategy`
+    strategy: Strategy,
+
+    // output types compared before marking parents dirty; see `EngineBuilder::equality_cutoff`
+    equality_cutoffs: HashMap<TypeId, EqualityCutoff>,
 
     // tracks the current stabilization generation; incremented on every stabilize
     generation: Generation,
 }
 
 struct Mounter {
-    graph: Rc<Graph2>,
+    graph: Weak<Graph2>,
+}
+
+/// Keys of the constants created with `Engine::constant_interned`, by output type and hash of
+/// their value. Nodes aren't kept alive by this, so keys may refer to freed nodes until they're
+/// pruned.
+#[derive(Default)]
+struct InternedConstants {
+    keys: HashMap<(TypeId, u64), Vec<NodeKey>>,
+    /// total number of keys
+    len: usize,
+    /// `len` at which to prune freed nodes next
+    prune_at: usize,
+}
+
+impl InternedConstants {
+    /// forgets freed constants, then waits for the pool to double before doing it again, so it
+    /// doesn't grow without bound
+    fn prune(&mut self, graph: Graph2Guard<'_>) {
+        self.keys.retain(|_, keys| {
+            keys.retain(|key| graph.get(*key).is_some());
+            !keys.is_empty()
+        });
+        self.len = self.keys.values().map(Vec::len).sum();
+        self.prune_at = (2 * self.len).max(64);
+    }
 }
 
 impl crate::expert::Engine for Engine {
@@ -82,40 +221,224 @@ impl crate::expert::Engine for Engine {
             let this = borrow1
                 .as_mut()
                 .expect("no engine was initialized. did you call `Engine::new()`?");
+            let graph = this
+                .graph
+                .upgrade()
+                .expect("the engine anchors are created in has been dropped. use `Engine::enter` to pick another engine");
             let debug_info = inner.debug_info();
-            let handle = this.graph.insert(Box::new(inner), debug_info);
+            let handle = graph.insert(graph.box_anchor(inner), debug_info);
             Anchor::new_from_expert(handle)
         })
     }
 }
 
 impl Engine {
-    /// Creates a new Engine with maximum height 256.
+    /// Creates a new Engine with the default configuration. There's no limit on how deep its graph
+    /// can get.
+    ///
+    /// New Anchors are created in this Engine unless another Engine on this thread is still
+    /// alive, in which case `Engine::enter` picks which one to use.
     pub fn new() -> Self {
-        Self::new_with_max_height(256)
+        Self::builder().build()
     }
 
-    /// Creates a new Engine with a custom maximum height.
+    /// Returns an `EngineBuilder` for creating an Engine with a custom configuration.
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::default()
+    }
+
+    /// Creates a new Engine with a maximum height.
+    #[deprecated(note = "use `Engine::builder().max_height(max_height).build()` instead")]
     pub fn new_with_max_height(max_height: usize) -> Self {
-        let graph = Rc::new(Graph2::new(max_height));
+        Self::builder().max_height(max_height).build()
+    }
+
+    fn from_builder(builder: EngineBuilder) -> Self {
+        let graph = Rc::new(Graph2::new(builder.max_height));
+        graph.preallocate(builder.initial_capacity);
         let mounter = Mounter {
-            graph: graph.clone(),
+            graph: Rc::downgrade(&graph),
         };
-        DEFAULT_MOUNTER.with(|v| *v.borrow_mut() = Some(mounter));
+        DEFAULT_MOUNTER.with(|v| {
+            let mut default_mounter = v.borrow_mut();
+            // another live Engine stays the default; use `enter` to create Anchors in this one
+            let replace = match default_mounter.as_ref() {
+                Some(existing) => existing.graph.strong_count() == 0,
+                None => true,
+            };
+            if replace {
+                *default_mounter = Some(mounter);
+            }
+        });
+        let dirty_marks = Rc::new(DirtyMarks {
+            marks: RefCell::new(MarkSet::default()),
+            payloads: RefCell::new(Vec::new()),
+            graph: Rc::downgrade(&graph),
+            wake: RefCell::new(None),
+            woken: Cell::new(false),
+        });
         Self {
             graph,
-            dirty_marks: Default::default(),
+            dirty_marks,
+            send_dirty_marks: Default::default(),
+            subscriptions: Vec::new(),
+            next_subscription: 0,
+            pre_stabilize_hooks: Vec::new(),
+            post_stabilize_hooks: Vec::new(),
+            removable_hooks: Vec::new(),
+            failure: RefCell::new(None),
+            partial_stabilize: None,
+            updated_observed: Vec::new(),
+            #[cfg(feature = "snapshot")]
+            snapshot_vars: Vec::new(),
+            #[cfg(feature = "history")]
+            histories: Default::default(),
+            clocks: Vec::new(),
+            interned_constants: Default::default(),
+            keep_alive: builder.gc_policy.keep_alive,
+            kept_alive: Vec::new(),
+            cached_output_limit: builder.gc_policy.cached_output_limit,
+            cached_outputs: RefCell::new(Vec::new()),
+            time_recalculations: builder.time_recalculations,
+            catch_panics: builder.catch_panics,
+            strategy: builder.strategy,
+            equality_cutoffs: builder.equality_cutoffs,
             generation: Generation::new(),
         }
     }
 
+    /// Creates a new Var in this Engine, regardless of which Engine `Var::new` would use.
+    pub fn var<T: 'static>(&self, val: T) -> Var<T> {
+        self.enter(|| Var::new(val))
+    }
+
+    /// Creates a new constant Anchor in this Engine, regardless of which Engine
+    /// `Anchor::constant` would use.
+    pub fn constant<T: 'static>(&self, val: T) -> Anchor<T> {
+        self.enter(|| Anchor::constant(val))
+    }
+
+    /// Like `constant`, but returns the same Anchor every time it's called with equal values while
+    /// that Anchor is alive, so graphs built from templates share one node per distinct constant
+    /// rather than creating one per use. Values are only compared with constants of the same type
+    /// created by this method.
+    pub fn constant_interned<T: Hash + Eq + 'static>(&self, val: T) -> Anchor<T> {
+        let mut hasher = DefaultHasher::new();
+        val.hash(&mut hasher);
+        let pool_key = (TypeId::of::<T>(), hasher.finish());
+        let mut interned = self.interned_constants.borrow_mut();
+        let interned = &mut *interned;
+        let keys = interned.keys.entry(pool_key).or_default();
+        let before = keys.len();
+        let found = self.graph.with(|graph| {
+            keys.retain(|key| graph.get(*key).is_some());
+            keys.iter().copied().find(|key| {
+                let node = graph.get(*key).unwrap();
+                let borrow = node.anchor.borrow();
+                let output = borrow
+                    .as_ref()
+                    .unwrap()
+                    .output(&mut EngineContext { engine: self });
+                output.downcast_ref::<T>() == Some(&val)
+            })
+        });
+        interned.len -= before - keys.len();
+        if let Some(handle) = found.and_then(|key| self.graph.handle_for(key)) {
+            return Anchor::new_from_expert(handle);
+        }
+        let anchor = self.constant(val);
+        keys.push(anchor.token());
+        interned.len += 1;
+        if interned.len >= interned.prune_at {
+            self.graph.with(|graph| interned.prune(graph));
+        }
+        anchor
+    }
+
+    /// Mounts a custom `AnchorInner` in this Engine, regardless of which Engine
+    /// `expert::Engine::mount` would use.
+    pub fn mount<I: AnchorInner<Self> + 'static>(&self, inner: I) -> Anchor<I::Output> {
+        self.enter(|| <Self as crate::expert::Engine>::mount(inner))
+    }
+
+    /// Runs `f` with this Engine as the one new Anchors are created in, restoring the previous
+    /// Engine afterwards. By default, Anchors are created in the first Engine created on the
+    /// current thread that's still alive, so this is needed to create Anchors in any other.
+    ///
+    /// An Anchor belongs to the Engine it was created in for its entire lifetime, since other
+    /// Anchors' inputs refer to it by its position in that Engine's graph. To hand a graph built
+    /// elsewhere to an Engine, build it again inside that Engine's `enter`.
+    pub fn enter<R, F: FnOnce() -> R>(&self, f: F) -> R {
+        struct Restore(Option<Mounter>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let previous = self.0.take();
+                DEFAULT_MOUNTER.with(|v| *v.borrow_mut() = previous);
+            }
+        }
+        let mounter = Mounter {
+            graph: Rc::downgrade(&self.graph),
+        };
+        let _restore = Restore(DEFAULT_MOUNTER.with(|v| v.borrow_mut().replace(mounter)));
+        f()
+    }
+
+    /// Drops every node in the graph, empties the recalculation queue, and resets the generation
+    /// number. Anchors created before calling this can no longer be used with this Engine. If this
+    /// Engine was the one new Anchors are created in, it stays that way; otherwise that Engine is
+    /// left as is.
+    ///
+    /// Subscriptions, clocks, and other per-node state are removed. Stabilization hooks, wake
+    /// callbacks, equality cutoffs, the keep-alive policy, and the cached output limit are kept.
+    pub fn clear(&mut self) {
+        // building registers the fresh Engine as the default, which should only replace this one
+        let previous = DEFAULT_MOUNTER.with(|v| v.borrow_mut().take());
+        let mut fresh = Self::builder()
+            .max_height(self.graph.max_height())
+            .gc_policy(self.gc_policy())
+            .time_recalculations(self.time_recalculations)
+            .catch_panics(self.catch_panics)
+            .strategy(self.strategy)
+            .build();
+        fresh.equality_cutoffs = std::mem::take(&mut self.equality_cutoffs);
+        fresh.pre_stabilize_hooks = std::mem::take(&mut self.pre_stabilize_hooks);
+        fresh.post_stabilize_hooks = std::mem::take(&mut self.post_stabilize_hooks);
+        fresh.removable_hooks = std::mem::take(&mut self.removable_hooks);
+        *fresh.dirty_marks.wake.borrow_mut() = self.dirty_marks.wake.borrow_mut().take();
+        *fresh.send_dirty_marks.wake.lock().unwrap() =
+            self.send_dirty_marks.wake.lock().unwrap().take();
+        fresh
+            .graph
+            .event_senders
+            .replace(self.graph.event_senders.take());
+        let mounter = match previous {
+            Some(mounter) if mounter.graph.as_ptr() != Rc::as_ptr(&self.graph) => Some(mounter),
+            Some(_) => Some(Mounter {
+                graph: Rc::downgrade(&fresh.graph),
+            }),
+            None => None,
+        };
+        DEFAULT_MOUNTER.with(|v| *v.borrow_mut() = mounter);
+        *self = fresh;
+    }
+
     /// Marks an Anchor as observed. All observed nodes will always be brought up-to-date
     /// when *any* Anchor in the graph is retrieved. If you get an output value fairly
     /// often, it's best to mark it as Observed so that Anchors can calculate its
     /// dependencies faster.
     pub fn mark_observed<O: 'static>(&mut self, anchor: &Anchor<O>) {
+        // an explicit observation outlives any subscription that observed the Anchor first
+        for entry in &mut self.subscriptions {
+            if entry.token == anchor.token() {
+                entry.observes = false;
+            }
+        }
+        self.observe(anchor.token());
+    }
+
+    fn observe(&self, token: NodeKey) {
         self.graph.with(|graph| {
-            let node = graph.get(anchor.token()).unwrap();
+            let node = graph.lookup(token);
             node.observed.set(true);
             if graph2::recalc_state(node) != RecalcState::Ready {
                 graph.queue_recalc(node);
@@ -127,13 +450,362 @@ impl Engine {
     /// because `anchor` was previously observed, those parents will be unmarked as
     /// necessary.
     pub fn mark_unobserved<O: 'static>(&mut self, anchor: &Anchor<O>) {
+        let keep_alive_until = self.keep_alive_until();
+        let kept_alive = &mut self.kept_alive;
         self.graph.with(|graph| {
-            let node = graph.get(anchor.token()).unwrap();
+            let node = graph.lookup(anchor.token());
             node.observed.set(false);
-            Self::update_necessary_children(node);
+            Self::release_observed(node, keep_alive_until, kept_alive);
         })
     }
 
+    /// Sets how many stabilizations an Anchor stays up-to-date for after it's unobserved. During
+    /// that time its dependencies are still kept necessary, so observing it again doesn't
+    /// require recalculating the whole subgraph. Defaults to 0, releasing them immediately.
+    pub fn set_keep_alive(&mut self, generations: u64) {
+        self.keep_alive = generations;
+    }
+
+    /// Returns the current policies for releasing memory held by unobserved Anchors.
+    pub fn gc_policy(&self) -> GcPolicy {
+        GcPolicy {
+            keep_alive: self.keep_alive,
+            cached_output_limit: self.cached_output_limit,
+        }
+    }
+
+    /// Limits how many unnecessary Anchors keep their cached outputs. At the end of each
+    /// stabilization, the outputs of the least recently recalculated unnecessary Anchors beyond
+    /// `limit` are dropped, and recalculated if they're requested again. Only Anchors whose
+    /// output can be recalculated from their inputs, such as `map`, can be evicted, and never
+    /// while an observed Anchor still reads them, as under `Strategy::Pull`. `None`, the default,
+    /// keeps every output.
+    pub fn set_cached_output_limit(&mut self, limit: Option<usize>) {
+        self.cached_output_limit = limit;
+        if limit.is_none() {
+            let cached = std::mem::take(&mut *self.cached_outputs.borrow_mut());
+            self.graph.with(|graph| {
+                for key in cached {
+                    if let Some(node) = graph.get(key) {
+                        node.output_cached.set(false);
+                    }
+                }
+            })
+        }
+    }
+
+    /// records that `node` now holds a cached output, if outputs are being limited
+    fn track_cached_output<'a>(&self, node: NodeGuard<'a>) {
+        if self.cached_output_limit.is_some() && !node.output_cached.replace(true) {
+            self.cached_outputs.borrow_mut().push(node.key());
+        }
+    }
+
+    /// returns true if `node`'s type has an equality cutoff and its freshly calculated output
+    /// equals the one it last updated with. Otherwise, keeps a copy of the new output to compare
+    /// against next time.
+    fn output_unchanged<'a>(&self, node: NodeGuard<'a>) -> bool {
+        let cutoff = match node
+            .output_type
+            .get()
+            .and_then(|ty| self.equality_cutoffs.get(&ty))
+        {
+            Some(cutoff) => cutoff,
+            None => return false,
+        };
+        let anchor = node.anchor.borrow();
+        let output = match anchor.as_ref() {
+            Some(anchor) => anchor.output(&mut EngineContext { engine: self }),
+            None => return false,
+        };
+        let mut previous = node.cutoff_output.borrow_mut();
+        if previous
+            .as_deref()
+            .is_some_and(|previous| (cutoff.eq)(previous, output))
+        {
+            return true;
+        }
+        *previous = Some((cutoff.copy)(output));
+        false
+    }
+
+    /// copies `node`'s freshly calculated output into its inline slot, if it's been read with
+    /// `get_copy` and is small enough
+    fn store_inline_output<'a>(&self, node: NodeGuard<'a>) {
+        if !node.inline_output.wanted() {
+            return;
+        }
+        if let Some(anchor) = node.anchor.borrow().as_ref() {
+            anchor.store_inline_output(&mut EngineContext { engine: self }, &node.inline_output);
+        }
+    }
+
+    /// drops the least recently recalculated unnecessary outputs beyond `cached_output_limit`
+    fn evict_cached_outputs(&self) {
+        let limit = match self.cached_output_limit {
+            Some(limit) => limit,
+            None => return,
+        };
+        let mut cached = self.cached_outputs.borrow_mut();
+        self.graph.with(|graph| {
+            // forget freed nodes, duplicates left behind by reused nodes, and nodes that are
+            // waiting for recalculation anyway
+            let mut seen = HashSet::new();
+            cached.retain(|key| {
+                let node = match graph.get(*key) {
+                    Some(node) if node.output_cached.get() && seen.insert(*key) => node,
+                    _ => return false,
+                };
+                if graph2::recalc_state(node) != RecalcState::Ready {
+                    node.output_cached.set(false);
+                    return false;
+                }
+                true
+            });
+            let mut unnecessary: Vec<_> = cached
+                .iter()
+                .filter_map(|key| graph.get(*key))
+                .filter(|node| Self::check_observed_raw(*node) == ObservedState::Unnecessary)
+                .filter(|node| !feeds_needed_anchor(*node))
+                .collect();
+            if unnecessary.len() <= limit {
+                return;
+            }
+            unnecessary.sort_by_key(|node| node.last_ready.get());
+            let excess = unnecessary.len() - limit;
+            for node in unnecessary.into_iter().take(excess) {
+                node.output_cached.set(false);
+                evict_output(graph, node);
+            }
+            cached.retain(|key| matches!(graph.get(*key), Some(node) if node.output_cached.get()));
+        })
+    }
+
+    /// Drops the cached output of every unnecessary Anchor that can recalculate it, such as `map`,
+    /// and returns how many were dropped. They're recalculated if they're requested again. Nodes
+    /// themselves are already freed as soon as their last `Anchor` is dropped, so this reclaims
+    /// the memory still held by subgraphs nothing observes.
+    pub fn prune_unobserved(&mut self) -> usize {
+        self.graph.with(|graph| {
+            graph
+                .live_nodes()
+                .into_iter()
+                .filter(|node| Self::check_observed_raw(*node) == ObservedState::Unnecessary)
+                .filter(|node| !feeds_needed_anchor(*node) && evict_output(graph, *node))
+                .count()
+        })
+    }
+
+    /// Releases memory left over from Anchors that have since been dropped, such as after closing
+    /// a large document. Freed node slots can't be returned to the allocator, since Anchors point
+    /// directly at them, but they're reused by new Anchors, and their edge lists and the engine's
+    /// internal buffers are shrunk.
+    pub fn shrink_to_fit(&mut self) {
+        self.graph.with(|graph| graph.shrink_to_fit());
+        self.dirty_marks.marks.borrow_mut().shrink_to_fit();
+        self.dirty_marks.payloads.borrow_mut().shrink_to_fit();
+        self.subscriptions.shrink_to_fit();
+        self.updated_observed.shrink_to_fit();
+        self.kept_alive.shrink_to_fit();
+        self.cached_outputs.borrow_mut().shrink_to_fit();
+    }
+
+    /// Returns counts of the nodes and edges in this Engine's graph, for monitoring its growth.
+    pub fn stats(&self) -> EngineStats {
+        self.graph.with(|graph| {
+            let live = graph.live_nodes();
+            EngineStats {
+                nodes: live.len(),
+                free_nodes: graph.allocated_nodes() - live.len(),
+                necessary_edges: live
+                    .iter()
+                    .map(|node| node.necessary_children().count())
+                    .sum(),
+                clean_parent_edges: live.iter().map(|node| node.clean_parents().count()).sum(),
+                approx_bytes: graph.approx_bytes(),
+            }
+        })
+    }
+
+    /// Returns the `top_n` most expensive live Anchors, most expensive first. Anchors are ranked
+    /// by total recalculation time if the Engine was built with
+    /// `EngineBuilder::time_recalculations`, and by number of recalculations otherwise.
+    pub fn hotspots(&self, top_n: usize) -> Vec<Hotspot> {
+        let mut hotspots = self.graph.with(|graph| {
+            graph
+                .live_nodes()
+                .into_iter()
+                .filter(|node| node.recalculations.get() > 0)
+                .map(|node| Hotspot {
+                    token: node.key(),
+                    debug_info: node.debug_info.get(),
+                    recalculations: node.recalculations.get(),
+                    recalc_time: node.recalc_time.get(),
+                })
+                .collect::<Vec<_>>()
+        });
+        hotspots.sort_by(|a, b| {
+            (b.recalc_time, b.recalculations).cmp(&(a.recalc_time, a.recalculations))
+        });
+        hotspots.truncate(top_n);
+        hotspots
+    }
+
+    /// Returns the height and degree distributions of this Engine's graph. Useful for figuring out
+    /// why stabilization got more expensive.
+    pub fn topology(&self) -> TopologyStats {
+        fn bump(distribution: &mut Vec<usize>, i: usize) {
+            if distribution.len() <= i {
+                distribution.resize(i + 1, 0);
+            }
+            distribution[i] += 1;
+        }
+
+        self.graph.with(|graph| {
+            let live = graph.live_nodes();
+            let mut inputs: HashMap<NodeKey, usize> = HashMap::new();
+            let mut stats = TopologyStats::default();
+            for node in &live {
+                bump(&mut stats.height_histogram, graph2::height(*node));
+                // clean parent lists may contain duplicates
+                let parents: HashSet<_> = node.clean_parents().map(|parent| parent.key()).collect();
+                for parent in &parents {
+                    *inputs.entry(*parent).or_insert(0) += 1;
+                }
+                bump(&mut stats.fan_out, parents.len());
+            }
+            for node in &live {
+                bump(
+                    &mut stats.fan_in,
+                    inputs.get(&node.key()).copied().unwrap_or(0),
+                );
+            }
+            stats
+        })
+    }
+
+    fn keep_alive_until(&self) -> Option<u64> {
+        if self.keep_alive == 0 {
+            None
+        } else {
+            Some(self.generation.get() + self.keep_alive)
+        }
+    }
+
+    /// called after `node` is marked unobserved; either keeps it necessary for a while, or
+    /// immediately unmarks its children as necessary
+    fn release_observed<'a>(
+        node: NodeGuard<'a>,
+        keep_alive_until: Option<u64>,
+        kept_alive: &mut Vec<NodeKey>,
+    ) {
+        match keep_alive_until {
+            Some(until) => {
+                if node.keep_alive_until.replace(Some(until)).is_none() {
+                    kept_alive.push(node.key());
+                }
+            }
+            None => Self::update_necessary_children(node),
+        }
+    }
+
+    /// releases kept-alive nodes whose keep-alive period has ended
+    fn expire_keep_alive(&mut self) {
+        if self.kept_alive.is_empty() {
+            return;
+        }
+        let generation = self.generation.get();
+        let kept_alive = &mut self.kept_alive;
+        self.graph.with(|graph| {
+            kept_alive.retain(|key| {
+                let node = match graph.get(*key) {
+                    Some(node) => node,
+                    None => return false,
+                };
+                match node.keep_alive_until.get() {
+                    Some(until) if until >= generation => true,
+                    Some(_) => {
+                        node.keep_alive_until.set(None);
+                        Self::update_necessary_children(node);
+                        false
+                    }
+                    None => false,
+                }
+            })
+        })
+    }
+
+    /// Marks several Anchors as observed at once. Equivalent to calling `mark_observed` on each,
+    /// but the graph and the subscriptions are only visited once.
+    pub fn mark_observed_many<'a, O: 'static, I>(&mut self, anchors: I)
+    where
+        I: IntoIterator<Item = &'a Anchor<O>>,
+    {
+        let tokens: HashSet<NodeKey> = self.graph.with(|graph| {
+            anchors
+                .into_iter()
+                .map(|anchor| {
+                    let node = graph.lookup(anchor.token());
+                    node.observed.set(true);
+                    if graph2::recalc_state(node) != RecalcState::Ready {
+                        graph.queue_recalc(node);
+                    }
+                    anchor.token()
+                })
+                .collect()
+        });
+        // an explicit observation outlives any subscription that observed the Anchor first
+        for entry in &mut self.subscriptions {
+            if tokens.contains(&entry.token) {
+                entry.observes = false;
+            }
+        }
+    }
+
+    /// Marks several Anchors as unobserved at once. Every Anchor is unmarked before necessity is
+    /// updated, so dependencies shared between them are only released once, by the last of them.
+    pub fn mark_unobserved_many<'a, O: 'static, I>(&mut self, anchors: I)
+    where
+        I: IntoIterator<Item = &'a Anchor<O>>,
+    {
+        let keep_alive_until = self.keep_alive_until();
+        let kept_alive = &mut self.kept_alive;
+        self.graph.with(|graph| {
+            let nodes: Vec<_> = anchors
+                .into_iter()
+                .map(|anchor| graph.lookup(anchor.token()))
+                .collect();
+            for node in &nodes {
+                node.observed.set(false);
+            }
+            for node in nodes {
+                Self::release_observed(node, keep_alive_until, kept_alive);
+            }
+        })
+    }
+
+    /// Marks `anchor` as observed, stabilizes, and passes a reference to its value to `f`. If
+    /// `anchor` wasn't already observed, it's marked unobserved again afterwards. Useful for
+    /// occasionally sampling an expensive Anchor.
+    pub fn with_observed<O: 'static, R, F: FnOnce(&O) -> R>(
+        &mut self,
+        anchor: &Anchor<O>,
+        f: F,
+    ) -> R {
+        let was_observed = self
+            .graph
+            .with(|graph| graph.lookup(anchor.token()).observed.get());
+        if !was_observed {
+            self.mark_observed(anchor);
+        }
+        self.stabilize();
+        let res = self.read(anchor, f);
+        if !was_observed {
+            self.mark_unobserved(anchor);
+        }
+        res
+    }
+
     fn update_necessary_children<'a>(node: NodeGuard<'a>) {
         if Self::check_observed_raw(node) != ObservedState::Unnecessary {
             // we have another parent still observed, so skip this
@@ -148,90 +820,740 @@ impl Engine {
     /// Retrieves the value of an Anchor, recalculating dependencies as necessary to get the
     /// latest value.
     pub fn get<'out, O: Clone + 'static>(&mut self, anchor: &Anchor<O>) -> O {
+        self.read(anchor, O::clone)
+    }
+
+    /// Retrieves the values of several Anchors at once, such as `engine.get_many((&a, &b))`.
+    /// Unlike calling `get` for each Anchor, all the values are guaranteed to come from the same
+    /// stabilization. Like `get`, panics if any of them is poisoned.
+    pub fn get_many<T: AnchorTuple>(&mut self, anchors: T) -> T::Output {
+        self.stabilize();
+        self.graph.with(|graph| {
+            let mut queued = false;
+            for token in anchors.tokens() {
+                let node = graph.lookup(token);
+                if graph2::recalc_state(node) != RecalcState::Ready {
+                    graph.queue_recalc(node);
+                    queued = true;
+                }
+            }
+            if queued {
+                self.stabilize0(&mut StabilizeReport::default());
+            }
+            for token in anchors.tokens() {
+                if let Some(err) = &*graph.lookup(token).poison.borrow() {
+                    panic!("{}", err);
+                }
+            }
+        });
+        anchors.outputs(self)
+    }
+
+    /// Like `get`, but passes a reference to the Anchor's value to `f` instead of cloning it.
+    /// Useful for inspecting large values, or values that aren't `Clone`.
+    pub fn read<O: 'static, R, F: FnOnce(&O) -> R>(&mut self, anchor: &Anchor<O>, f: F) -> R {
+        self.try_read(anchor, f)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like `get`, but returns an error instead of panicking if `anchor` can't be retrieved.
+    pub fn try_get<O: Clone + 'static>(&mut self, anchor: &Anchor<O>) -> Result<O, Error> {
+        self.try_read(anchor, O::clone)
+    }
+
+    /// Like `read`, but returns an error instead of panicking if `anchor` can't be retrieved.
+    pub fn try_read<O: 'static, R, F: FnOnce(&O) -> R>(
+        &mut self,
+        anchor: &Anchor<O>,
+        f: F,
+    ) -> Result<R, Error> {
+        self.try_bring_up_to_date(anchor)?;
+        self.graph.with(|graph| {
+            let target_node = graph.lookup(anchor.token());
+            let borrow = target_node.anchor.borrow();
+            Ok(f(borrow
+                .as_ref()
+                .unwrap()
+                .output(&mut EngineContext { engine: &self })
+                .downcast_ref::<O>()
+                .unwrap()))
+        })
+    }
+
+    /// Like `get`, for small `Copy` outputs. Once an Anchor has been read with `get_copy`, outputs
+    /// of at most 16 bytes are copied inline into the engine's node for it whenever it's
+    /// recalculated, so later reads take them directly instead of borrowing the Anchor and
+    /// downcasting its output. Larger outputs fall back to `get`.
+    pub fn get_copy<O: Copy + 'static>(&mut self, anchor: &Anchor<O>) -> O {
+        self.try_bring_up_to_date(anchor)
+            .unwrap_or_else(|err| panic!("{}", err));
+        let inline = self.graph.with(|graph| {
+            let node = graph.lookup(anchor.token());
+            if !node.inline_output.wanted() && InlineOutput::fits::<O>() {
+                // start copying this node's outputs, beginning with the current one
+                node.inline_output.want();
+                self.store_inline_output(node);
+            }
+            node.inline_output.load::<O>()
+        });
+        match inline {
+            Some(val) => val,
+            None => self.get(anchor),
+        }
+    }
+
+    /// stabilizes, then makes sure `anchor` itself is up-to-date even if it isn't necessary.
+    /// returns an error if it's poisoned
+    fn try_bring_up_to_date<O>(&mut self, anchor: &Anchor<O>) -> Result<(), Error> {
+        let settled = self.graph.with(|graph| {
+            let node = graph.try_lookup(anchor.token())?;
+            Ok::<_, Error>(
+                graph2::recalc_state(node) == RecalcState::Ready && node.poison.borrow().is_none(),
+            )
+        })?;
+        if settled && self.stabilize_is_noop() {
+            // nothing changed since the last stabilization, so the cached output is current
+            return Ok(());
+        }
         // stabilize once before, since the stabilization process may mark our requested node
         // as dirty
-        self.stabilize();
+        self.try_stabilize()?;
         self.graph.with(|graph| {
-            let anchor_node = graph.get(anchor.token()).unwrap();
+            let anchor_node = graph.lookup(anchor.token());
             if graph2::recalc_state(anchor_node) != RecalcState::Ready {
                 graph.queue_recalc(anchor_node);
                 // stabilize again, to make sure our target node that is now in the queue is up-to-date
                 // use stabilize0 because no dirty marks have occured since last stabilization, and we want
                 // to make sure we don't unnecessarily increment generation number
-                self.stabilize0();
+                self.try_stabilize0(&mut StabilizeReport::default())?;
+            }
+            let poison = anchor_node.poison.borrow().clone();
+            match poison {
+                Some(err) => Err(err),
+                None => Ok(()),
+            }
+        })
+    }
+
+    /// Like `get`, but returns the value in an `Rc`. For Anchors that already store their value
+    /// in an `Rc`, such as those returned by `Var::watch`, the `Rc` is shared rather than cloning
+    /// the value. Otherwise, the value is cloned into a new `Rc`.
+    pub fn get_rc<O: Clone + 'static>(&mut self, anchor: &Anchor<O>) -> Rc<O> {
+        // bring the anchor up-to-date
+        self.read(anchor, |_| ());
+        self.graph.with(|graph| {
+            let node = graph.lookup(anchor.token());
+            let borrow = node.anchor.borrow();
+            let inner = borrow.as_ref().unwrap();
+            match inner.output_rc() {
+                Some(rc) => rc.downcast_ref::<Rc<O>>().unwrap().clone(),
+                None => Rc::new(
+                    inner
+                        .output(&mut EngineContext { engine: self })
+                        .downcast_ref::<O>()
+                        .unwrap()
+                        .clone(),
+                ),
+            }
+        })
+    }
+
+    /// Returns the value of an Anchor as of the last time it was calculated, without recalculating
+    /// anything. The value may be out-of-date if the Anchor's inputs have changed since. Returns
+    /// `None` if the Anchor has never been calculated, or is poisoned, in which case `try_get`
+    /// returns the error.
+    pub fn peek<O: Clone + 'static>(&self, anchor: &Anchor<O>) -> Option<O> {
+        self.graph.with(|graph| {
+            let node = graph.lookup(anchor.token());
+            node.last_ready.get()?;
+            if node.poison.borrow().is_some() {
+                return None;
+            }
+            let borrow = node.anchor.borrow();
+            let val = borrow
+                .as_ref()
+                .unwrap()
+                .output(&mut EngineContext { engine: self })
+                .downcast_ref::<O>()
+                .unwrap()
+                .clone();
+            Some(val)
+        })
+    }
+
+    /// Retrieves the value of an Anchor, recalculating only the Anchors it depends on. Unlike `get`,
+    /// observed Anchors that aren't dependencies of `anchor` are not brought up-to-date; they'll
+    /// be recalculated on the next call to `stabilize`. Useful for occasionally probing expensive
+    /// unobserved values without paying for a full stabilization.
+    pub fn get_isolated<O: Clone + 'static>(&mut self, anchor: &Anchor<O>) -> O {
+        self.finish_partial_stabilize();
+        let start = Instant::now();
+        let mut report = self.begin_stabilize();
+        let val = self.graph.with(|graph| {
+            for node in graph.begin_deferring() {
+                mark_dirty0(graph, node);
+            }
+            let anchor_node = graph.lookup(anchor.token());
+            if graph2::recalc_state(anchor_node) != RecalcState::Ready {
+                graph.queue_recalc(anchor_node);
+                self.stabilize0(&mut report);
             }
-            let target_anchor = &graph.get(anchor.token()).unwrap().anchor;
-            let borrow = target_anchor.borrow();
+            graph.end_deferring();
+            let borrow = anchor_node.anchor.borrow();
             borrow
                 .as_ref()
                 .unwrap()
-                .output(&mut EngineContext { engine: &self })
+                .output(&mut EngineContext { engine: self })
                 .downcast_ref::<O>()
                 .unwrap()
                 .clone()
-        })
+        });
+        self.finish_stabilize(&mut report, start);
+        val
+    }
+
+    /// Returns an Anchor containing the current time, rounded down to a multiple of `granularity`.
+    /// The time is refreshed at most once per stabilization, and dependent Anchors are only
+    /// recalculated when the rounded time changes. Calling this repeatedly with the same
+    /// `granularity` returns the same Anchor. On `wasm32-unknown-unknown`, the output is a
+    /// `web_time::Instant`, which reads the browser's `performance.now()`.
+    #[track_caller]
+    pub fn now(&mut self, granularity: Duration) -> Anchor<Instant> {
+        if let Some((_, clock)) = self.clocks.iter().find(|(g, _)| *g == granularity) {
+            return clock.clone();
+        }
+        let clock = self.mount(clock::Clock::new(granularity));
+        self.clocks.push((granularity, clock.clone()));
+        clock
+    }
+
+    /// Sets a callback that is invoked whenever a `SendDirtyHandle` marks a node as dirty, unless
+    /// it was already marked since the last stabilization. The callback may run on any thread,
+    /// and is typically used to wake up whatever thread owns this engine so it knows to call
+    /// `stabilize`.
+    pub fn set_send_wake_callback<F: Fn() + Send + Sync + 'static>(&mut self, f: F) {
+        *self.send_dirty_marks.wake.lock().unwrap() = Some(Box::new(f));
+    }
+
+    /// Sets a callback that is invoked when a `Var` is set or a node is marked dirty in a way that
+    /// makes some observed Anchor out of date. This is useful for event loops that want to
+    /// schedule a stabilization only when one is actually needed. The callback is called at most
+    /// once between stabilizations.
+    pub fn set_wake_callback<F: FnMut() + 'static>(&mut self, f: F) {
+        *self.dirty_marks.wake.borrow_mut() = Some(Box::new(f));
     }
 
     pub(crate) fn update_dirty_marks(&mut self) {
+        let send_payloads = std::mem::take(&mut *self.send_dirty_marks.payloads.lock().unwrap());
+        self.dirty_marks.payloads.borrow_mut().extend(
+            send_payloads
+                .into_iter()
+                .map(|(SendNodeKey(key), payload)| (key, payload as Box<dyn Any>)),
+        );
+        let send_dirty_marks = self.send_dirty_marks.marks.lock().unwrap().take();
+        self.dirty_marks
+            .marks
+            .borrow_mut()
+            .extend(send_dirty_marks.into_iter().map(|SendNodeKey(key)| key));
+        self.dirty_marks.woken.set(false);
         self.graph.with(|graph| {
-            let dirty_marks = std::mem::replace(&mut *self.dirty_marks.borrow_mut(), Vec::new());
+            let payloads = std::mem::take(&mut *self.dirty_marks.payloads.borrow_mut());
+            for (key, payload) in payloads {
+                if let Some(node) = graph.get(key) {
+                    if let Some(anchor) = node.anchor.borrow_mut().as_mut() {
+                        anchor.dirty_payload(payload);
+                    }
+                }
+            }
+            let dirty_marks = self.dirty_marks.marks.borrow_mut().take();
             for dirty in dirty_marks {
-                let node = graph.get(dirty).unwrap();
-                mark_dirty(graph, node, false);
+                // the anchor may have been freed since it was marked
+                if let Some(node) = graph.get(dirty) {
+                    self.graph.emit(EngineEvent::DirtyMarked { id: dirty.id() });
+                    mark_dirty(graph, node, false);
+                }
             }
         })
     }
 
+    /// Returns the generation number of the most recent stabilization, matching
+    /// `StabilizeReport::generation`.
+    pub fn generation(&self) -> u64 {
+        self.generation.get()
+    }
+
+    /// Returns true if any dirty marks are pending or any observed node is queued for
+    /// recalculation, meaning a call to `stabilize` may do some work. This does not account
+    /// for clocks created with `now`, which may tick on any stabilization.
+    pub fn needs_stabilize(&self) -> bool {
+        !self.dirty_marks.marks.borrow().is_empty()
+            || !self.send_dirty_marks.marks.lock().unwrap().is_empty()
+            || self.graph.with(|graph| graph.recalc_queued())
+    }
+
+    /// Returns true if a stabilization would do nothing at all, including running hooks, ticking
+    /// clocks, or expiring kept-alive Anchors, so `get` can skip it.
+    fn stabilize_is_noop(&mut self) -> bool {
+        self.remove_stopped_hooks();
+        self.partial_stabilize.is_none()
+            && self.pre_stabilize_hooks.is_empty()
+            && self.post_stabilize_hooks.is_empty()
+            && self.removable_hooks.is_empty()
+            && self.clocks.is_empty()
+            && self.kept_alive.is_empty()
+            && !self.needs_stabilize()
+    }
+
     /// Ensure any Observed nodes are up-to-date, recalculating dependencies as necessary. You
     /// should rarely need to call this yourself; `Engine::get` calls it automatically.
-    pub fn stabilize(&mut self) {
+    ///
+    /// Returns a `StabilizeReport` describing the work done. If a budgeted stabilization was
+    /// in progress, it is finished first, and its work is not included in the report.
+    pub fn stabilize(&mut self) -> StabilizeReport {
+        self.try_stabilize().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like `stabilize`, but returns an error instead of panicking if some node can't be
+    /// recalculated. The failed node stays queued, so later stabilizations fail in the same way
+    /// until the problem is fixed, for instance by setting a `Var` that breaks a cycle.
+    pub fn try_stabilize(&mut self) -> Result<StabilizeReport, Error> {
+        self.try_finish_partial_stabilize()?;
+        let start = Instant::now();
+        let mut report = self.begin_stabilize();
+        self.try_stabilize0(&mut report)?;
+        self.finish_stabilize(&mut report, start);
+        Ok(report)
+    }
+
+    /// Like `stabilize`, but stops recalculating nodes once `budget` is exhausted. Returns
+    /// `None` if work remains, in which case the next call to `stabilize_budgeted` resumes
+    /// where this one left off. Once the stabilization completes, returns its report.
+    ///
+    /// A stabilization started by this function does not see `Var` changes or dirty marks that
+    /// happen until it has completed.
+    pub fn stabilize_budgeted(&mut self, budget: StabilizeBudget) -> Option<StabilizeReport> {
+        let start = Instant::now();
+        let mut report = match self.partial_stabilize.take() {
+            Some(report) => report,
+            None => self.begin_stabilize(),
+        };
+        let nodes_at_start = report.nodes_recalculated;
+        let complete = self
+            .stabilize_until(&mut report, |report| match budget {
+                StabilizeBudget::Nodes(max) => report.nodes_recalculated - nodes_at_start >= max,
+                StabilizeBudget::Duration(max) => start.elapsed() >= max,
+            })
+            .unwrap_or_else(|err| panic!("{}", err));
+        if complete {
+            self.finish_stabilize(&mut report, start);
+            Some(report)
+        } else {
+            report.duration += start.elapsed();
+            self.partial_stabilize = Some(report);
+            None
+        }
+    }
+
+    /// Recalculates a single node from the recalculation queue, returning which node was
+    /// recalculated and the result of polling it. Starts a new stabilization if one isn't already
+    /// in progress, and finishes it once the queue is empty. Returns `None` if there's nothing
+    /// left to recalculate. Useful for diagnosing why a node is recalculated unexpectedly.
+    ///
+    /// Steps share their in-progress stabilization with `stabilize_budgeted`.
+    pub fn step(&mut self) -> Option<StepInfo> {
+        let start = Instant::now();
+        let mut report = match self.partial_stabilize.take() {
+            Some(report) => report,
+            None if !self.needs_stabilize() => return None,
+            None => self.begin_stabilize(),
+        };
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!("stabilize", generation = report.generation).entered();
+        let info = self
+            .graph
+            .with(|graph| self.recalculate_next(graph, &mut report));
+        #[cfg(feature = "tracing")]
+        drop(span);
+        if let Err(err) = self.take_failure() {
+            panic!("{}", err)
+        }
+        if self.graph.with(|graph| graph.recalc_queued()) {
+            report.duration += start.elapsed();
+            self.partial_stabilize = Some(report);
+        } else {
+            self.finish_stabilize(&mut report, start);
+        }
+        info
+    }
+
+    /// Registers a hook that runs at the start of every stabilization, before dirty marks are
+    /// processed. Any `Var`s set by the hook are seen by that stabilization, making this a good
+    /// place to drain external event queues. Hooks run in the order they were added.
+    pub fn add_pre_stabilize_hook<F: FnMut() + 'static>(&mut self, f: F) {
+        self.pre_stabilize_hooks.push(Box::new(f));
+    }
+
+    /// Registers a hook that runs at the end of every stabilization, after subscription
+    /// callbacks, with that stabilization's report. Hooks run in the order they were added.
+    pub fn add_post_stabilize_hook<F: FnMut(&StabilizeReport) + 'static>(&mut self, f: F) {
+        self.post_stabilize_hooks.push(Box::new(f));
+    }
+
+    /// like `add_post_stabilize_hook`, but the hook is dropped without running again once
+    /// `stopped` is set, which may happen on any thread
+    #[cfg(feature = "debug-server")]
+    fn add_removable_post_stabilize_hook<F: FnMut(&StabilizeReport) + 'static>(
+        &mut self,
+        stopped: Arc<AtomicBool>,
+        f: F,
+    ) {
+        self.removable_hooks.push((stopped, Box::new(f)));
+    }
+
+    fn remove_stopped_hooks(&mut self) {
+        self.removable_hooks
+            .retain(|(stopped, _)| !stopped.load(Ordering::SeqCst));
+    }
+
+    /// Returns a channel that receives an `EngineEvent` for each step of every stabilization,
+    /// for profilers and visualizers built outside this crate. Events are only produced while
+    /// some receiver is alive, so the stream has no cost until it's requested.
+    pub fn event_stream(&mut self) -> Receiver<EngineEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.graph.event_senders.borrow_mut().push(sender);
+        receiver
+    }
+
+    /// Runs `f` to build up a batch of `Var` changes, then applies them all at once and
+    /// stabilizes. No stabilization ever sees only some of the changes, and if `f` panics, none
+    /// of them are applied. Returns the report for the stabilization.
+    pub fn transaction<F: FnOnce(&mut Transaction)>(&mut self, f: F) -> StabilizeReport {
+        let mut tx = Transaction { ops: Vec::new() };
+        f(&mut tx);
+        // an in-progress stabilization might otherwise read some of the new values
+        self.finish_partial_stabilize();
+        for op in tx.ops {
+            op();
+        }
+        self.stabilize()
+    }
+
+    fn finish_partial_stabilize(&mut self) {
+        self.try_finish_partial_stabilize()
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    fn try_finish_partial_stabilize(&mut self) -> Result<(), Error> {
+        if let Some(mut report) = self.partial_stabilize.take() {
+            let start = Instant::now();
+            self.try_stabilize0(&mut report)?;
+            self.finish_stabilize(&mut report, start);
+        }
+        Ok(())
+    }
+
+    fn begin_stabilize(&mut self) -> StabilizeReport {
+        for hook in &mut self.pre_stabilize_hooks {
+            hook();
+        }
+        self.dirty_marks
+            .marks
+            .borrow_mut()
+            .extend(self.clocks.iter().map(|(_, clock)| clock.token()));
         self.update_dirty_marks();
         self.generation.increment();
-        self.stabilize0();
+        self.expire_keep_alive();
+        self.graph.emit(EngineEvent::StabilizeStart {
+            generation: self.generation.get(),
+        });
+        StabilizeReport {
+            generation: self.generation.get(),
+            ..Default::default()
+        }
     }
 
-    /// internal function for stabilization. does not update dirty marks or increment the stabilization number
-    fn stabilize0(&self) {
+    fn finish_stabilize(&mut self, report: &mut StabilizeReport, start: Instant) {
+        for token in &report.observed_updated {
+            if !self.updated_observed.contains(token) {
+                self.updated_observed.push(*token);
+            }
+        }
+        self.run_subscriptions(report);
+        #[cfg(feature = "history")]
+        self.record_history(report);
+        self.evict_cached_outputs();
+        report.duration += start.elapsed();
+        self.graph.emit(EngineEvent::StabilizeEnd {
+            generation: report.generation,
+        });
+        for hook in &mut self.post_stabilize_hooks {
+            hook(report);
+        }
+        self.remove_stopped_hooks();
+        for (_, hook) in &mut self.removable_hooks {
+            hook(report);
+        }
+    }
+
+    /// Returns the tokens of all observed Anchors whose output changed during any stabilization
+    /// since the last call to `take_updated_observed`, including stabilizations triggered by `get`.
+    /// Useful for redrawing only the parts of a UI whose backing Anchors changed.
+    pub fn take_updated_observed(&mut self) -> Vec<AnchorToken> {
+        std::mem::take(&mut self.updated_observed)
+    }
+
+    /// Registers a callback that is called with `anchor`'s new value at the end of every
+    /// stabilization in which `anchor`'s output changed, including the first stabilization
+    /// after subscribing. `anchor` is marked as observed so that it's always kept up-to-date, if
+    /// it isn't already.
+    ///
+    /// Callbacks run in the order they were subscribed. Pass the returned `Subscription` to
+    /// `unsubscribe` to remove the callback.
+    pub fn subscribe<O: 'static, F: FnMut(&O) + 'static>(
+        &mut self,
+        anchor: &Anchor<O>,
+        mut f: F,
+    ) -> Subscription {
+        let observes = self
+            .graph
+            .with(|graph| !graph.lookup(anchor.token()).observed.get());
+        self.observe(anchor.token());
+        let id = self.next_subscription;
+        self.next_subscription += 1;
+        let kept_anchor = anchor.clone();
+        self.subscriptions.push(SubscriptionEntry {
+            id,
+            token: anchor.token(),
+            observes,
+            callback: Box::new(move |val| {
+                let _ = &kept_anchor;
+                f(val.downcast_ref().unwrap())
+            }),
+        });
+        Subscription(id)
+    }
+
+    /// Removes a callback registered with `subscribe`. If subscribing marked the Anchor as
+    /// observed and no other subscriptions exist for it, it's marked as unobserved. Anchors that
+    /// were already observed, or were passed to `mark_observed` since, stay observed.
+    pub fn unsubscribe(&mut self, subscription: Subscription) {
+        let i = match self
+            .subscriptions
+            .iter()
+            .position(|entry| entry.id == subscription.0)
+        {
+            Some(i) => i,
+            None => return,
+        };
+        let entry = self.subscriptions.remove(i);
+        if !entry.observes {
+            return;
+        }
+        // hand the observation over to another subscription to the same Anchor, if any
+        if let Some(other) = self
+            .subscriptions
+            .iter_mut()
+            .find(|e| e.token == entry.token)
+        {
+            other.observes = true;
+        } else {
+            let keep_alive_until = self.keep_alive_until();
+            let kept_alive = &mut self.kept_alive;
+            self.graph.with(|graph| {
+                if let Some(node) = graph.get(entry.token) {
+                    node.observed.set(false);
+                    Self::release_observed(node, keep_alive_until, kept_alive);
+                }
+            })
+        }
+    }
+
+    fn run_subscriptions(&mut self, report: &StabilizeReport) {
+        if self.subscriptions.is_empty() || report.observed_updated.is_empty() {
+            return;
+        }
+        let mut subscriptions = std::mem::take(&mut self.subscriptions);
         self.graph.with(|graph| {
-            while let Some((height, node)) = graph.recalc_pop_next() {
-                let calculation_complete = if graph2::height(node) == height {
-                    // TODO with new graph we can automatically relocate nodes if their height changes
-                    // this nodes height is current, so we can recalculate
-                    self.recalculate(graph, node)
-                } else {
-                    // skip calculation, redo at correct height
-                    false
-                };
+            for entry in &mut subscriptions {
+                if !report.observed_updated.contains(&entry.token) {
+                    continue;
+                }
+                let node = graph.get(entry.token).unwrap();
+                let borrow = node.anchor.borrow();
+                let val = borrow
+                    .as_ref()
+                    .unwrap()
+                    .output(&mut EngineContext { engine: self });
+                (entry.callback)(val);
+            }
+        });
+        self.subscriptions = subscriptions;
+    }
 
-                if !calculation_complete {
-                    graph.queue_recalc(node);
+    /// internal function for stabilization. does not update dirty marks or increment the stabilization number
+    fn stabilize0(&self, report: &mut StabilizeReport) {
+        self.try_stabilize0(report)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    fn try_stabilize0(&self, report: &mut StabilizeReport) -> Result<(), Error> {
+        self.stabilize_until(report, |_| false).map(|_| ())
+    }
+
+    /// processes the recalculation queue until it's empty, `should_stop` returns true, or some
+    /// node fails to recalculate. returns true if the queue was emptied.
+    fn stabilize_until<F: FnMut(&StabilizeReport) -> bool>(
+        &self,
+        report: &mut StabilizeReport,
+        mut should_stop: F,
+    ) -> Result<bool, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("stabilize", generation = report.generation).entered();
+        self.graph.with(|graph| {
+            while !should_stop(report) {
+                let recalculated = self.recalculate_next(graph, report);
+                self.take_failure()?;
+                if recalculated.is_none() {
+                    return Ok(true);
                 }
             }
+            Ok(!graph.recalc_queued())
         })
     }
 
-    /// returns false if calculation is still pending
-    fn recalculate<'a>(&self, graph: Graph2Guard<'a>, node: NodeGuard<'a>) -> bool {
+    fn take_failure(&self) -> Result<(), Error> {
+        match self.failure.borrow_mut().take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// pops the next node from the recalculation queue and recalculates it, skipping over nodes
+    /// whose height has changed since they were queued. returns None if the queue is empty.
+    fn recalculate_next<'a>(
+        &self,
+        graph: Graph2Guard<'a>,
+        report: &mut StabilizeReport,
+    ) -> Option<StepInfo> {
+        loop {
+            let (height, node) = graph.recalc_pop_next()?;
+            if graph2::height(node) != height {
+                // queued nodes are moved when their height changes, so this shouldn't happen; if
+                // it does, skip calculation and redo at correct height
+                graph.queue_recalc(node);
+                continue;
+            }
+            // this nodes height is current, so we can recalculate
+            let previous_update = node.last_update.get();
+            let poll = if self.time_recalculations {
+                let start = Instant::now();
+                let poll = self.recalculate(graph, node);
+                node.recalc_time
+                    .set(node.recalc_time.get() + start.elapsed());
+                poll
+            } else {
+                self.recalculate(graph, node)
+            };
+            node.recalculations.set(node.recalculations.get() + 1);
+            #[cfg(feature = "tracing")]
+            tracing::trace!(
+                location = %node.debug_info.get(),
+                height,
+                poll = ?poll,
+                "recalculated anchor"
+            );
+            self.graph.emit(EngineEvent::NodeRecalculated {
+                id: node.key().id(),
+                height,
+                poll,
+            });
+            if poll == Poll::Pending {
+                graph.queue_recalc(node);
+            } else {
+                report.nodes_recalculated += 1;
+                let updated = node.last_update.get();
+                if node.observed.get() && updated != previous_update {
+                    report.observed_updated.push(node.key());
+                }
+            }
+            return Some(StepInfo {
+                token: node.key(),
+                height,
+                poll,
+            });
+        }
+    }
+
+    /// returns Poll::Pending if calculation is still pending
+    fn recalculate<'a>(&self, graph: Graph2Guard<'a>, node: NodeGuard<'a>) -> Poll {
         let this_anchor = &node.anchor;
+        let mut dirtied_children = node.dirtied_children.take();
+        dirtied_children.sort_unstable_by_key(|key| key.ptr);
+        dirtied_children.dedup();
         let mut ecx = EngineContextMut {
             engine: &self,
             node,
             graph,
             pending_on_anchor_get: false,
+            poisoned_input: None,
+            dirtied_children,
+        };
+        if node.poison.replace(None).is_some() {
+            // parents saw the poison, so they need to see whatever we recover to
+            mark_dirty(graph, node, true);
+            node.last_update.set(Some(self.generation));
+        }
+        let poll_result = if self.catch_panics {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                this_anchor
+                    .borrow_mut()
+                    .as_mut()
+                    .unwrap()
+                    .poll_updated(&mut ecx)
+            }));
+            result.map_err(|payload| Error::Poisoned {
+                anchor: node.debug_info.get(),
+                message: panic_message(&*payload),
+            })
+        } else {
+            Ok(this_anchor
+                .borrow_mut()
+                .as_mut()
+                .unwrap()
+                .poll_updated(&mut ecx))
+        };
+        let mut dirtied_children = ecx.dirtied_children;
+        if poll_result == Ok(Poll::Pending) {
+            // keep them around until a poll actually finishes
+            dirtied_children.append(&mut node.dirtied_children.borrow_mut());
+        } else {
+            dirtied_children.clear();
+        }
+        node.dirtied_children.replace(dirtied_children);
+        let poll_result = match (poll_result, ecx.poisoned_input) {
+            (Ok(poll), None) => poll,
+            (Err(err), _) | (Ok(_), Some(err)) => {
+                // leave the node ready so it isn't recalculated until an input changes, and make
+                // sure parents find out about the poison
+                node.poison.replace(Some(err));
+                node.last_ready.set(Some(self.generation));
+                mark_dirty(graph, node, true);
+                return Poll::Unchanged;
+            }
         };
-        let poll_result = this_anchor
-            .borrow_mut()
-            .as_mut()
-            .unwrap()
-            .poll_updated(&mut ecx);
         let pending_on_anchor_get = ecx.pending_on_anchor_get;
+        let poll_result = if poll_result == Poll::Updated && self.output_unchanged(node) {
+            Poll::Unchanged
+        } else {
+            poll_result
+        };
         match poll_result {
             Poll::Pending => {
                 if pending_on_anchor_get {
                     // looks like we requested an anchor that isn't yet calculated, so we
                     // reinsert into the graph directly; our height either was higher than this
                     // requested anchor's already, or it was updated so it's higher now.
-                    false
+                    Poll::Pending
                 } else {
                     // in the future, this means we polled on some non-anchors future. since
                     // that isn't supported for now, this just means something went wrong
@@ -243,59 +1565,150 @@ impl Engine {
                 mark_dirty(graph, node, true);
                 node.last_update.set(Some(self.generation));
                 node.last_ready.set(Some(self.generation));
-                true
+                self.track_cached_output(node);
+                self.store_inline_output(node);
+                Poll::Updated
             }
             Poll::Unchanged => {
                 node.last_ready.set(Some(self.generation));
-                true
+                self.track_cached_output(node);
+                self.store_inline_output(node);
+                Poll::Unchanged
             }
         }
     }
 
     /// Returns a debug string containing the current state of the recomputation graph.
     pub fn debug_state(&self) -> String {
-        let debug = "".to_string();
-        // for (node_id, _) in nodes.iter() {
-        //     let node = self.graph.get(node_id).unwrap();
-        //     let necessary = if self.graph.is_necessary(node_id) {
-        //         "necessary"
-        //     } else {
-        //         "   --    "
-        //     };
-        //     let observed = if Self::check_observed_raw(node) == ObservedState::Observed {
-        //         "observed"
-        //     } else {
-        //         "   --   "
-        //     };
-        //     let state = match self.to_recalculate.borrow_mut().state(node_id) {
-        //         RecalcState::NeedsRecalc => "NeedsRecalc  ",
-        //         RecalcState::PendingRecalc => "PendingRecalc",
-        //         RecalcState::Ready => "Ready        ",
-        //     };
-        //     debug += &format!(
-        //         "{:>80}  {}  {}  {}\n",
-        //         node.debug_info.get().to_string(),
-        //         necessary,
-        //         observed,
-        //         state
-        //     );
-        // }
+        let mut debug = "".to_string();
+        self.graph.with(|graph| {
+            for node in graph.live_nodes() {
+                let necessary = if Self::check_observed_raw(node) != ObservedState::Unnecessary {
+                    "necessary"
+                } else {
+                    "   --    "
+                };
+                let observed = if Self::check_observed_raw(node) == ObservedState::Observed {
+                    "observed"
+                } else {
+                    "   --   "
+                };
+                let state = match graph2::recalc_state(node) {
+                    RecalcState::Needed => "Needed ",
+                    RecalcState::Pending => "Pending",
+                    RecalcState::Ready => "Ready  ",
+                };
+                debug += &format!(
+                    "{:>80}  {:>4}  {}  {}  {}\n",
+                    node.debug_info.get().to_string(),
+                    graph2::height(node),
+                    necessary,
+                    observed,
+                    state
+                );
+            }
+        });
         debug
     }
 
     pub fn check_observed<T>(&self, anchor: &Anchor<T>) -> ObservedState {
         self.graph.with(|graph| {
-            let node = graph.get(anchor.token()).unwrap();
+            let node = graph.lookup(anchor.token());
             Self::check_observed_raw(node)
         })
     }
 
+    /// Returns the engine's bookkeeping for `anchor`, for debugging why it was or wasn't
+    /// recalculated.
+    pub fn node_info<O>(&self, anchor: &Anchor<O>) -> NodeInfo {
+        self.graph.with(|graph| {
+            let node = graph.lookup(anchor.token());
+            NodeInfo {
+                height: graph2::height(node),
+                observed: Self::check_observed_raw(node),
+                recalc_state: graph2::recalc_state(node),
+                last_ready: node.last_ready.get().map(Generation::get),
+                last_update: node.last_update.get().map(Generation::get),
+                necessary_count: node.necessary_count.get(),
+                debug_info: node.debug_info.get(),
+            }
+        })
+    }
+
+    /// Returns the Anchors `anchor` read during its last recalculation. Like `topology`, this only
+    /// includes edges the engine still tracks: an input that changed since `anchor` last
+    /// recalculated is only listed if `anchor` is necessary.
+    pub fn dependencies<O>(&self, anchor: &Anchor<O>) -> Vec<(AnchorToken, AnchorDebugInfo)> {
+        self.graph.with(|graph| {
+            let node = graph.lookup(anchor.token());
+            let mut found = HashSet::new();
+            let mut deps = vec![];
+            let necessary = node.necessary_children().collect::<Vec<_>>();
+            let inputs = graph
+                .live_nodes()
+                .into_iter()
+                .filter(|child| child.clean_parents().any(|parent| parent == node));
+            for dep in necessary.into_iter().chain(inputs) {
+                if found.insert(dep.key()) {
+                    deps.push((dep.key(), dep.debug_info.get()));
+                }
+            }
+            deps
+        })
+    }
+
+    /// Returns the Anchors that will be recalculated, or marked as needing recalculation, when
+    /// `anchor`'s output changes.
+    pub fn dependents<O>(&self, anchor: &Anchor<O>) -> Vec<(AnchorToken, AnchorDebugInfo)> {
+        self.graph.with(|graph| {
+            let node = graph.lookup(anchor.token());
+            let mut found = HashSet::new();
+            node.clean_parents()
+                .filter(|parent| found.insert(parent.key()))
+                .map(|parent| (parent.key(), parent.debug_info.get()))
+                .collect()
+        })
+    }
+
+    /// Returns the tokens of every live Anchor whose debug info matches `predicate`, in no
+    /// particular order. Useful for finding Anchors by name or source location from debugging
+    /// tools, without threading handles to them everywhere.
+    pub fn find_anchors<F: FnMut(&AnchorDebugInfo) -> bool>(
+        &self,
+        mut predicate: F,
+    ) -> Vec<AnchorToken> {
+        self.graph.with(|graph| {
+            graph
+                .live_nodes()
+                .into_iter()
+                .filter(|node| predicate(&node.debug_info.get()))
+                .map(|node| node.key())
+                .collect()
+        })
+    }
+
+    /// Recovers an Anchor from its token, for code that stores tokens rather than Anchors, such as
+    /// ECS components or serialized state. The returned Anchor keeps its node alive like any other
+    /// clone. Returns `None` if the Anchor has been freed, belongs to a different engine, or
+    /// doesn't output a `T`.
+    pub fn upgrade<T: 'static>(&self, token: AnchorToken) -> Option<Anchor<T>> {
+        let output_matches = self.graph.with(|graph| {
+            let node = graph.get(token)?;
+            let anchor = node.anchor.borrow();
+            Some(anchor.as_ref()?.output_type() == TypeId::of::<T>())
+        });
+        if output_matches != Some(true) {
+            return None;
+        }
+        self.graph.handle_for(token).map(Anchor::new_from_expert)
+    }
+
     /// Returns whether an Anchor is Observed, Necessary, or Unnecessary.
     pub fn check_observed_raw<'a>(node: NodeGuard<'a>) -> ObservedState {
         if node.observed.get() {
             return ObservedState::Observed;
         }
-        if node.necessary_count.get() > 0 {
+        if node.necessary_count.get() > 0 || node.keep_alive_until.get().is_some() {
             ObservedState::Necessary
         } else {
             ObservedState::Unnecessary
@@ -309,13 +1722,7 @@ fn mark_dirty<'a>(graph: Graph2Guard<'a>, node: NodeGuard<'a>, skip_self: bool)
     if skip_self {
         let parents = node.drain_clean_parents();
         for parent in parents {
-            // TODO still calling dirty twice on observed relationships
-            parent
-                .anchor
-                .borrow_mut()
-                .as_mut()
-                .unwrap()
-                .dirty(&node.key());
+            dirty_parent(parent, node.key());
             mark_dirty0(graph, parent);
         }
     } else {
@@ -325,30 +1732,518 @@ fn mark_dirty<'a>(graph: Graph2Guard<'a>, node: NodeGuard<'a>, skip_self: bool)
 
 fn mark_dirty0<'a>(graph: Graph2Guard<'a>, next: NodeGuard<'a>) {
     let id = next.key();
-    if Engine::check_observed_raw(next) != ObservedState::Unnecessary {
-        graph.queue_recalc(next);
-    } else if graph2::recalc_state(next) == RecalcState::Ready {
+    if Engine::check_observed_raw(next) != ObservedState::Unnecessary
+        && graph.queue_recalc_pushed(next)
+    {
+        return;
+    }
+    // unnecessary nodes, and necessary ones whose recalculation is deferred, are only recalculated
+    // when requested, so they and their parents must look out of date until then
+    if graph2::recalc_state(next) == RecalcState::Ready {
         graph2::needs_recalc(next);
         let parents = next.drain_clean_parents();
         for parent in parents {
-            if let Some(v) = parent.anchor.borrow_mut().as_mut() {
-                v.dirty(&id);
+            if dirty_parent(parent, id) {
                 mark_dirty0(graph, parent);
             }
         }
     }
 }
 
+/// calls `parent`'s `dirty` method for `child`, and records `child` for
+/// `UpdateContext::dirtied_children`. returns false if `parent` has no `AnchorInner`
+fn dirty_parent(parent: NodeGuard<'_>, child: NodeKey) -> bool {
+    match parent.anchor.borrow_mut().as_mut() {
+        Some(anchor) => {
+            anchor.dirty(&child);
+            parent.dirtied_children.borrow_mut().push(child);
+            true
+        }
+        None => false,
+    }
+}
+
+/// drops a node's cached output if its `AnchorInner` supports it, returning whether it did
+fn evict_output<'a>(graph: Graph2Guard<'a>, node: NodeGuard<'a>) -> bool {
+    let evicted = match node.anchor.borrow_mut().as_mut() {
+        Some(anchor) => anchor.evict_output(),
+        None => false,
+    };
+    if evicted {
+        node.cutoff_output.replace(None);
+        invalidate_evicted(graph, node);
+    }
+    evicted
+}
+
+/// returns true if an observed or necessary Anchor reads `node`'s output, directly or through
+/// the unnecessary parents that evicting it would invalidate. Under `Strategy::Pull` nothing is
+/// necessary, so the inputs of observed Anchors look unnecessary too.
+fn feeds_needed_anchor(node: NodeGuard<'_>) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        for parent in node.clean_parents() {
+            if Engine::check_observed_raw(parent) != ObservedState::Unnecessary {
+                return true;
+            }
+            if visited.insert(parent.key()) {
+                stack.push(parent);
+            }
+        }
+    }
+    false
+}
+
+/// marks a node whose output was evicted as needing recalculation, along with any unnecessary
+/// parents that may read from its output, and queues necessary parents
+fn invalidate_evicted<'a>(graph: Graph2Guard<'a>, node: NodeGuard<'a>) {
+    let id = node.key();
+    node.last_ready.set(None);
+    node.inline_output.clear();
+    graph2::needs_recalc(node);
+    for parent in node.drain_clean_parents() {
+        dirty_parent(parent, id);
+        if Engine::check_observed_raw(parent) == ObservedState::Unnecessary {
+            invalidate_evicted(graph, parent);
+        } else {
+            graph.queue_recalc_pushed(parent);
+        }
+    }
+}
+
+/// extracts the message from a panic payload, which is usually a `&str` or `String`
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+/// A handle to a callback registered with `Engine::subscribe`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Subscription(u64);
+
+type SubscriptionCallback = Box<dyn FnMut(&dyn Any)>;
+type PreStabilizeHook = Box<dyn FnMut()>;
+type PostStabilizeHook = Box<dyn FnMut(&StabilizeReport)>;
+
+struct SubscriptionEntry {
+    id: u64,
+    token: NodeKey,
+    /// true if this subscription marked the Anchor observed, and should unobserve it when removed
+    observes: bool,
+    callback: SubscriptionCallback,
+}
+
+/// A batch of `Var` changes built up by `Engine::transaction`.
+pub struct Transaction {
+    ops: Vec<Box<dyn FnOnce()>>,
+}
+
+impl Transaction {
+    /// Queues `var` to be set to `val` when the transaction is applied. If the same `Var` is set
+    /// more than once, the last value wins.
+    pub fn set<T: 'static>(&mut self, var: &Var<T>, val: T) {
+        let var = var.clone();
+        self.ops.push(Box::new(move || var.set(val)));
+    }
+}
+
+/// A limit on the amount of work done by `Engine::stabilize_budgeted`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StabilizeBudget {
+    /// Stop after recalculating this many nodes.
+    Nodes(usize),
+
+    /// Stop once this much time has elapsed. The budget is checked between node
+    /// recalculations, so a single slow node may exceed it.
+    Duration(Duration),
+}
+
+/// Identifies an Anchor in `EngineEvent`s. Unlike `AnchorToken`, it's `Send`, so events can be
+/// handled on other threads, but it can't be used to look up the Anchor. Get an Anchor's id from
+/// its token with `AnchorToken::id`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AnchorId {
+    graph: u32,
+    /// the Anchor's slot in the graph, which is reused by later Anchors once it's dropped
+    index: u32,
+    /// distinguishes the Anchors that have used the slot
+    generation: u32,
+}
+
+/// Something that happened inside an Engine, sent to receivers created with
+/// `Engine::event_stream`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineEvent {
+    /// A stabilization started.
+    StabilizeStart { generation: u64 },
+
+    /// A stabilization finished, after running subscription callbacks but before post-stabilize
+    /// hooks.
+    StabilizeEnd { generation: u64 },
+
+    /// An Anchor was marked dirty by its `DirtyHandle`, for instance because a `Var` was set.
+    DirtyMarked { id: AnchorId },
+
+    /// An Anchor was added to the recalculation queue.
+    NodeQueued { id: AnchorId, height: usize },
+
+    /// An Anchor was polled. `Poll::Pending` means it requested an input that wasn't ready yet,
+    /// and has been queued to be polled again.
+    NodeRecalculated {
+        id: AnchorId,
+        height: usize,
+        poll: Poll,
+    },
+}
+
+/// Describes a single node recalculation performed by `Engine::step`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepInfo {
+    /// The token of the recalculated Anchor.
+    pub token: AnchorToken,
+
+    /// The height of the recalculated Anchor in the graph.
+    pub height: usize,
+
+    /// The result of polling the Anchor. `Poll::Pending` means the Anchor requested an input that
+    /// wasn't ready yet, and it has been queued to be polled again.
+    pub poll: Poll,
+}
+
+/// Counts of the nodes and edges in an Engine's graph, returned by `Engine::stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EngineStats {
+    /// The number of live nodes, one for each Anchor that hasn't been dropped.
+    pub nodes: usize,
+
+    /// The number of freed nodes waiting to be reused by new Anchors.
+    pub free_nodes: usize,
+
+    /// The number of edges from necessary nodes to the children they keep necessary.
+    pub necessary_edges: usize,
+
+    /// The number of edges from nodes to the parents that are notified when they change.
+    pub clean_parent_edges: usize,
+
+    /// An estimate of the memory used by the graph, in bytes. Heap memory owned by Anchor
+    /// outputs, such as the contents of a `Vec`, isn't counted.
+    pub approx_bytes: usize,
+}
+
+/// How much work an Anchor has cost, returned by `Engine::hotspots`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hotspot {
+    /// The token of the Anchor.
+    pub token: AnchorToken,
+
+    /// Where the Anchor was created, and what type implements it.
+    pub debug_info: AnchorDebugInfo,
+
+    /// How many times the Anchor has been polled, including polls that returned `Poll::Pending`.
+    pub recalculations: u64,
+
+    /// Total time spent polling the Anchor. Always zero unless the Engine was built with
+    /// `EngineBuilder::time_recalculations`.
+    pub recalc_time: Duration,
+}
+
+/// The engine's bookkeeping for a single Anchor, returned by `Engine::node_info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeInfo {
+    /// The node's height. Every node is higher than the nodes it depends on.
+    pub height: usize,
+
+    /// Whether the node is observed, or necessary for some observed node.
+    pub observed: ObservedState,
+
+    /// Whether the node's output is up-to-date.
+    pub recalc_state: RecalcState,
+
+    /// The generation in which the node last finished recalculating, if it ever has.
+    pub last_ready: Option<u64>,
+
+    /// The generation in which the node's output last changed, if it ever has.
+    pub last_update: Option<u64>,
+
+    /// The number of necessary nodes that keep this node necessary.
+    pub necessary_count: usize,
+
+    /// Where the Anchor was created, and what type implements it.
+    pub debug_info: AnchorDebugInfo,
+}
+
+/// The shape of an Engine's graph, returned by `Engine::topology`. Each distribution is indexed
+/// by value, so `height_histogram[3]` is the number of nodes at height 3.
+///
+/// Edges are the ones the engine currently tracks from a node to the parents it notifies when it
+/// changes. These are removed when a node is marked dirty and added again when its parents
+/// recalculate, so stabilize before calling `topology` to see the whole graph.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TopologyStats {
+    /// The number of live nodes at each height.
+    pub height_histogram: Vec<usize>,
+
+    /// The number of live nodes with each number of inputs.
+    pub fan_in: Vec<usize>,
+
+    /// The number of live nodes with each number of dependents.
+    pub fan_out: Vec<usize>,
+}
+
+/// A summary of the work done by a call to `Engine::stabilize`.
+#[derive(Debug, Clone, Default)]
+pub struct StabilizeReport {
+    /// The generation number of this stabilization. Incremented on every call to `stabilize`.
+    pub generation: u64,
+
+    /// The number of nodes that were recalculated.
+    pub nodes_recalculated: usize,
+
+    /// Tokens for the observed Anchors whose output changed.
+    pub observed_updated: Vec<AnchorToken>,
+
+    /// How long the stabilization took.
+    pub duration: Duration,
+}
+
 /// Singlethread's implementation of Anchors' `DirtyHandle`, which allows a node with non-Anchors inputs to manually mark itself as dirty.
 #[derive(Debug, Clone)]
 pub struct DirtyHandle {
     num: NodeKey,
-    dirty_marks: Rc<RefCell<Vec<NodeKey>>>,
+    dirty_marks: Rc<DirtyMarks>,
+    send_dirty_marks: Arc<SendDirtyMarks>,
 }
 impl crate::expert::DirtyHandle for DirtyHandle {
     fn mark_dirty(&self) {
-        self.dirty_marks.borrow_mut().push(self.num);
+        self.dirty_marks.push(self.num);
+    }
+
+    fn mark_dirty_with<P: Any + Send>(&self, payload: P) {
+        self.dirty_marks
+            .payloads
+            .borrow_mut()
+            .push((self.num, Box::new(payload)));
+        self.mark_dirty();
+    }
+}
+
+/// Keys marked dirty since the last stabilization, in the order they were first marked. Marking
+/// a key again before the next stabilization does nothing.
+#[derive(Debug)]
+struct MarkSet<K> {
+    order: Vec<K>,
+    marked: HashSet<K>,
+}
+
+impl<K> Default for MarkSet<K> {
+    fn default() -> Self {
+        MarkSet {
+            order: Vec::new(),
+            marked: HashSet::new(),
+        }
+    }
+}
+
+impl<K: Copy + Eq + std::hash::Hash> MarkSet<K> {
+    /// Returns true if `key` wasn't already marked.
+    fn insert(&mut self, key: K) -> bool {
+        let inserted = self.marked.insert(key);
+        if inserted {
+            self.order.push(key);
+        }
+        inserted
+    }
+
+    fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    fn take(&mut self) -> Vec<K> {
+        self.marked.clear();
+        std::mem::take(&mut self.order)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.order.shrink_to_fit();
+        self.marked.shrink_to_fit();
+    }
+}
+
+impl<K: Copy + Eq + std::hash::Hash> Extend<K> for MarkSet<K> {
+    fn extend<I: IntoIterator<Item = K>>(&mut self, keys: I) {
+        for key in keys {
+            self.insert(key);
+        }
+    }
+}
+
+/// Dirty marks queued up since the last stabilization.
+struct DirtyMarks {
+    marks: RefCell<MarkSet<NodeKey>>,
+    /// payloads from `mark_dirty_with`, delivered before the marks are applied
+    payloads: RefCell<Vec<(NodeKey, Box<dyn Any>)>>,
+    graph: Weak<Graph2>,
+    wake: RefCell<Option<Box<dyn FnMut()>>>,
+    /// true if the wake callback has been called since the last stabilization
+    woken: Cell<bool>,
+}
+
+impl DirtyMarks {
+    fn push(&self, key: NodeKey) {
+        if !self.marks.borrow_mut().insert(key) {
+            return;
+        }
+        if self.woken.get() || self.wake.borrow().is_none() {
+            return;
+        }
+        let is_necessary = match self.graph.upgrade() {
+            Some(graph) => graph.with(|graph| match graph.get(key) {
+                Some(node) => Engine::check_observed_raw(node) != ObservedState::Unnecessary,
+                None => false,
+            }),
+            None => false,
+        };
+        if is_necessary {
+            self.woken.set(true);
+            if let Some(wake) = &mut *self.wake.borrow_mut() {
+                wake();
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for DirtyMarks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DirtyMarks")
+            .field("marks", &self.marks)
+            .finish()
+    }
+}
+
+impl DirtyHandle {
+    /// Returns a `SendDirtyHandle` for the same node, which can be moved to and used from other threads.
+    pub fn to_send(&self) -> SendDirtyHandle {
+        SendDirtyHandle {
+            num: SendNodeKey(self.num),
+            marks: self.send_dirty_marks.clone(),
+        }
+    }
+
+    /// Marks every handle in `handles` dirty. Equivalent to calling `mark_dirty` on each, which
+    /// is already cheap: marking a node that's been marked since the last stabilization does
+    /// nothing, and the wake callback is called at most once.
+    pub fn mark_dirty_batch<'a, I: IntoIterator<Item = &'a DirtyHandle>>(handles: I) {
+        for handle in handles {
+            handle.dirty_marks.push(handle.num);
+        }
+    }
+}
+
+/// A thread-safe version of `DirtyHandle`, created with `DirtyHandle::to_send`. Useful for `AnchorInner`s
+/// wrapping some external resource that reports changes from a callback thread. Dirty marks are
+/// applied the next time the engine stabilizes.
+#[derive(Debug, Clone)]
+pub struct SendDirtyHandle {
+    num: SendNodeKey,
+    marks: Arc<SendDirtyMarks>,
+}
+impl crate::expert::DirtyHandle for SendDirtyHandle {
+    fn mark_dirty(&self) {
+        Self::mark_dirty_batch(std::iter::once(self));
+    }
+
+    fn mark_dirty_with<P: Any + Send>(&self, payload: P) {
+        self.marks
+            .payloads
+            .lock()
+            .unwrap()
+            .push((self.num, Box::new(payload)));
+        self.mark_dirty();
+    }
+}
+
+impl SendDirtyHandle {
+    /// Marks every handle in `handles` dirty. Each engine's mark list is locked once per run of
+    /// consecutive handles belonging to it, and its wake callback is called once per run rather
+    /// than once per handle. Nodes that have been marked since the last stabilization are
+    /// skipped, and the callback isn't called for a run made up only of those.
+    pub fn mark_dirty_batch<'a, I: IntoIterator<Item = &'a SendDirtyHandle>>(handles: I) {
+        let mut handles = handles.into_iter().peekable();
+        while let Some(first) = handles.next() {
+            let mut marks = first.marks.marks.lock().unwrap();
+            let mut marked_any = marks.insert(first.num);
+            while let Some(handle) = handles.next_if(|h| Arc::ptr_eq(&h.marks, &first.marks)) {
+                marked_any |= marks.insert(handle.num);
+            }
+            drop(marks);
+            if !marked_any {
+                continue;
+            }
+            if let Some(wake) = &*first.marks.wake.lock().unwrap() {
+                wake();
+            }
+        }
+    }
+}
+
+/// A NodeKey that may be sent between threads. It is only ever looked up on the engine's thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct SendNodeKey(NodeKey);
+unsafe impl Send for SendNodeKey {}
+
+#[derive(Default)]
+struct SendDirtyMarks {
+    marks: Mutex<MarkSet<SendNodeKey>>,
+    payloads: Mutex<Vec<(SendNodeKey, Box<dyn Any + Send>)>>,
+    wake: Mutex<Option<Box<dyn Fn() + Send + Sync>>>,
+}
+
+impl std::fmt::Debug for SendDirtyMarks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SendDirtyMarks")
+            .field("marks", &self.marks)
+            .finish()
+    }
+}
+
+/// Borrows a node's anchor without holding a `RefCell` guard, so outputs can be handed out for
+/// longer than a guard could live.
+///
+/// # Safety
+/// The anchor must not be mutably borrowed or replaced while the returned reference is alive.
+unsafe fn borrow_anchor_unguarded<'out>(
+    node: NodeGuard<'_>,
+) -> &'out Option<Box<dyn GenericAnchor>> {
+    // `try_borrow_unguarded` still checks for an outstanding mutable borrow, which lets Miri
+    // validate callers; the raw pointer skips that check in regular builds
+    #[cfg(miri)]
+    let ptr: *const _ = node
+        .anchor
+        .try_borrow_unguarded()
+        .expect("anchor was mutably borrowed while its output was read");
+    #[cfg(not(miri))]
+    let ptr: *const _ = node.anchor.as_ptr();
+    &*ptr
+}
+
+/// Reads `node`'s output as an `O`. Nodes record their output's `TypeId` when they're created,
+/// so checking the type is a field comparison instead of a call through `dyn Any`, which adds up
+/// in context `get`s, called for every input of every recalculated node.
+///
+/// # Safety
+/// Same as `borrow_anchor_unguarded`.
+unsafe fn typed_output<'out, O: 'static>(engine: &'out Engine, node: NodeGuard<'_>) -> &'out O {
+    if node.output_type.get() != Some(TypeId::of::<O>()) {
+        panic!("anchor's output is not a {}", std::any::type_name::<O>());
     }
+    let anchor = borrow_anchor_unguarded(node).as_ref().unwrap();
+    &*(anchor.output_ptr(&mut EngineContext { engine }) as *const O)
 }
 
 struct EngineContext<'eng> {
@@ -360,6 +2255,10 @@ struct EngineContextMut<'eng, 'gg> {
     graph: Graph2Guard<'gg>,
     node: NodeGuard<'gg>,
     pending_on_anchor_get: bool,
+    /// set if this node requested a poisoned input, so it should be poisoned too
+    poisoned_input: Option<Error>,
+    /// the node's `dirtied_children`, taken out for the duration of the poll
+    dirtied_children: Vec<NodeKey>,
 }
 
 impl<'eng> OutputContext<'eng> for EngineContext<'eng> {
@@ -370,109 +2269,152 @@ impl<'eng> OutputContext<'eng> for EngineContext<'eng> {
         'eng: 'out,
     {
         self.engine.graph.with(|graph| {
-            let node = graph.get(anchor.token()).unwrap();
+            let node = graph.lookup(anchor.token());
             if graph2::recalc_state(node) != RecalcState::Ready {
                 panic!("attempted to get node that was not previously requested")
             }
-            let unsafe_borrow = unsafe { node.anchor.as_ptr().as_ref().unwrap() };
-            let output: &O = unsafe_borrow
-                .as_ref()
-                .unwrap()
-                .output(&mut EngineContext {
-                    engine: self.engine,
-                })
-                .downcast_ref()
-                .unwrap();
-            output
+            unsafe { typed_output(self.engine, node) }
         })
     }
 }
 
-impl<'eng, 'gg> UpdateContext for EngineContextMut<'eng, 'gg> {
-    type Engine = Engine;
-
-    fn get<'out, 'slf, O: 'static>(&'slf self, anchor: &Anchor<O>) -> &'out O
-    where
-        'slf: 'out,
-    {
-        self.engine.graph.with(|graph| {
-            let node = graph.get(anchor.token()).unwrap();
-            if graph2::recalc_state(node) != RecalcState::Ready {
-                panic!("attempted to get node that was not previously requested")
-            }
-
-            let unsafe_borrow = unsafe { node.anchor.as_ptr().as_ref().unwrap() };
-            let output: &O = unsafe_borrow
-                .as_ref()
-                .unwrap()
-                .output(&mut EngineContext {
-                    engine: self.engine,
-                })
-                .downcast_ref()
-                .unwrap();
-            output
-        })
+impl<'eng, 'gg> EngineContextMut<'eng, 'gg> {
+    /// whether this node's necessary inputs should be marked necessary too
+    fn is_necessary(&self) -> bool {
+        // in pull mode, nothing is necessary, so changes are never pushed to this node's inputs
+        self.engine.strategy == Strategy::Push
+            && Engine::check_observed_raw(self.node) != ObservedState::Unnecessary
     }
 
-    fn request<'out, O: 'static>(&mut self, anchor: &Anchor<O>, necessary: bool) -> Poll {
-        let child = self.graph.get(anchor.token()).unwrap();
+    /// does the work of `request` for `child`, except for marking it necessary, which callers do
+    /// themselves if the returned bool is true
+    fn request_node(&mut self, child: NodeGuard<'gg>) -> (Poll, bool) {
         let height_already_increased = match graph2::ensure_height_increases(child, self.node) {
             Ok(v) => v,
-            Err(()) => {
-                panic!("loop detected in anchors!\n");
+            Err(err) => {
+                // stop the stabilization; this node is requeued, and will fail again next time
+                // it's recalculated
+                *self.engine.failure.borrow_mut() = Some(err);
+                self.pending_on_anchor_get = true;
+                return (Poll::Pending, false);
             }
         };
 
-        let self_is_necessary = Engine::check_observed_raw(self.node) != ObservedState::Unnecessary;
+        if let Some(err) = &*child.poison.borrow() {
+            if graph2::recalc_state(child) == RecalcState::Ready && height_already_increased {
+                // stay subscribed to the child, so we're recalculated once it recovers
+                child.add_clean_parent(self.node);
+                self.poisoned_input = Some(err.clone());
+                self.pending_on_anchor_get = true;
+                return (Poll::Pending, true);
+            }
+        }
 
         if graph2::recalc_state(child) != RecalcState::Ready {
             self.pending_on_anchor_get = true;
             self.graph.queue_recalc(child);
-            if necessary && self_is_necessary {
-                self.node.add_necessary_child(child);
-            }
-            Poll::Pending
+            (Poll::Pending, true)
         } else if !height_already_increased {
             self.pending_on_anchor_get = true;
-            Poll::Pending
+            (Poll::Pending, false)
         } else {
             child.add_clean_parent(self.node);
-            if necessary && self_is_necessary {
-                self.node.add_necessary_child(child);
-            }
-            match (child.last_update.get(), self.node.last_ready.get()) {
+            let poll = match (child.last_update.get(), self.node.last_ready.get()) {
                 (Some(a), Some(b)) if a <= b => Poll::Unchanged,
                 _ => Poll::Updated,
+            };
+            (poll, true)
+        }
+    }
+}
+
+impl<'eng, 'gg> UpdateContext for EngineContextMut<'eng, 'gg> {
+    type Engine = Engine;
+
+    fn get<'out, 'slf, O: 'static>(&'slf self, anchor: &Anchor<O>) -> &'out O
+    where
+        'slf: 'out,
+    {
+        self.engine.graph.with(|graph| {
+            let node = graph.lookup(anchor.token());
+            if graph2::recalc_state(node) != RecalcState::Ready {
+                panic!("attempted to get node that was not previously requested")
+            }
+
+            unsafe { typed_output(self.engine, node) }
+        })
+    }
+
+    fn request<'out, O: 'static>(&mut self, anchor: &Anchor<O>, necessary: bool) -> Poll {
+        let child = self.graph.lookup(anchor.token());
+        let (poll, subscribed) = self.request_node(child);
+        if subscribed && necessary && self.is_necessary() {
+            self.node.add_necessary_child(child);
+        }
+        poll
+    }
+
+    fn request_many<'a, O: 'static, I: IntoIterator<Item = &'a Anchor<O>>>(
+        &mut self,
+        anchors: I,
+        necessary: bool,
+    ) -> Poll {
+        let self_is_necessary = necessary && self.is_necessary();
+        let mut necessary_children = Vec::new();
+        let mut poll = Poll::Unchanged;
+        for anchor in anchors {
+            let child = self.graph.lookup(anchor.token());
+            let (child_poll, subscribed) = self.request_node(child);
+            if subscribed && self_is_necessary {
+                necessary_children.push(child);
             }
+            poll = poll.combine(child_poll);
         }
+        self.node.add_necessary_children(&mut necessary_children);
+        poll
     }
 
     fn unrequest<'out, O: 'static>(&mut self, anchor: &Anchor<O>) {
-        let child = self.graph.get(anchor.token()).unwrap();
+        let child = self.graph.lookup(anchor.token());
         self.node.remove_necessary_child(child);
         Engine::update_necessary_children(child);
     }
 
+    fn dirtied_children(&self) -> Option<&[NodeKey]> {
+        Some(&self.dirtied_children)
+    }
+
     fn dirty_handle(&mut self) -> DirtyHandle {
         DirtyHandle {
             num: self.node.key(),
             dirty_marks: self.engine.dirty_marks.clone(),
+            send_dirty_marks: self.engine.send_dirty_marks.clone(),
         }
     }
 }
 
 trait GenericAnchor {
     fn dirty(&mut self, child: &NodeKey);
+    fn dirty_payload(&mut self, payload: Box<dyn Any>);
     fn poll_updated<'eng, 'gg>(&mut self, ctx: &mut EngineContextMut<'eng, 'gg>) -> Poll;
     fn output<'slf, 'out>(&'slf self, ctx: &mut EngineContext<'out>) -> &'out dyn Any
     where
         'slf: 'out;
+    /// like `output`, but without the `dyn Any` wrapper; see `typed_output`
+    fn output_ptr<'slf>(&'slf self, ctx: &mut EngineContext<'slf>) -> *const ();
+    fn output_rc(&self) -> Option<&dyn Any>;
+    fn output_type(&self) -> TypeId;
+    fn evict_output(&mut self) -> bool;
+    fn store_inline_output<'slf>(&'slf self, ctx: &mut EngineContext<'slf>, slot: &InlineOutput);
     fn debug_info(&self) -> AnchorDebugInfo;
 }
 impl<I: AnchorInner<Engine> + 'static> GenericAnchor for I {
     fn dirty(&mut self, child: &NodeKey) {
         AnchorInner::dirty(self, child)
     }
+    fn dirty_payload(&mut self, payload: Box<dyn Any>) {
+        AnchorInner::dirty_payload(self, payload)
+    }
     fn poll_updated<'eng, 'gg>(&mut self, ctx: &mut EngineContextMut<'eng, 'gg>) -> Poll {
         AnchorInner::poll_updated(self, ctx)
     }
@@ -482,25 +2424,72 @@ impl<I: AnchorInner<Engine> + 'static> GenericAnchor for I {
     {
         AnchorInner::output(self, ctx)
     }
+    fn output_ptr<'slf>(&'slf self, ctx: &mut EngineContext<'slf>) -> *const () {
+        AnchorInner::output(self, ctx) as *const I::Output as *const ()
+    }
+    fn output_rc(&self) -> Option<&dyn Any> {
+        AnchorInner::output_rc(self)
+    }
+    fn output_type(&self) -> TypeId {
+        TypeId::of::<I::Output>()
+    }
+    fn evict_output(&mut self) -> bool {
+        AnchorInner::evict_output(self)
+    }
+    fn store_inline_output<'slf>(&'slf self, ctx: &mut EngineContext<'slf>, slot: &InlineOutput) {
+        if InlineOutput::fits::<I::Output>() {
+            slot.store(AnchorInner::output(self, ctx));
+        }
+    }
     fn debug_info(&self) -> AnchorDebugInfo {
         AnchorDebugInfo {
+            name: None,
             location: self.debug_location(),
             type_info: std::any::type_name::<I>(),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-struct AnchorDebugInfo {
-    location: Option<(&'static str, &'static Location<'static>)>,
-    type_info: &'static str,
+impl<O> Anchor<O> {
+    /// Gives this Anchor a name, which is shown alongside its source location in `debug_state`,
+    /// errors, and anywhere else its `AnchorDebugInfo` is displayed. Clones of the Anchor share
+    /// the name. Useful when many Anchors are created at the same location, for instance by a
+    /// macro or helper function.
+    pub fn named(self, name: &'static str) -> Self {
+        self.handle().set_name(name);
+        self
+    }
+
+    /// Sets this Anchor's recalculation priority, which defaults to 0. Anchors are always
+    /// recalculated after everything they depend on, but among Anchors whose inputs are equally
+    /// deep in the graph, higher priorities go first. Useful with `Engine::stabilize_budgeted`,
+    /// so that visible or interactive Anchors are updated before background ones when the budget
+    /// runs out. Clones of the Anchor share the priority.
+    pub fn with_priority(self, priority: i32) -> Self {
+        self.handle().set_priority(priority);
+        self
+    }
+}
+
+/// Where an Anchor was created, and what type implements it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnchorDebugInfo {
+    /// The name given to the Anchor with `Anchor::named`, if any.
+    pub name: Option<&'static str>,
+    /// The name of the Anchor's constructor, and the location it was called from.
+    pub location: Option<(&'static str, &'static Location<'static>)>,
+    /// The type name of the Anchor's `AnchorInner` implementation.
+    pub type_info: &'static str,
 }
 
-impl AnchorDebugInfo {
-    fn _to_string(&self) -> String {
+impl std::fmt::Display for AnchorDebugInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(name) = self.name {
+            write!(f, "{}: ", name)?;
+        }
         match self.location {
-            Some((name, location)) => format!("{} ({})", location, name),
-            None => format!("{}", self.type_info),
+            Some((name, location)) => write!(f, "{} ({})", location, name),
+            None => write!(f, "{}", self.type_info),
         }
     }
 }
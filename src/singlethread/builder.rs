@@ -0,0 +1,163 @@
+use super::Engine;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// Policies for releasing memory held by Anchors that are no longer observed. See
+/// `Engine::set_keep_alive` and `Engine::set_cached_output_limit` for details on each field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GcPolicy {
+    /// How many stabilizations an Anchor stays up-to-date for after it's unobserved.
+    pub keep_alive: u64,
+
+    /// How many unnecessary Anchors keep their cached outputs, or `None` for no limit.
+    pub cached_output_limit: Option<usize>,
+}
+
+/// How changes propagate through an Engine's graph. Set with `EngineBuilder::strategy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    /// Observed Anchors mark everything they read as necessary, and necessary Anchors are
+    /// recalculated as soon as their inputs change. Best when the same Anchors are read
+    /// repeatedly, since unchanged parts of the graph are never revisited.
+    #[default]
+    Push,
+
+    /// No Anchor is ever marked necessary. Changes only mark Anchors as needing
+    /// recalculation, and they're recalculated when something requests them, either a read or
+    /// an observed Anchor that was affected by the change. Avoids the bookkeeping of tracking
+    /// necessary Anchors, which is wasted on batch workloads that read each result once.
+    Pull,
+}
+
+/// Configures and creates an `Engine`. Created with `Engine::builder`.
+///
+/// ```
+/// use anchors::singlethread::{Engine, GcPolicy};
+///
+/// let engine = Engine::builder()
+///     .max_height(1000)
+///     .initial_capacity(10_000)
+///     .gc_policy(GcPolicy {
+///         keep_alive: 2,
+///         ..GcPolicy::default()
+///     })
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct EngineBuilder {
+    pub(super) max_height: usize,
+    pub(super) initial_capacity: usize,
+    pub(super) gc_policy: GcPolicy,
+    pub(super) time_recalculations: bool,
+    pub(super) catch_panics: bool,
+    pub(super) strategy: Strategy,
+    pub(super) equality_cutoffs: HashMap<TypeId, EqualityCutoff>,
+}
+
+/// How outputs of one type are copied and compared, for `EngineBuilder::equality_cutoff`.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct EqualityCutoff {
+    pub(super) copy: fn(&dyn Any) -> Box<dyn Any>,
+    pub(super) eq: fn(&dyn Any, &dyn Any) -> bool,
+}
+
+impl EqualityCutoff {
+    fn of<T: Clone + PartialEq + 'static>() -> Self {
+        Self {
+            copy: |val| Box::new(val.downcast_ref::<T>().unwrap().clone()),
+            eq: |a, b| a.downcast_ref::<T>() == b.downcast_ref::<T>(),
+        }
+    }
+}
+
+impl Default for EngineBuilder {
+    fn default() -> Self {
+        Self {
+            max_height: usize::MAX,
+            initial_capacity: 0,
+            gc_policy: GcPolicy::default(),
+            time_recalculations: false,
+            catch_panics: false,
+            strategy: Strategy::default(),
+            equality_cutoffs: HashMap::new(),
+        }
+    }
+}
+
+impl EngineBuilder {
+    /// Sets the maximum height of the graph. Stabilizing fails with `Error::MaxHeightExceeded` if
+    /// some Anchor would need to be at or above `max_height`, which can help catch graphs that
+    /// grow without bound. Defaults to no limit.
+    pub fn max_height(mut self, max_height: usize) -> Self {
+        self.max_height = max_height;
+        self
+    }
+
+    /// Sets how many Anchors the Engine should expect. Nodes for that many Anchors are allocated
+    /// up front, along with their bookkeeping, so creating them doesn't allocate anything beyond
+    /// each Anchor's own boxed state. Since dropped Anchors' nodes and allocations are reused, a
+    /// graph that stays within this size stops allocating once it has warmed up.
+    pub fn initial_capacity(mut self, nodes: usize) -> Self {
+        self.initial_capacity = nodes;
+        self
+    }
+
+    /// Sets the policies for releasing memory held by unobserved Anchors.
+    pub fn gc_policy(mut self, gc_policy: GcPolicy) -> Self {
+        self.gc_policy = gc_policy;
+        self
+    }
+
+    /// Sets whether the Engine measures how long each Anchor takes to recalculate, for
+    /// `Engine::hotspots`. Recalculations are always counted, but timing them adds overhead to
+    /// every recalculation, so this defaults to false.
+    pub fn time_recalculations(mut self, enabled: bool) -> Self {
+        self.time_recalculations = enabled;
+        self
+    }
+
+    /// Sets whether the Engine catches panics from recalculating Anchors. An Anchor that panics
+    /// is poisoned: reading it, or any Anchor that depends on it, returns `Error::Poisoned` until
+    /// one of its inputs changes and it recalculates successfully. The rest of the graph keeps
+    /// working. Defaults to false, in which case panics propagate out of `stabilize`.
+    pub fn catch_panics(mut self, enabled: bool) -> Self {
+        self.catch_panics = enabled;
+        self
+    }
+
+    /// Sets how changes propagate through the Engine's graph. Defaults to `Strategy::Push`.
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Makes the Engine compare every new output of type `T` against the previous one, and treat
+    /// equal outputs as unchanged, so Anchors depending on them aren't recalculated. This is like
+    /// calling `cutoff` on every Anchor of that type, including Vars set to an equal value, but
+    /// keeps a clone of each one's latest output. Can be called once for each type that's worth
+    /// comparing; types without an equality cutoff aren't affected.
+    ///
+    /// ```
+    /// use anchors::singlethread::*;
+    ///
+    /// let mut engine = Engine::builder().equality_cutoff::<i32>().build();
+    /// let num = Var::new(3);
+    /// let parity = num.watch().then(|n| Anchor::constant(n % 2));
+    /// let label = parity.map(|parity| if *parity == 0 { "even" } else { "odd" });
+    /// assert_eq!(engine.get(&label), "odd");
+    ///
+    /// // `parity` is still 1, so `label` isn't recalculated
+    /// num.set(5);
+    /// assert_eq!(engine.get(&label), "odd");
+    /// ```
+    pub fn equality_cutoff<T: Clone + PartialEq + 'static>(mut self) -> Self {
+        self.equality_cutoffs
+            .insert(TypeId::of::<T>(), EqualityCutoff::of::<T>());
+        self
+    }
+
+    /// Creates the Engine. Like `Engine::new`, new Anchors on this thread are created in it.
+    pub fn build(self) -> Engine {
+        Engine::from_builder(self)
+    }
+}
@@ -0,0 +1,69 @@
+use super::{Engine, Instant};
+use crate::expert::{AnchorHandle, AnchorInner, OutputContext, Poll, UpdateContext};
+use std::panic::Location;
+use std::time::Duration;
+
+/// An Anchor reporting the current time, rounded down to some granularity. The engine marks
+/// every clock dirty at the start of each stabilization; see `Engine::now`.
+pub(super) struct Clock {
+    start: Instant,
+    granularity: Duration,
+    output: Option<Instant>,
+    location: &'static Location<'static>,
+}
+
+impl Clock {
+    #[track_caller]
+    pub(super) fn new(granularity: Duration) -> Self {
+        Self {
+            start: Instant::now(),
+            granularity,
+            output: None,
+            location: Location::caller(),
+        }
+    }
+
+    fn quantized_now(&self) -> Instant {
+        let elapsed = self.start.elapsed();
+        let granularity = self.granularity.as_nanos();
+        if granularity == 0 {
+            return self.start + elapsed;
+        }
+        let ticks = elapsed.as_nanos() / granularity;
+        self.start + Duration::from_nanos((ticks * granularity) as u64)
+    }
+}
+
+impl AnchorInner<Engine> for Clock {
+    type Output = Instant;
+
+    fn dirty(&mut self, _child: &<super::AnchorHandle as AnchorHandle>::Token) {
+        panic!("somehow an input was dirtied on Clock; it never has any inputs to dirty")
+    }
+
+    fn poll_updated<G: UpdateContext<Engine = Engine>>(&mut self, _ctx: &mut G) -> Poll {
+        let now = Some(self.quantized_now());
+        if now == self.output {
+            Poll::Unchanged
+        } else {
+            self.output = now;
+            Poll::Updated
+        }
+    }
+
+    fn output<'slf, 'out, G: OutputContext<'out, Engine = Engine>>(
+        &'slf self,
+        _ctx: &mut G,
+    ) -> &'out Self::Output
+    where
+        'slf: 'out,
+    {
+        self.output
+            .as_ref()
+            .expect("output called on Clock before value was calculated")
+    }
+
+    fn debug_location(&self) -> Option<(&'static str, &'static Location<'static>)> {
+        Some(("clock", self.location))
+    }
+}
@@ -0,0 +1,188 @@
+//! A live view of the graph served over HTTP. Requires the `debug-server` feature.
+
+use super::{graph2, Engine, ObservedState, RecalcState};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+const PAGE: &str = include_str!("debug_server.html");
+
+/// A running graph inspector, created by `Engine::serve_debug`. The server stops, and stops
+/// slowing down stabilization, when this is dropped.
+pub struct DebugServer {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+}
+
+impl DebugServer {
+    /// Returns the address the inspector is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for DebugServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // wake the server thread up so it notices it's been stopped
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+impl Engine {
+    /// Serves a view of this Engine's graph at `addr`: an HTML page at `/`, and the underlying
+    /// JSON at `/graph.json`. The view is updated at the end of every stabilization, and lists
+    /// each node's location, height, observed and recalculation state, last generations, and the
+    /// parents it notifies when it changes.
+    ///
+    /// Requests are handled on a background thread, so stabilization is never blocked on clients.
+    pub fn serve_debug<A: ToSocketAddrs>(&mut self, addr: A) -> std::io::Result<DebugServer> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let latest = Arc::new(Mutex::new(self.debug_json()));
+
+        let graph = std::rc::Rc::downgrade(&self.graph);
+        let hook_latest = latest.clone();
+        self.add_removable_post_stabilize_hook(stopped.clone(), move |report| {
+            if let Some(graph) = graph.upgrade() {
+                let json = graph.with(|graph| graph_json(graph, report.generation));
+                *hook_latest.lock().unwrap() = json;
+            }
+        });
+
+        let thread_stopped = stopped.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let _ = respond(stream, &latest);
+                }
+            }
+        });
+        Ok(DebugServer { addr, stopped })
+    }
+
+    fn debug_json(&self) -> String {
+        self.graph
+            .with(|graph| graph_json(graph, self.generation.get()))
+    }
+}
+
+fn graph_json(graph: graph2::Graph2Guard<'_>, generation: u64) -> String {
+    let live = graph.live_nodes();
+    let ids: HashMap<_, _> = live
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.key(), i))
+        .collect();
+    let nodes: Vec<_> = live
+        .iter()
+        .map(|node| {
+            let observed = match Engine::check_observed_raw(*node) {
+                ObservedState::Observed => "observed",
+                ObservedState::Necessary => "necessary",
+                ObservedState::Unnecessary => "unnecessary",
+            };
+            let state = match graph2::recalc_state(*node) {
+                RecalcState::Needed => "needed",
+                RecalcState::Pending => "pending",
+                RecalcState::Ready => "ready",
+            };
+            json!({
+                "id": ids[&node.key()],
+                "name": node.debug_info.get().to_string(),
+                "height": graph2::height(*node),
+                "observed": observed,
+                "state": state,
+                "last_ready": node.last_ready.get().map(|gen| gen.get()),
+                "last_update": node.last_update.get().map(|gen| gen.get()),
+            })
+        })
+        .collect();
+    let mut edges = vec![];
+    let mut seen = HashSet::new();
+    for node in &live {
+        for parent in node.clean_parents() {
+            if let Some(parent_id) = ids.get(&parent.key()) {
+                let edge = (ids[&node.key()], *parent_id);
+                if seen.insert(edge) {
+                    edges.push(edge);
+                }
+            }
+        }
+    }
+    json!({
+        "generation": generation,
+        "nodes": nodes,
+        "edges": edges,
+    })
+    .to_string()
+}
+
+fn respond(mut stream: TcpStream, latest: &Mutex<String>) -> std::io::Result<()> {
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let (status, content_type, body) = match path {
+        "/" => ("200 OK", "text/html", PAGE.to_string()),
+        "/graph.json" => ("200 OK", "application/json", latest.lock().unwrap().clone()),
+        _ => ("404 Not Found", "text/plain", "not found".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::singlethread::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    fn fetch(addr: std::net::SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_graph_after_stabilize() {
+        let mut engine = Engine::new();
+        let server = engine.serve_debug("127.0.0.1:0").unwrap();
+        let var = Var::new(1);
+        let doubled = var.watch().map(|v| *v * 2);
+        engine.mark_observed(&doubled);
+        engine.stabilize();
+
+        let response = fetch(server.local_addr(), "/graph.json");
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        let graph: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(graph["generation"], engine.generation());
+        let nodes = graph["nodes"].as_array().unwrap();
+        assert!(nodes
+            .iter()
+            .any(|node| node["observed"] == "observed" && node["height"] == 1));
+        assert_eq!(graph["edges"].as_array().unwrap().len(), 1);
+
+        assert!(fetch(server.local_addr(), "/").contains("<html"));
+        assert!(fetch(server.local_addr(), "/missing").starts_with("HTTP/1.1 404"));
+
+        // dropping the server removes its hook, so `get` can skip stabilizing again
+        assert!(!engine.stabilize_is_noop());
+        drop(server);
+        assert!(engine.stabilize_is_noop());
+    }
+}
@@ -0,0 +1,187 @@
+//! Helpers for driving Anchors from an egui application. Requires the `egui` feature.
+//!
+//! Call `EguiState::begin_frame` at the start of each frame, read Anchors with `EguiState::get`
+//! while building widgets, and call `EguiState::end_frame` once the frame is done. Anchors read
+//! during a frame stay observed until a frame passes without them being read, so Anchors backing
+//! widgets that are no longer shown stop being recalculated.
+//!
+//! The widget functions, like `text_edit` and `slider`, show a widget editing a `Var`'s value and
+//! set the `Var` when the user changes it. As usual for immediate mode UIs, Anchors depending on
+//! the `Var` pick up the change on the next frame.
+
+use super::{Anchor, AnchorToken, Engine, StabilizeReport, Var};
+use egui::emath::Numeric;
+use egui::{Context, Response, Slider, Ui, WidgetText};
+use std::collections::hash_map::{Entry, HashMap};
+use std::ops::RangeInclusive;
+
+/// Wraps an Anchors engine, stabilizing it once per frame and tracking which Anchors the UI
+/// reads.
+pub struct EguiState {
+    engine: Engine,
+    observed: HashMap<AnchorToken, Observation>,
+    frame: u64,
+}
+
+struct Observation {
+    /// the last frame this Anchor was read in
+    frame: u64,
+    unobserve: Box<dyn FnMut(&mut Engine)>,
+}
+
+impl EguiState {
+    pub fn new(engine: Engine) -> Self {
+        Self {
+            engine,
+            observed: HashMap::new(),
+            frame: 0,
+        }
+    }
+
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    pub fn engine_mut(&mut self) -> &mut Engine {
+        &mut self.engine
+    }
+
+    /// Marks Anchors that weren't read during the previous frame as unobserved, then stabilizes
+    /// if `Engine::needs_stabilize` says there's anything to do. Returns the stabilization's
+    /// report, if there was one.
+    pub fn begin_frame(&mut self) -> Option<StabilizeReport> {
+        let frame = self.frame;
+        let engine = &mut self.engine;
+        self.observed.retain(|_, observation| {
+            if observation.frame < frame {
+                (observation.unobserve)(engine);
+                false
+            } else {
+                true
+            }
+        });
+        self.frame += 1;
+        if self.engine.needs_stabilize() {
+            Some(self.engine.stabilize())
+        } else {
+            None
+        }
+    }
+
+    /// Requests another frame from `ctx` if some `Var` was set or Anchor marked dirty during this
+    /// one, so the change shows up without waiting for more input.
+    pub fn end_frame(&self, ctx: &Context) {
+        if self.engine.needs_stabilize() {
+            ctx.request_repaint();
+        }
+    }
+
+    /// Returns `anchor`'s value as of the start of this frame, marking it observed until a frame
+    /// passes without it being read. Anchors read for the first time are calculated right away.
+    ///
+    /// Anchors read through this are unobserved once they're no longer read, even if they were
+    /// also marked observed some other way.
+    pub fn get<T: Clone + 'static>(&mut self, anchor: &Anchor<T>) -> T {
+        let frame = self.frame;
+        match self.observed.entry(anchor.token()) {
+            Entry::Occupied(mut entry) => entry.get_mut().frame = frame,
+            Entry::Vacant(entry) => {
+                self.engine.mark_observed(anchor);
+                let kept_anchor = anchor.clone();
+                entry.insert(Observation {
+                    frame,
+                    unobserve: Box::new(move |engine| engine.mark_unobserved(&kept_anchor)),
+                });
+                // it may have been calculated before, but not kept up-to-date
+                return self.engine.get(anchor);
+            }
+        }
+        match self.engine.peek(anchor) {
+            Some(val) => val,
+            None => self.engine.get(anchor),
+        }
+    }
+}
+
+/// Shows a single-line text field editing `var`, setting it when the text changes.
+pub fn text_edit(ui: &mut Ui, var: &Var<String>) -> Response {
+    let mut text = String::clone(&var.get());
+    let response = ui.text_edit_singleline(&mut text);
+    if response.changed() {
+        var.set(text);
+    }
+    response
+}
+
+/// Shows a slider editing `var` within `range`, setting it when the slider moves.
+pub fn slider<T: Numeric>(ui: &mut Ui, var: &Var<T>, range: RangeInclusive<T>) -> Response {
+    let mut val = *var.get();
+    let response = ui.add(Slider::new(&mut val, range));
+    if response.changed() {
+        var.set(val);
+    }
+    response
+}
+
+/// Shows a checkbox editing `var`, setting it when it's clicked.
+pub fn checkbox(ui: &mut Ui, var: &Var<bool>, text: impl Into<WidgetText>) -> Response {
+    let mut checked = *var.get();
+    let response = ui.checkbox(&mut checked, text);
+    if response.changed() {
+        var.set(checked);
+    }
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::singlethread::ObservedState;
+
+    #[test]
+    fn get_observes_until_a_frame_skips_it() {
+        let mut state = EguiState::new(Engine::new());
+        let var = Var::new(1);
+        let doubled = var.watch().map(|v| *v * 2);
+
+        state.begin_frame();
+        assert_eq!(state.get(&doubled), 2);
+        assert_eq!(
+            state.engine().check_observed(&doubled),
+            ObservedState::Observed
+        );
+
+        var.set(2);
+        assert!(state.begin_frame().is_some());
+        assert_eq!(state.get(&doubled), 4);
+
+        // nothing to stabilize, so no report
+        assert!(state.begin_frame().is_none());
+        assert_eq!(
+            state.engine().check_observed(&doubled),
+            ObservedState::Observed
+        );
+
+        // the previous frame didn't read it
+        state.begin_frame();
+        assert_eq!(
+            state.engine().check_observed(&doubled),
+            ObservedState::Unnecessary
+        );
+    }
+
+    #[test]
+    fn end_frame_requests_repaint_when_dirty() {
+        let ctx = Context::default();
+        let mut state = EguiState::new(Engine::new());
+        let var = Var::new(1);
+        state.begin_frame();
+        assert_eq!(state.get(&var.watch()), 1);
+        state.end_frame(&ctx);
+        assert!(!ctx.has_requested_repaint());
+
+        var.set(2);
+        state.end_frame(&ctx);
+        assert!(ctx.has_requested_repaint());
+    }
+}
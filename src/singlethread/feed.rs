@@ -0,0 +1,391 @@
+//! Anchors following a live feed of JSON messages, such as a websocket. Requires the `feed`
+//! feature, plus the `websocket` feature for `websocket`.
+//!
+//! Like `timer`, these must be created from within a tokio runtime, since the connection is
+//! driven by a task spawned onto the current runtime. The runtime must keep running for messages
+//! to arrive.
+
+use super::{Anchor, DirtyHandle, Engine, SendDirtyHandle};
+use crate::expert::{AnchorInner, OutputContext, Poll, UpdateContext};
+use futures_core::Stream;
+use serde::de::DeserializeOwned;
+use std::fmt::Display;
+use std::future::Future;
+use std::panic::Location;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+/// How long to wait before reconnecting after a feed's connection fails or closes. The delay
+/// starts at `initial` and doubles after each failed attempt, up to `max`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    /// Gives up after this many reconnection attempts in a row, or never if `None`.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+}
+
+impl Backoff {
+    /// Returns the delay before reconnection attempt `attempt`, counting from 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(1).min(31);
+        self.initial.saturating_mul(1 << doublings).min(self.max)
+    }
+}
+
+/// The state of a feed's connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeedStatus {
+    /// Connecting for the first time.
+    Connecting,
+    Connected,
+    /// The connection failed or closed with `error`, and reconnection attempt `attempt` is
+    /// waiting for its backoff delay or in progress.
+    Reconnecting {
+        attempt: u32,
+        error: String,
+    },
+    /// `Backoff::max_attempts` reconnection attempts failed in a row, so the feed gave up.
+    Failed {
+        error: String,
+    },
+}
+
+/// The output of a feed Anchor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedState<T> {
+    /// The most recent message that was successfully deserialized.
+    pub latest: Option<T>,
+    /// How many messages have been successfully deserialized.
+    pub received: u64,
+    /// The error from deserializing the most recent message, if it failed.
+    pub decode_error: Option<String>,
+    pub status: FeedStatus,
+}
+
+/// Creates an Anchor following a feed of JSON messages. `connect` is called to open a
+/// connection, returning a stream of raw messages; each message is deserialized into a `T`, and
+/// the Anchor is marked dirty so the next stabilization picks it up. When connecting fails, or
+/// the stream returns an error or ends, `connect` is called again after waiting according to
+/// `backoff`. The connection is closed when the Anchor is dropped.
+///
+/// Panics if called outside of a tokio runtime.
+#[track_caller]
+pub fn live_feed<T, C, Fut, S, B, Err>(connect: C, backoff: Backoff) -> Anchor<FeedState<T>>
+where
+    T: DeserializeOwned + Clone + Send + 'static,
+    C: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<S, Err>> + Send + 'static,
+    S: Stream<Item = Result<B, Err>> + Send + 'static,
+    B: AsRef<[u8]> + Send + 'static,
+    Err: Display + Send + 'static,
+{
+    let shared = Arc::new(Mutex::new(Shared {
+        state: FeedState {
+            latest: None,
+            received: 0,
+            decode_error: None,
+            status: FeedStatus::Connecting,
+        },
+        changed: false,
+    }));
+    <Engine as crate::expert::Engine>::mount(Feed {
+        runtime: Handle::current(),
+        start: Some(Box::new({
+            let shared = shared.clone();
+            move |runtime: &Handle, dirty_handle| {
+                runtime.spawn(run(connect, backoff, shared, dirty_handle))
+            }
+        })),
+        shared,
+        task: None,
+        output: None,
+        location: Location::caller(),
+    })
+}
+
+/// Creates an Anchor following a websocket's JSON messages, reconnecting according to `backoff`
+/// whenever the connection fails or closes. Text and binary messages are both deserialized;
+/// other messages are skipped. See `live_feed`.
+///
+/// Panics if called outside of a tokio runtime.
+#[cfg(feature = "websocket")]
+#[track_caller]
+pub fn websocket<T: DeserializeOwned + Clone + Send + 'static>(
+    url: impl Into<String>,
+    backoff: Backoff,
+) -> Anchor<FeedState<T>> {
+    let url = url.into();
+    live_feed(
+        move || {
+            let url = url.clone();
+            async move {
+                let (stream, _) = tokio_tungstenite::connect_async(url).await?;
+                Ok(websocket::Messages(stream))
+            }
+        },
+        backoff,
+    )
+}
+
+#[cfg(feature = "websocket")]
+mod websocket {
+    use futures_core::Stream;
+    use std::pin::Pin;
+    use std::task::{self, Context};
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::tungstenite::{Error, Message};
+    use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+    /// A websocket's data messages.
+    pub(super) struct Messages(pub(super) WebSocketStream<MaybeTlsStream<TcpStream>>);
+
+    impl Stream for Messages {
+        type Item = Result<Vec<u8>, Error>;
+
+        fn poll_next(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> task::Poll<Option<Self::Item>> {
+            loop {
+                let message = match Pin::new(&mut self.0).poll_next(cx) {
+                    task::Poll::Ready(message) => message,
+                    task::Poll::Pending => return task::Poll::Pending,
+                };
+                return task::Poll::Ready(match message {
+                    Some(Ok(Message::Text(text))) => Some(Ok(text.into_bytes())),
+                    Some(Ok(Message::Binary(bytes))) => Some(Ok(bytes)),
+                    Some(Ok(Message::Close(_))) | None => None,
+                    Some(Ok(_)) => continue,
+                    Some(Err(err)) => Some(Err(err)),
+                });
+            }
+        }
+    }
+}
+
+struct Shared<T> {
+    state: FeedState<T>,
+    /// set by the connection task whenever `state` changes
+    changed: bool,
+}
+
+fn update<T>(
+    shared: &Mutex<Shared<T>>,
+    dirty_handle: &SendDirtyHandle,
+    f: impl FnOnce(&mut FeedState<T>),
+) {
+    use crate::expert::DirtyHandle as _;
+    let mut shared = shared.lock().unwrap();
+    f(&mut shared.state);
+    shared.changed = true;
+    dirty_handle.mark_dirty();
+}
+
+async fn run<T, C, Fut, S, B, Err>(
+    mut connect: C,
+    backoff: Backoff,
+    shared: Arc<Mutex<Shared<T>>>,
+    dirty_handle: SendDirtyHandle,
+) where
+    T: DeserializeOwned,
+    C: FnMut() -> Fut,
+    Fut: Future<Output = Result<S, Err>>,
+    S: Stream<Item = Result<B, Err>>,
+    B: AsRef<[u8]>,
+    Err: Display,
+{
+    let mut attempt = 0;
+    loop {
+        let error = match connect().await {
+            Ok(stream) => {
+                attempt = 0;
+                update(&shared, &dirty_handle, |state| {
+                    state.status = FeedStatus::Connected
+                });
+                let mut stream = Box::pin(stream);
+                loop {
+                    match std::future::poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+                        Some(Ok(message)) => {
+                            update(
+                                &shared,
+                                &dirty_handle,
+                                |state| match serde_json::from_slice(message.as_ref()) {
+                                    Ok(val) => {
+                                        state.latest = Some(val);
+                                        state.received += 1;
+                                        state.decode_error = None;
+                                    }
+                                    Err(err) => state.decode_error = Some(err.to_string()),
+                                },
+                            )
+                        }
+                        Some(Err(err)) => break err.to_string(),
+                        None => break "connection closed".to_string(),
+                    }
+                }
+            }
+            Err(err) => err.to_string(),
+        };
+        attempt += 1;
+        if backoff.max_attempts.is_some_and(|max| attempt > max) {
+            update(&shared, &dirty_handle, |state| {
+                state.status = FeedStatus::Failed { error }
+            });
+            return;
+        }
+        update(&shared, &dirty_handle, |state| {
+            state.status = FeedStatus::Reconnecting { attempt, error }
+        });
+        tokio::time::sleep(backoff.delay(attempt)).await;
+    }
+}
+
+type StartFn = Box<dyn FnOnce(&Handle, SendDirtyHandle) -> JoinHandle<()>>;
+
+struct Feed<T> {
+    runtime: Handle,
+    /// spawns the connection task; taken on the first poll
+    start: Option<StartFn>,
+    shared: Arc<Mutex<Shared<T>>>,
+    task: Option<JoinHandle<()>>,
+    output: Option<FeedState<T>>,
+    location: &'static Location<'static>,
+}
+
+impl<T> Drop for Feed<T> {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+impl<T: Clone + 'static> AnchorInner<Engine> for Feed<T> {
+    type Output = FeedState<T>;
+
+    fn dirty(&mut self, _child: &<super::AnchorHandle as crate::expert::AnchorHandle>::Token) {
+        panic!("somehow an input was dirtied on Feed; it never has any inputs to dirty")
+    }
+
+    fn poll_updated<G: UpdateContext<Engine = Engine>>(&mut self, ctx: &mut G) -> Poll {
+        if let Some(start) = self.start.take() {
+            let dirty_handle: DirtyHandle = ctx.dirty_handle();
+            self.task = Some(start(&self.runtime, dirty_handle.to_send()));
+        }
+        let mut shared = self.shared.lock().unwrap();
+        if self.output.is_some() && !shared.changed {
+            return Poll::Unchanged;
+        }
+        shared.changed = false;
+        self.output = Some(shared.state.clone());
+        Poll::Updated
+    }
+
+    fn output<'slf, 'out, G: OutputContext<'out, Engine = Engine>>(
+        &'slf self,
+        _ctx: &mut G,
+    ) -> &'out Self::Output
+    where
+        'slf: 'out,
+    {
+        self.output
+            .as_ref()
+            .expect("output called on Feed before value was calculated")
+    }
+
+    fn debug_location(&self) -> Option<(&'static str, &'static Location<'static>)> {
+        Some(("feed", self.location))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::pin::Pin;
+    use std::task::{self, Context};
+
+    /// A stream returning some fixed messages, then ending.
+    struct Messages(VecDeque<Result<&'static str, String>>);
+
+    impl Stream for Messages {
+        type Item = Result<&'static str, String>;
+
+        fn poll_next(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> task::Poll<Option<Self::Item>> {
+            task::Poll::Ready(self.0.pop_front())
+        }
+    }
+
+    #[test]
+    fn feed_reconnects_with_backoff() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let _guard = runtime.enter();
+        let mut engine = Engine::new();
+        let mut connects = 0;
+        let feed = live_feed::<u32, _, _, _, _, _>(
+            move || {
+                connects += 1;
+                // only the second connection succeeds
+                let res = if connects != 2 {
+                    Err("refused".to_string())
+                } else {
+                    Ok(Messages(
+                        vec![Ok("1"), Ok("oops"), Ok("2")].into_iter().collect(),
+                    ))
+                };
+                async move { res }
+            },
+            Backoff {
+                initial: Duration::from_millis(1),
+                max: Duration::from_millis(1),
+                max_attempts: Some(1),
+            },
+        );
+        engine.mark_observed(&feed);
+        assert_eq!(engine.get(&feed).status, FeedStatus::Connecting);
+        runtime.block_on(tokio::time::sleep(Duration::from_millis(30)));
+        assert_eq!(
+            engine.get(&feed),
+            FeedState {
+                latest: Some(2),
+                received: 2,
+                decode_error: None,
+                status: FeedStatus::Failed {
+                    error: "refused".to_string()
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let backoff = Backoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(1),
+            max_attempts: None,
+        };
+        assert_eq!(backoff.delay(1), Duration::from_millis(100));
+        assert_eq!(backoff.delay(3), Duration::from_millis(400));
+        assert_eq!(backoff.delay(5), Duration::from_secs(1));
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(1));
+    }
+}
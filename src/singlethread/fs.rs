@@ -0,0 +1,153 @@
+//! Anchors backed by the filesystem. Requires the `notify` feature.
+
+use super::{Anchor, DirtyHandle, Engine};
+use crate::expert::{AnchorHandle, AnchorInner, OutputContext, Poll, UpdateContext};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::io;
+use std::panic::Location;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+impl Anchor<Result<String, io::Error>> {
+    /// Creates an Anchor containing the contents of the file at `path`. The file is watched for
+    /// changes, and re-read whenever it is modified, created, or removed.
+    ///
+    /// The parent directory is watched rather than the file itself, so the file does not need to
+    /// exist yet, and editors that save by replacing the file are handled correctly.
+    #[track_caller]
+    pub fn watch_file<P: Into<PathBuf>>(path: P) -> Self {
+        <Engine as crate::expert::Engine>::mount(FileWatch {
+            path: path.into(),
+            watcher: None,
+            changed: Arc::new(AtomicBool::new(true)),
+            output: None,
+            location: Location::caller(),
+        })
+    }
+}
+
+struct FileWatch {
+    path: PathBuf,
+    watcher: Option<RecommendedWatcher>,
+    /// set by the watcher thread whenever an event touching `path` is received
+    changed: Arc<AtomicBool>,
+    output: Option<Result<String, io::Error>>,
+    location: &'static Location<'static>,
+}
+
+impl FileWatch {
+    fn start_watching(&mut self, dirty_handle: DirtyHandle) -> Result<(), io::Error> {
+        use crate::expert::DirtyHandle as _;
+        let dirty_handle = dirty_handle.to_send();
+        let changed = self.changed.clone();
+        let path = self.path.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let touches_path = match event {
+                    Ok(event) => event
+                        .paths
+                        .iter()
+                        .any(|p| p.file_name() == path.file_name()),
+                    // we don't know what happened, so to be safe reread the file
+                    Err(_) => true,
+                };
+                if touches_path {
+                    changed.store(true, Ordering::SeqCst);
+                    dirty_handle.mark_dirty();
+                }
+            })
+            .map_err(to_io_error)?;
+        let dir = match self.path.parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(to_io_error)?;
+        self.watcher = Some(watcher);
+        Ok(())
+    }
+}
+
+fn to_io_error(err: notify::Error) -> io::Error {
+    match err.kind {
+        notify::ErrorKind::Io(err) => err,
+        _ => io::Error::other(err),
+    }
+}
+
+impl AnchorInner<Engine> for FileWatch {
+    type Output = Result<String, io::Error>;
+
+    fn dirty(&mut self, _child: &<super::AnchorHandle as AnchorHandle>::Token) {
+        panic!("somehow an input was dirtied on FileWatch; it never has any inputs to dirty")
+    }
+
+    fn poll_updated<G: UpdateContext<Engine = Engine>>(&mut self, ctx: &mut G) -> Poll {
+        if self.watcher.is_none() {
+            if let Err(err) = self.start_watching(ctx.dirty_handle()) {
+                self.output = Some(Err(err));
+                return Poll::Updated;
+            }
+        }
+        if !self.changed.swap(false, Ordering::SeqCst) {
+            return Poll::Unchanged;
+        }
+        let new_val = std::fs::read_to_string(&self.path);
+        match (&self.output, &new_val) {
+            (Some(Ok(old)), Ok(new)) if old == new => Poll::Unchanged,
+            _ => {
+                self.output = Some(new_val);
+                Poll::Updated
+            }
+        }
+    }
+
+    fn output<'slf, 'out, G: OutputContext<'out, Engine = Engine>>(
+        &'slf self,
+        _ctx: &mut G,
+    ) -> &'out Self::Output
+    where
+        'slf: 'out,
+    {
+        self.output
+            .as_ref()
+            .expect("output called on FileWatch before value was calculated")
+    }
+
+    fn debug_location(&self) -> Option<(&'static str, &'static Location<'static>)> {
+        Some(("watch_file", self.location))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn watch_file_rereads() {
+        let dir = std::env::temp_dir().join(format!("anchors-watch-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.txt");
+        std::fs::write(&path, "one").unwrap();
+
+        let mut engine = Engine::new();
+        let contents = Anchor::watch_file(&path).map(|res| res.as_ref().ok().cloned());
+        engine.mark_observed(&contents);
+        assert_eq!(engine.get(&contents), Some("one".to_string()));
+
+        std::fs::write(&path, "two").unwrap();
+        let mut found = None;
+        for _ in 0..100 {
+            found = engine.get(&contents);
+            if found.as_deref() == Some("two") {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        assert_eq!(found, Some("two".to_string()));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -10,4 +10,7 @@ impl Generation {
         let gen: u64 = u64::from(self.0) + 1;
         self.0 = NonZeroU64::new(gen).unwrap();
     }
+    pub fn get(self) -> u64 {
+        self.0.get()
+    }
 }
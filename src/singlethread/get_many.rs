@@ -0,0 +1,127 @@
+use super::{Anchor, Engine, NodeKey};
+
+/// A tuple of Anchor references that can be retrieved together with `Engine::get_many`.
+pub trait AnchorTuple {
+    type Output;
+
+    #[doc(hidden)]
+    fn tokens(&self) -> Vec<NodeKey>;
+
+    #[doc(hidden)]
+    fn outputs(&self, engine: &Engine) -> Self::Output;
+}
+
+macro_rules! impl_anchor_tuple {
+    ($([$output_type:ident, $num:tt])+) => {
+        impl<'a, $($output_type,)+> AnchorTuple for ($(&'a Anchor<$output_type>,)+)
+        where
+            $(
+                $output_type: Clone + 'static,
+            )+
+        {
+            type Output = ($($output_type,)+);
+
+            fn tokens(&self) -> Vec<NodeKey> {
+                vec![$(self.$num.token(),)+]
+            }
+
+            fn outputs(&self, engine: &Engine) -> Self::Output {
+                ($(
+                    engine
+                        .peek(self.$num)
+                        .expect("get_many anchor was not calculated"),
+                )+)
+            }
+        }
+    }
+}
+
+impl_anchor_tuple! {
+    [O0, 0]
+}
+
+impl_anchor_tuple! {
+    [O0, 0]
+    [O1, 1]
+}
+
+impl_anchor_tuple! {
+    [O0, 0]
+    [O1, 1]
+    [O2, 2]
+}
+
+impl_anchor_tuple! {
+    [O0, 0]
+    [O1, 1]
+    [O2, 2]
+    [O3, 3]
+}
+
+impl_anchor_tuple! {
+    [O0, 0]
+    [O1, 1]
+    [O2, 2]
+    [O3, 3]
+    [O4, 4]
+}
+
+impl_anchor_tuple! {
+    [O0, 0]
+    [O1, 1]
+    [O2, 2]
+    [O3, 3]
+    [O4, 4]
+    [O5, 5]
+}
+
+impl_anchor_tuple! {
+    [O0, 0]
+    [O1, 1]
+    [O2, 2]
+    [O3, 3]
+    [O4, 4]
+    [O5, 5]
+    [O6, 6]
+}
+
+impl_anchor_tuple! {
+    [O0, 0]
+    [O1, 1]
+    [O2, 2]
+    [O3, 3]
+    [O4, 4]
+    [O5, 5]
+    [O6, 6]
+    [O7, 7]
+}
+
+impl_anchor_tuple! {
+    [O0, 0]
+    [O1, 1]
+    [O2, 2]
+    [O3, 3]
+    [O4, 4]
+    [O5, 5]
+    [O6, 6]
+    [O7, 7]
+    [O8, 8]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::singlethread::{Engine, Var};
+
+    #[test]
+    fn get_many_same_generation() {
+        let mut engine = Engine::new();
+        let var = Var::new(1);
+        let a = var.watch().map(|v| *v + 1);
+        let b = var.watch().map(|v| *v * 10);
+        let c = a.map(|a| a.to_string());
+        assert_eq!(engine.get_many((&a, &b, &c)), (2, 10, "2".to_string()));
+        var.set(2);
+        assert_eq!(engine.get_many((&c,)), ("3".to_string(),));
+        assert_eq!(engine.get_many((&a, &b)), (3, 20));
+    }
+}
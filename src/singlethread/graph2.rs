@@ -1,8 +1,17 @@
-use super::{AnchorDebugInfo, Generation, GenericAnchor};
+use super::{AnchorDebugInfo, AnchorId, EngineEvent, Generation, GenericAnchor};
+use crate::Error;
+use std::alloc::Layout;
+use std::any::{Any, TypeId};
 use std::cell::{Cell, RefCell, RefMut};
+use std::collections::HashMap;
+use std::mem::MaybeUninit;
+use std::ptr::NonNull;
 use std::rc::Rc;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
 
 use arena_graph::raw as ag;
+use smallvec::SmallVec;
 
 use std::iter::Iterator;
 use std::marker::PhantomData;
@@ -12,10 +21,20 @@ pub struct NodeGuard<'gg>(ag::NodeGuard<'gg, Node>);
 
 type NodePtr = ag::NodePtr<Node>;
 
+/// most nodes have only one or two parents and children, so edge lists are stored inline until
+/// they grow past that, rather than each needing its own allocation
+type NodePtrVec = SmallVec<[NodePtr; 2]>;
+
+/// Whether a node's output is up-to-date.
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum RecalcState {
+    /// Some input may have changed, and the node will be recalculated next time it's requested.
     Needed,
+
+    /// The node is waiting in the recalculation queue.
     Pending,
+
+    /// The node's output is up-to-date.
     Ready,
 }
 
@@ -27,26 +46,104 @@ impl Default for RecalcState {
 
 thread_local! {
     pub static NEXT_TOKEN: Cell<u32> = Cell::new(0);
+
+    /// tokens of every graph on this thread that hasn't been dropped yet
+    static LIVE_GRAPHS: RefCell<Vec<u32>> = const { RefCell::new(Vec::new()) };
+
+    /// every node allocated by a live graph on this thread, and whether it holds an Anchor rather
+    /// than waiting on the free list, so the `checked` feature can validate pointers before
+    /// dereferencing them
+    #[cfg(feature = "checked")]
+    static CHECKED_NODES: RefCell<HashMap<NodePtr, bool>> = RefCell::new(Default::default());
+}
+
+/// Dereferences a pointer to a node holding an Anchor. With the `checked` feature enabled, panics
+/// instead of reading memory that isn't a node owned by a live graph, or a node that has been
+/// freed since the pointer was taken.
+///
+/// # Safety
+/// `ptr` must point to a node in a graph that hasn't been dropped, and the returned guard must
+/// not outlive that graph.
+#[inline]
+unsafe fn deref_ptr<'a>(ptr: NodePtr) -> ag::NodeGuard<'a, Node> {
+    #[cfg(feature = "checked")]
+    check_node(ptr, true);
+    ptr.lookup_unchecked()
+}
+
+/// Like `deref_ptr`, but for pointers that may refer to freed nodes, such as those on the free
+/// list, or keys whose generation is compared afterwards.
+///
+/// # Safety
+/// Same as `deref_ptr`.
+#[inline]
+unsafe fn deref_slot<'a>(ptr: NodePtr) -> ag::NodeGuard<'a, Node> {
+    #[cfg(feature = "checked")]
+    check_node(ptr, false);
+    ptr.lookup_unchecked()
 }
 
+#[cfg(feature = "checked")]
+fn check_node(ptr: NodePtr, must_be_live: bool) {
+    CHECKED_NODES.with(|nodes| match nodes.borrow().get(&ptr) {
+        None => panic!(
+            "anchors: dereferenced {:?}, which isn't a node of any live graph",
+            ptr
+        ),
+        Some(false) if must_be_live => {
+            panic!("anchors: dereferenced {:?}, which has been freed", ptr)
+        }
+        Some(_) => {}
+    });
+}
+
+/// records whether an allocated node holds an Anchor, for `deref_ptr` under the `checked` feature
+#[inline]
+fn register_node(_ptr: NodePtr, _live: bool) {
+    #[cfg(feature = "checked")]
+    CHECKED_NODES.with(|nodes| nodes.borrow_mut().insert(_ptr, _live));
+}
+
+/// number of heights the recalculation queue starts with
+const INITIAL_QUEUE_HEIGHTS: usize = 64;
+
 pub struct Graph2 {
     nodes: ag::Graph<Node>,
     graph_token: u32,
 
     still_alive: Rc<Cell<bool>>,
 
-    /// height -> first node in that height's queue
+    /// height -> first node in that height's queue. Grows as needed, up to `max_height`
     recalc_queues: RefCell<Vec<Option<NodePtr>>>,
+    /// height -> last node in that height's queue, so nodes of the same height are recalculated
+    /// in the order they were queued. Always the same length as `recalc_queues`
+    recalc_queue_tails: RefCell<Vec<Option<NodePtr>>>,
+    max_height: usize,
     recalc_min_height: Cell<usize>,
     recalc_max_height: Cell<usize>,
 
+    /// Some() while push-based recalculations are deferred; see `begin_deferring`
+    deferred_recalc: RefCell<Option<Vec<NodeKey>>>,
+
     /// pointer to head of linked list of free nodes
     free_head: Box<Cell<Option<NodePtr>>>,
+
+    /// every node ever allocated, including freed ones, since the arena can't be iterated
+    all_nodes: RefCell<Vec<NodePtr>>,
+
+    /// receivers of events, registered with `Engine::event_stream`
+    pub(super) event_senders: RefCell<Vec<Sender<EngineEvent>>>,
+
+    /// allocations that held freed nodes' `AnchorInner`s, by layout, so new nodes can reuse them
+    /// instead of allocating. never holds more allocations than there are nodes
+    box_pool: RefCell<HashMap<Layout, Vec<NonNull<u8>>>>,
+    pooled_boxes: Cell<usize>,
 }
 
 #[derive(Clone, Copy)]
 pub struct Graph2Guard<'gg> {
-    nodes: ag::GraphGuard<'gg, Node>,
+    /// only held to brand `'gg`; lookups go through `deref_ptr`
+    _nodes: ag::GraphGuard<'gg, Node>,
     graph: &'gg Graph2,
 }
 
@@ -59,8 +156,46 @@ pub struct Node {
     /// number of nodes that list this node as a necessary child
     pub necessary_count: Cell<usize>,
 
+    /// Some(generation) if this node was unobserved recently, and should stay necessary until
+    /// that generation
+    pub keep_alive_until: Cell<Option<u64>>,
+
+    /// true if this node is in the engine's list of cached outputs that may be evicted
+    pub output_cached: Cell<bool>,
+
+    /// number of times this node has been polled, and total time spent polling it if the engine
+    /// times recalculations
+    pub recalculations: Cell<u64>,
+    pub recalc_time: Cell<Duration>,
+
+    /// nodes with higher priorities are recalculated before others of the same height
+    pub priority: Cell<i32>,
+
+    /// a copy of this node's output, if it's small enough; see `Engine::get_copy`
+    pub inline_output: InlineOutput,
+
+    /// the type of this node's output, checked when it's read by `get`
+    pub(super) output_type: Cell<Option<TypeId>>,
+
+    /// a copy of the output this node last updated with, if its type has an equality cutoff; see
+    /// `EngineBuilder::equality_cutoff`
+    pub(super) cutoff_output: RefCell<Option<Box<dyn Any>>>,
+
+    /// Some() if this node panicked while recalculating, or read from a node that did
+    pub(super) poison: RefCell<Option<Error>>,
+
+    /// inputs that have marked this node dirty since it last finished polling; see
+    /// `UpdateContext::dirtied_children`
+    pub(super) dirtied_children: RefCell<Vec<NodeKey>>,
+
     pub token: u32,
 
+    /// this node's position in `Graph2::all_nodes`, which identifies it in `AnchorId`s
+    pub(super) index: u32,
+
+    /// incremented each time this node is freed, so keys to its previous occupants stop matching
+    pub(super) generation: Cell<u32>,
+
     pub(super) debug_info: Cell<AnchorDebugInfo>,
 
     /// tracks the generation when this Node last polled as Updated or Unchanged
@@ -74,19 +209,98 @@ pub struct Node {
     pub ptrs: NodePtrs,
 }
 
+/// A bitwise copy of a node's output, stored in the node itself so small outputs can be read
+/// without borrowing the node's `AnchorInner` and downcasting its output.
+pub struct InlineOutput {
+    /// whether the node's output has been read with `Engine::get_copy`. copies are only kept for
+    /// nodes that have been, so other nodes don't pay for them on every recalculation
+    wanted: Cell<bool>,
+    /// the type of the copied output, or None if no output has been copied
+    type_id: Cell<Option<TypeId>>,
+    bytes: Cell<MaybeUninit<[u64; 2]>>,
+}
+
+impl InlineOutput {
+    pub fn new() -> Self {
+        Self {
+            wanted: Cell::new(false),
+            type_id: Cell::new(None),
+            bytes: Cell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// whether values of type `O` can be copied inline. types that need dropping can't be
+    /// `Copy`, so they're never read back out, and aren't worth storing. always false under Miri,
+    /// so it checks the ordinary borrow-and-clone path instead of the bytewise copy
+    pub fn fits<O>() -> bool {
+        !cfg!(miri)
+            && std::mem::size_of::<O>() <= std::mem::size_of::<[u64; 2]>()
+            && std::mem::align_of::<O>() <= std::mem::align_of::<[u64; 2]>()
+            && !std::mem::needs_drop::<O>()
+    }
+
+    /// stores a bitwise copy of `val`. does nothing unless `fits::<O>()`
+    pub fn store<O: 'static>(&self, val: &O) {
+        if !Self::fits::<O>() {
+            return;
+        }
+        let mut bytes = MaybeUninit::<[u64; 2]>::uninit();
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                val as *const O as *const u8,
+                bytes.as_mut_ptr() as *mut u8,
+                std::mem::size_of::<O>(),
+            );
+        }
+        self.bytes.set(bytes);
+        self.type_id.set(Some(TypeId::of::<O>()));
+    }
+
+    /// returns the stored value if it has type `O`. requiring `Copy` here is what makes handing
+    /// out a bitwise copy sound, even though `store` accepts any type
+    pub fn load<O: Copy + 'static>(&self) -> Option<O> {
+        if self.type_id.get() != Some(TypeId::of::<O>()) {
+            return None;
+        }
+        let bytes = self.bytes.get();
+        Some(unsafe { std::ptr::read(bytes.as_ptr() as *const O) })
+    }
+
+    pub fn wanted(&self) -> bool {
+        self.wanted.get()
+    }
+
+    pub fn want(&self) {
+        self.wanted.set(true);
+    }
+
+    /// drops the stored copy, but keeps copying later outputs if they're wanted
+    pub fn clear(&self) {
+        self.type_id.set(None);
+    }
+
+    /// drops the stored copy and stops copying, for when the node is freed
+    pub fn reset(&self) {
+        self.wanted.set(false);
+        self.type_id.set(None);
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub struct NodeKey {
-    ptr: NodePtr,
+    pub(super) ptr: NodePtr,
     token: u32,
+    index: u32,
+    generation: u32,
+    /// keys point into a single-threaded graph, so they must not be `Send` or `Sync`
+    _not_send: PhantomData<*const ()>,
 }
 
-impl !Send for NodeKey {}
-impl !Sync for NodeKey {}
-
 pub struct NodePtrs {
-    /// first parent, remaining parents. unsorted, duplicates may exist
+    /// first parent, remaining parents. unsorted; a parent that requests this node again is added
+    /// again, so duplicates may exist until the list is compacted or drained
     clean_parent0: Cell<Option<NodePtr>>,
-    clean_parents: RefCell<Vec<NodePtr>>,
+    clean_parents: RefCell<NodePtrVec>,
 
     graph: *const Graph2,
 
@@ -99,7 +313,7 @@ pub struct NodePtrs {
     recalc_state: Cell<RecalcState>,
 
     /// sorted in pointer order
-    necessary_children: RefCell<Vec<NodePtr>>,
+    necessary_children: RefCell<NodePtrVec>,
 
     height: Cell<usize>,
 
@@ -116,7 +330,7 @@ pub struct AnchorHandle {
 impl Clone for AnchorHandle {
     fn clone(&self) -> Self {
         if self.still_alive.get() {
-            let count = &unsafe { self.num.ptr.lookup_unchecked() }.ptrs.handle_count;
+            let count = &unsafe { deref_ptr(self.num.ptr) }.ptrs.handle_count;
             count.set(count.get() + 1);
         }
         AnchorHandle {
@@ -129,7 +343,7 @@ impl Clone for AnchorHandle {
 impl Drop for AnchorHandle {
     fn drop(&mut self) {
         if self.still_alive.get() {
-            let count = &unsafe { self.num.ptr.lookup_unchecked() }.ptrs.handle_count;
+            let count = &unsafe { deref_ptr(self.num.ptr) }.ptrs.handle_count;
             let new_count = count.get() - 1;
             count.set(new_count);
             std::mem::drop(count);
@@ -139,6 +353,35 @@ impl Drop for AnchorHandle {
         }
     }
 }
+impl AnchorHandle {
+    /// sets the user-assigned name in this node's debug info. does nothing if the graph is gone
+    pub(super) fn set_name(&self, name: &'static str) {
+        if self.still_alive.get() {
+            let node = unsafe { deref_ptr(self.num.ptr) };
+            let mut debug_info = node.debug_info.get();
+            debug_info.name = Some(name);
+            node.debug_info.set(debug_info);
+        }
+    }
+
+    /// sets this node's priority, moving it within the recalculation queue if it's queued. does
+    /// nothing if the graph is gone
+    pub(super) fn set_priority(&self, priority: i32) {
+        if self.still_alive.get() {
+            let node = NodeGuard(unsafe { deref_ptr(self.num.ptr) });
+            let graph = unsafe { &*node.ptrs.graph };
+            let queued = node.ptrs.recalc_state.get() == RecalcState::Pending;
+            if queued {
+                dequeue_calc(graph, node);
+            }
+            node.priority.set(priority);
+            if queued {
+                enqueue_calc(graph, node);
+            }
+        }
+    }
+}
+
 impl crate::expert::AnchorHandle for AnchorHandle {
     type Token = NodeKey;
     fn token(&self) -> NodeKey {
@@ -153,24 +396,71 @@ impl<'a> std::ops::Deref for NodeGuard<'a> {
     }
 }
 
+impl NodeKey {
+    /// Returns a `Send` id for this Anchor, for matching it up with `EngineEvent`s.
+    pub fn id(self) -> AnchorId {
+        AnchorId {
+            graph: self.token,
+            index: self.index,
+            generation: self.generation,
+        }
+    }
+}
+
 impl<'a> NodeGuard<'a> {
     pub fn key(self) -> NodeKey {
         NodeKey {
             ptr: unsafe { self.0.make_ptr() },
             token: self.token,
+            index: self.index,
+            generation: self.generation.get(),
+            _not_send: PhantomData,
         }
     }
 
     pub fn add_clean_parent(self, parent: NodeGuard<'a>) {
-        if self.ptrs.clean_parent0.get().is_none() {
-            self.ptrs
-                .clean_parent0
-                .set(Some(unsafe { parent.0.make_ptr() }))
+        let parent = unsafe { parent.0.make_ptr() };
+        match self.ptrs.clean_parent0.get() {
+            None => self.ptrs.clean_parent0.set(Some(parent)),
+            Some(first) if first == parent => {}
+            Some(_) => {
+                let mut parents = self.ptrs.clean_parents.borrow_mut();
+                if parents.last() == Some(&parent) {
+                    return;
+                }
+                // observed parents request their inputs every time they're recalculated, so
+                // compact the list when it fills up to keep inputs that rarely change from growing
+                // it without bound. if that doesn't free at least half of it, grow it anyway, so
+                // compacting stays amortized
+                if parents.len() >= 8 && parents.len() == parents.capacity() {
+                    self.dedup_clean_parents(&mut parents);
+                    if parents.len() > parents.capacity() / 2 {
+                        let capacity = parents.capacity();
+                        parents.reserve(capacity);
+                    }
+                }
+                parents.push(parent);
+            }
+        }
+    }
+
+    /// removes duplicates from the remaining clean parents, including copies of the first. keeps
+    /// the order they were added in, since same-priority parents are recalculated in that order
+    fn dedup_clean_parents(self, parents: &mut NodePtrVec) {
+        let first = self.ptrs.clean_parent0.get();
+        if parents.len() <= 8 {
+            let mut kept = 0;
+            for i in 0..parents.len() {
+                let parent = parents[i];
+                if Some(parent) != first && !parents[..kept].contains(&parent) {
+                    parents[kept] = parent;
+                    kept += 1;
+                }
+            }
+            parents.truncate(kept);
         } else {
-            self.ptrs
-                .clean_parents
-                .borrow_mut()
-                .push(unsafe { parent.0.make_ptr() })
+            let mut seen: std::collections::HashSet<NodePtr> = first.into_iter().collect();
+            parents.retain(|parent| seen.insert(*parent));
         }
     }
 
@@ -184,9 +474,15 @@ impl<'a> NodeGuard<'a> {
         }
     }
 
+    /// Removes and returns each clean parent, without duplicates, so each one is only dirtied
+    /// once per change.
     pub fn drain_clean_parents(self) -> impl Iterator<Item = NodeGuard<'a>> {
+        let mut parents = self.0.node().ptrs.clean_parents.borrow_mut();
+        if !parents.is_empty() {
+            self.dedup_clean_parents(&mut parents);
+        }
         RefCellVecIterator {
-            inside: self.0.node().ptrs.clean_parents.borrow_mut(),
+            inside: parents,
             next_i: 0,
             first: self.ptrs.clean_parent0.take(),
             f: PhantomData,
@@ -203,6 +499,34 @@ impl<'a> NodeGuard<'a> {
         }
     }
 
+    /// Like calling `add_necessary_child` for each of `children`, but sorts the list once rather
+    /// than inserting each child into place.
+    pub fn add_necessary_children(self, children: &mut [NodeGuard<'a>]) {
+        if children.is_empty() {
+            return;
+        }
+        children.sort_unstable_by_key(|child| unsafe { child.0.make_ptr() });
+        let mut necessary_children = self.ptrs.necessary_children.borrow_mut();
+        let existing = necessary_children.len();
+        let mut last = None;
+        for child in children.iter() {
+            let child_ptr = unsafe { child.0.make_ptr() };
+            if last == Some(child_ptr)
+                || necessary_children[..existing]
+                    .binary_search(&child_ptr)
+                    .is_ok()
+            {
+                continue;
+            }
+            last = Some(child_ptr);
+            necessary_children.push(child_ptr);
+            child.necessary_count.set(child.necessary_count.get() + 1)
+        }
+        if necessary_children.len() > existing {
+            necessary_children.sort_unstable();
+        }
+    }
+
     pub fn remove_necessary_child(self, child: NodeGuard<'a>) {
         let mut necessary_children = self.ptrs.necessary_children.borrow_mut();
         let child_ptr = unsafe { child.0.make_ptr() };
@@ -225,7 +549,7 @@ impl<'a> NodeGuard<'a> {
     pub fn drain_necessary_children(self) -> impl Iterator<Item = NodeGuard<'a>> {
         let necessary_children = self.0.node().ptrs.necessary_children.borrow_mut();
         for child in &*necessary_children {
-            let count = &unsafe { self.0.lookup_ptr(*child) }.necessary_count;
+            let count = &unsafe { deref_ptr(*child) }.necessary_count;
             count.set(count.get() - 1);
         }
         RefCellVecIterator {
@@ -239,7 +563,7 @@ impl<'a> NodeGuard<'a> {
 }
 
 struct RefCellVecIterator<'a> {
-    inside: RefMut<'a, Vec<NodePtr>>,
+    inside: RefMut<'a, NodePtrVec>,
     next_i: usize,
     first: Option<NodePtr>,
     // hack to make RefCellVecIterator invariant
@@ -250,13 +574,14 @@ struct RefCellVecIterator<'a> {
 impl<'a> Iterator for RefCellVecIterator<'a> {
     type Item = NodeGuard<'a>;
 
+    // clean parent edges aren't removed when the parent is freed, so edges may point at free slots
     fn next(&mut self) -> Option<Self::Item> {
         if let Some(first) = self.first.take() {
-            return Some(NodeGuard(unsafe { first.lookup_unchecked() }));
+            return Some(NodeGuard(unsafe { deref_slot(first) }));
         }
         let next = self.inside.get(self.next_i)?;
         self.next_i += 1;
-        Some(NodeGuard(unsafe { next.lookup_unchecked() }))
+        Some(NodeGuard(unsafe { deref_slot(*next) }))
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -283,7 +608,27 @@ impl<'gg> Graph2Guard<'gg> {
         if key.token != self.graph.graph_token {
             return None;
         }
-        Some(NodeGuard(unsafe { self.nodes.lookup_ptr(key.ptr) }))
+        let node = NodeGuard(unsafe { deref_slot(key.ptr) });
+        if node.generation.get() != key.generation {
+            // the node this key referred to was freed, and its slot may hold a different anchor now
+            return None;
+        }
+        Some(node)
+    }
+
+    /// Like `get`, but reports why `key` couldn't be found.
+    pub fn try_lookup(&self, key: NodeKey) -> Result<NodeGuard<'gg>, Error> {
+        if key.token != self.graph.graph_token
+            && LIVE_GRAPHS.with(|live| live.borrow().contains(&key.token))
+        {
+            return Err(Error::WrongEngine);
+        }
+        self.get(key).ok_or(Error::Freed)
+    }
+
+    /// Like `get`, but panics with a helpful message if `key` belongs to a different graph.
+    pub fn lookup(&self, key: NodeKey) -> NodeGuard<'gg> {
+        self.try_lookup(key).unwrap_or_else(|err| panic!("{}", err))
     }
 
     #[cfg(test)]
@@ -298,13 +643,13 @@ impl<'gg> Graph2Guard<'gg> {
         let mut recalc_queues = self.graph.recalc_queues.borrow_mut();
         while self.graph.recalc_min_height.get() <= self.graph.recalc_max_height.get() {
             if let Some(ptr) = recalc_queues[self.graph.recalc_min_height.get()] {
-                let node = unsafe { self.nodes.lookup_ptr(ptr) };
+                let node = unsafe { deref_ptr(ptr) };
                 recalc_queues[self.graph.recalc_min_height.get()] = node.ptrs.next.get();
                 if let Some(next_in_queue_ptr) = node.ptrs.next.get() {
-                    unsafe { self.nodes.lookup_ptr(next_in_queue_ptr) }
-                        .ptrs
-                        .prev
-                        .set(None);
+                    unsafe { deref_ptr(next_in_queue_ptr) }.ptrs.prev.set(None);
+                } else {
+                    self.graph.recalc_queue_tails.borrow_mut()
+                        [self.graph.recalc_min_height.get()] = None;
                 }
                 node.ptrs.prev.set(None);
                 node.ptrs.next.set(None);
@@ -320,55 +665,260 @@ impl<'gg> Graph2Guard<'gg> {
         None
     }
 
+    /// Starts deferring recalculations queued with `queue_recalc_pushed`. Every node currently in
+    /// the recalculation queue is removed and returned, so the caller can defer it as well.
+    pub fn begin_deferring(&self) -> Vec<NodeGuard<'gg>> {
+        let mut queued = vec![];
+        while let Some((_, node)) = self.recalc_pop_next() {
+            queued.push(node);
+        }
+        *self.graph.deferred_recalc.borrow_mut() = Some(vec![]);
+        queued
+    }
+
+    /// Stops deferring recalculations, and queues any deferred node that is still alive and
+    /// still needs it.
+    pub fn end_deferring(&self) {
+        let deferred = self.graph.deferred_recalc.borrow_mut().take();
+        for key in deferred.into_iter().flatten() {
+            match self.get(key) {
+                Some(node) if recalc_state(node) != RecalcState::Ready => self.queue_recalc(node),
+                _ => {}
+            }
+        }
+    }
+
+    /// Queues a node for recalculation because one of its inputs changed, rather than because
+    /// some other node requested it. Between `begin_deferring` and `end_deferring`, the node is
+    /// recorded to be queued later instead, and this returns false.
+    pub fn queue_recalc_pushed(&self, node: NodeGuard<'gg>) -> bool {
+        if let Some(deferred) = self.graph.deferred_recalc.borrow_mut().as_mut() {
+            deferred.push(node.key());
+            return false;
+        }
+        self.queue_recalc(node);
+        true
+    }
+
+    /// Returns every node that hasn't been freed.
+    pub fn live_nodes(&self) -> Vec<NodeGuard<'gg>> {
+        self.graph
+            .all_nodes
+            .borrow()
+            .iter()
+            .map(|ptr| NodeGuard(unsafe { deref_slot(*ptr) }))
+            .filter(|node| node.ptrs.handle_count.get() > 0)
+            .collect()
+    }
+
+    /// Returns the number of nodes allocated, including freed nodes waiting to be reused.
+    pub fn allocated_nodes(&self) -> usize {
+        self.graph.all_nodes.borrow().len()
+    }
+
+    /// Estimates the memory used by the graph: every allocated node, its edge lists, its boxed
+    /// `AnchorInner`, and the recalculation queue. Heap memory owned by `AnchorInner`s isn't
+    /// counted.
+    pub fn approx_bytes(&self) -> usize {
+        let ptr_size = std::mem::size_of::<NodePtr>();
+        let mut bytes = 0;
+        for ptr in self.graph.all_nodes.borrow().iter() {
+            let node = unsafe { deref_slot(*ptr) };
+            bytes += std::mem::size_of::<Node>();
+            for edges in [&node.ptrs.clean_parents, &node.ptrs.necessary_children] {
+                let edges = edges.borrow();
+                if edges.spilled() {
+                    bytes += edges.capacity() * ptr_size;
+                }
+            }
+            bytes += node.dirtied_children.borrow().capacity() * std::mem::size_of::<NodeKey>();
+            bytes += match node.anchor.try_borrow() {
+                Ok(anchor) => anchor
+                    .as_ref()
+                    .map_or(0, |anchor| std::mem::size_of_val(&**anchor)),
+                Err(_) => 0,
+            };
+        }
+        bytes += self.graph.all_nodes.borrow().capacity() * ptr_size;
+        bytes += (self.graph.recalc_queues.borrow().capacity()
+            + self.graph.recalc_queue_tails.borrow().capacity())
+            * std::mem::size_of::<Option<NodePtr>>();
+        for (layout, ptrs) in self.graph.box_pool.borrow().iter() {
+            bytes += layout.size() * ptrs.len();
+        }
+        bytes
+    }
+
+    /// Releases spare capacity held by nodes and the recalculation queue. Freed nodes drop their
+    /// edge lists entirely. The nodes themselves stay allocated, since handles point directly at
+    /// them, and are reused by later inserts.
+    pub fn shrink_to_fit(&self) {
+        self.graph.release_box_pool();
+        for ptr in self.graph.all_nodes.borrow().iter() {
+            let node = unsafe { deref_slot(*ptr) };
+            if node.ptrs.handle_count.get() == 0 {
+                node.ptrs.clean_parents.replace(NodePtrVec::new());
+                node.ptrs.necessary_children.replace(NodePtrVec::new());
+                node.dirtied_children.replace(Vec::new());
+            } else {
+                node.ptrs.clean_parents.borrow_mut().shrink_to_fit();
+                node.ptrs.necessary_children.borrow_mut().shrink_to_fit();
+                node.dirtied_children.borrow_mut().shrink_to_fit();
+            }
+        }
+        self.graph.all_nodes.borrow_mut().shrink_to_fit();
+
+        let mut recalc_queues = self.graph.recalc_queues.borrow_mut();
+        let min_len = INITIAL_QUEUE_HEIGHTS.min(self.graph.max_height);
+        let used_len = recalc_queues
+            .iter()
+            .rposition(Option::is_some)
+            .map_or(0, |i| i + 1)
+            .max(self.graph.recalc_max_height.get() + 1);
+        recalc_queues.truncate(used_len.max(min_len));
+        recalc_queues.shrink_to_fit();
+        let mut recalc_queue_tails = self.graph.recalc_queue_tails.borrow_mut();
+        recalc_queue_tails.truncate(used_len.max(min_len));
+        recalc_queue_tails.shrink_to_fit();
+    }
+
+    /// Returns true if any node is waiting in the recalculation queue.
+    pub fn recalc_queued(&self) -> bool {
+        let min = self.graph.recalc_min_height.get();
+        let max = self.graph.recalc_max_height.get();
+        min <= max
+            && self.graph.recalc_queues.borrow()[min..=max]
+                .iter()
+                .any(Option::is_some)
+    }
+
     pub fn queue_recalc(&self, node: NodeGuard<'gg>) {
         if node.ptrs.recalc_state.get() == RecalcState::Pending {
             // already in recalc queue
             return;
         }
         node.ptrs.recalc_state.set(RecalcState::Pending);
-        let node_height = height(node);
-        let mut recalc_queues = self.graph.recalc_queues.borrow_mut();
-        if node_height >= recalc_queues.len() {
-            panic!("too large height error");
-        }
-        if let Some(old) = recalc_queues[node_height] {
-            unsafe { self.nodes.lookup_ptr(old) }
-                .ptrs
-                .prev
-                .set(Some(unsafe { node.0.make_ptr() }));
-            node.ptrs.next.set(Some(old));
-        } else {
-            if self.graph.recalc_min_height.get() > node_height {
-                self.graph.recalc_min_height.set(node_height);
-            }
-            if self.graph.recalc_max_height.get() < node_height {
-                self.graph.recalc_max_height.set(node_height);
-            }
-        }
-        recalc_queues[node_height] = Some(unsafe { node.0.make_ptr() });
+        enqueue_calc(self.graph, node);
+        self.graph.emit(EngineEvent::NodeQueued {
+            id: node.key().id(),
+            height: height(node),
+        });
     }
 }
 
 impl Graph2 {
     pub fn new(max_height: usize) -> Self {
+        let graph_token = NEXT_TOKEN.with(|token| {
+            let n = token.get();
+            token.set(n + 1);
+            n
+        });
+        LIVE_GRAPHS.with(|live| live.borrow_mut().push(graph_token));
         Self {
             nodes: ag::Graph::new(),
-            graph_token: NEXT_TOKEN.with(|token| {
-                let n = token.get();
-                token.set(n + 1);
-                n
-            }),
-            recalc_queues: RefCell::new(vec![None; max_height]),
+            graph_token,
+            recalc_queues: RefCell::new(vec![None; max_height.min(INITIAL_QUEUE_HEIGHTS)]),
+            recalc_queue_tails: RefCell::new(vec![None; max_height.min(INITIAL_QUEUE_HEIGHTS)]),
+            max_height,
             recalc_min_height: Cell::new(max_height),
             recalc_max_height: Cell::new(0),
             still_alive: Rc::new(Cell::new(true)),
+            deferred_recalc: RefCell::new(None),
             free_head: Box::new(Cell::new(None)),
+            all_nodes: RefCell::new(vec![]),
+            event_senders: RefCell::new(vec![]),
+            box_pool: RefCell::new(HashMap::new()),
+            pooled_boxes: Cell::new(0),
+        }
+    }
+
+    /// boxes `anchor`, reusing the allocation of some freed node's `AnchorInner` if one with the
+    /// same layout is available
+    pub(super) fn box_anchor<I: GenericAnchor + 'static>(
+        &self,
+        anchor: I,
+    ) -> Box<dyn GenericAnchor> {
+        let reused = self
+            .box_pool
+            .borrow_mut()
+            .get_mut(&Layout::new::<I>())
+            .and_then(Vec::pop);
+        match reused {
+            Some(ptr) => {
+                self.pooled_boxes.set(self.pooled_boxes.get() - 1);
+                let ptr = ptr.as_ptr() as *mut I;
+                // SAFETY: the allocation came from a `Box` with the same layout as `I`, and its
+                // previous contents have already been dropped
+                unsafe {
+                    ptr.write(anchor);
+                    Box::from_raw(ptr)
+                }
+            }
+            None => Box::new(anchor),
+        }
+    }
+
+    /// drops a freed node's `AnchorInner`, keeping its allocation for `box_anchor` to reuse
+    fn recycle_box(&self, anchor: Box<dyn GenericAnchor>) {
+        if cfg!(miri) {
+            // under Miri, plain boxes let it track every allocation's lifetime on its own
+            std::mem::drop(anchor);
+            return;
+        }
+        let layout = Layout::for_value(&*anchor);
+        let ptr = Box::into_raw(anchor);
+        // this may free other nodes, which recycle their boxes too, so the pool can't be borrowed
+        unsafe { std::ptr::drop_in_place(ptr) };
+        let ptr = unsafe { NonNull::new_unchecked(ptr as *mut u8) };
+        if layout.size() == 0 {
+            return;
+        }
+        if self.pooled_boxes.get() >= self.all_nodes.borrow().len() {
+            unsafe { std::alloc::dealloc(ptr.as_ptr(), layout) };
+            return;
+        }
+        self.box_pool
+            .borrow_mut()
+            .entry(layout)
+            .or_default()
+            .push(ptr);
+        self.pooled_boxes.set(self.pooled_boxes.get() + 1);
+    }
+
+    /// deallocates every pooled allocation
+    fn release_box_pool(&self) {
+        for (layout, ptrs) in self.box_pool.borrow_mut().drain() {
+            for ptr in ptrs {
+                unsafe { std::alloc::dealloc(ptr.as_ptr(), layout) };
+            }
         }
+        self.pooled_boxes.set(0);
+    }
+
+    /// sends `event` to every receiver registered with `Engine::event_stream`, forgetting
+    /// receivers that have been dropped
+    pub fn emit(&self, event: EngineEvent) {
+        let mut senders = self.event_senders.borrow_mut();
+        if !senders.is_empty() {
+            senders.retain(|sender| sender.send(event).is_ok());
+        }
+    }
+
+    pub fn max_height(&self) -> usize {
+        self.max_height
+    }
+
+    /// Reserves room for bookkeeping on at least `additional` more nodes.
+    pub fn reserve(&self, additional: usize) {
+        self.all_nodes.borrow_mut().reserve(additional);
     }
 
     pub fn with<F: for<'any> FnOnce(Graph2Guard<'any>) -> R, R>(&self, func: F) -> R {
         let nodes = unsafe { self.nodes.with_unchecked() };
-        func(Graph2Guard { nodes, graph: self })
+        func(Graph2Guard {
+            _nodes: nodes,
+            graph: self,
+        })
     }
 
     #[cfg(test)]
@@ -376,6 +926,7 @@ impl Graph2 {
         self.insert(
             Box::new(crate::expert::constant::Constant::new_raw_testing(123)),
             AnchorDebugInfo {
+                name: None,
                 location: None,
                 type_info: "testing dummy anchor",
             },
@@ -389,19 +940,31 @@ impl Graph2 {
     ) -> AnchorHandle {
         self.nodes.with(|nodes| {
             let ptr = if let Some(free_head) = self.free_head.get() {
-                let node = unsafe { nodes.lookup_ptr(free_head) };
+                let node = unsafe { deref_slot(free_head) };
+                register_node(free_head, true);
                 self.free_head.set(node.ptrs.next.get());
                 if let Some(next_ptr) = node.ptrs.next.get() {
-                    let next_node = unsafe { nodes.lookup_ptr(next_ptr) };
+                    let next_node = unsafe { deref_slot(next_ptr) };
                     next_node.ptrs.prev.set(None);
                 }
                 node.observed.set(false);
                 node.visited.set(false);
                 node.necessary_count.set(0);
+                node.keep_alive_until.set(None);
+                node.output_cached.set(false);
+                node.recalculations.set(0);
+                node.recalc_time.set(Duration::ZERO);
+                node.priority.set(0);
+                node.inline_output.reset();
+                node.output_type.set(Some(anchor.output_type()));
+                node.cutoff_output.replace(None);
+                node.poison.replace(None);
+                node.dirtied_children.borrow_mut().clear();
                 node.ptrs.clean_parent0.set(None);
-                node.ptrs.clean_parents.replace(vec![]);
+                // keep any spilled edge lists' capacity, so churning nodes doesn't reallocate them
+                node.ptrs.clean_parents.borrow_mut().clear();
                 node.ptrs.recalc_state.set(RecalcState::Needed);
-                node.ptrs.necessary_children.replace(vec![]);
+                node.ptrs.necessary_children.borrow_mut().clear();
                 node.ptrs.height.set(0);
                 node.ptrs.handle_count.set(1);
                 node.ptrs.prev.set(None);
@@ -412,32 +975,18 @@ impl Graph2 {
                 node.anchor.replace(Some(anchor));
                 node
             } else {
-                let node = Node {
-                    observed: Cell::new(false),
-                    visited: Cell::new(false),
-                    necessary_count: Cell::new(0),
-                    token: self.graph_token,
-                    ptrs: NodePtrs {
-                        clean_parent0: Cell::new(None),
-                        clean_parents: RefCell::new(vec![]),
-                        graph: &*self,
-                        next: Cell::new(None),
-                        prev: Cell::new(None),
-                        recalc_state: Cell::new(RecalcState::Needed),
-                        necessary_children: RefCell::new(vec![]),
-                        height: Cell::new(0),
-                        handle_count: Cell::new(1),
-                    },
-                    debug_info: Cell::new(debug_info),
-                    last_ready: Cell::new(None),
-                    last_update: Cell::new(None),
-                    anchor: RefCell::new(Some(anchor)),
-                };
-                nodes.insert(node)
+                let node = nodes.insert(self.new_node(Some(anchor), debug_info, 1));
+                let ptr = unsafe { node.make_ptr() };
+                register_node(ptr, true);
+                self.all_nodes.borrow_mut().push(ptr);
+                node
             };
             let num = NodeKey {
                 ptr: unsafe { ptr.make_ptr() },
                 token: self.graph_token,
+                index: ptr.index,
+                generation: ptr.generation.get(),
+                _not_send: PhantomData,
             };
             AnchorHandle {
                 num,
@@ -445,54 +994,242 @@ impl Graph2 {
             }
         })
     }
+
+    /// Creates another handle to the node `key` refers to, or returns `None` if it's been freed
+    /// or belongs to a different graph.
+    pub(super) fn handle_for(&self, key: NodeKey) -> Option<AnchorHandle> {
+        self.with(|graph| {
+            let node = graph.get(key)?;
+            let count = &node.ptrs.handle_count;
+            count.set(count.get() + 1);
+            Some(AnchorHandle {
+                num: key,
+                still_alive: self.still_alive.clone(),
+            })
+        })
+    }
+
+    /// Allocates `count` nodes and adds them to the free list, so the next `count` inserts don't
+    /// need to allocate nodes.
+    pub fn preallocate(&self, count: usize) {
+        self.reserve(count);
+        self.nodes.with(|nodes| {
+            for _ in 0..count {
+                let debug_info = AnchorDebugInfo {
+                    name: None,
+                    location: None,
+                    type_info: "preallocated node",
+                };
+                let node = nodes.insert(self.new_node(None, debug_info, 0));
+                let ptr = unsafe { node.make_ptr() };
+                register_node(ptr, false);
+                self.all_nodes.borrow_mut().push(ptr);
+                let old_free = self.free_head.get();
+                if let Some(old_free) = old_free {
+                    unsafe { deref_slot(old_free) }.ptrs.prev.set(Some(ptr));
+                }
+                node.ptrs.next.set(old_free);
+                self.free_head.set(Some(ptr));
+            }
+        })
+    }
+
+    fn new_node(
+        &self,
+        anchor: Option<Box<dyn GenericAnchor>>,
+        debug_info: AnchorDebugInfo,
+        handle_count: usize,
+    ) -> Node {
+        Node {
+            observed: Cell::new(false),
+            visited: Cell::new(false),
+            necessary_count: Cell::new(0),
+            keep_alive_until: Cell::new(None),
+            output_cached: Cell::new(false),
+            recalculations: Cell::new(0),
+            recalc_time: Cell::new(Duration::ZERO),
+            priority: Cell::new(0),
+            inline_output: InlineOutput::new(),
+            output_type: Cell::new(anchor.as_ref().map(|anchor| anchor.output_type())),
+            cutoff_output: RefCell::new(None),
+            poison: RefCell::new(None),
+            dirtied_children: RefCell::new(Vec::new()),
+            token: self.graph_token,
+            index: self.all_nodes.borrow().len() as u32,
+            generation: Cell::new(0),
+            ptrs: NodePtrs {
+                clean_parent0: Cell::new(None),
+                clean_parents: RefCell::new(NodePtrVec::new()),
+                graph: self,
+                next: Cell::new(None),
+                prev: Cell::new(None),
+                recalc_state: Cell::new(RecalcState::Needed),
+                necessary_children: RefCell::new(NodePtrVec::new()),
+                height: Cell::new(0),
+                handle_count: Cell::new(handle_count),
+            },
+            debug_info: Cell::new(debug_info),
+            last_ready: Cell::new(None),
+            last_update: Cell::new(None),
+            anchor: RefCell::new(anchor),
+        }
+    }
 }
 
 impl Drop for Graph2 {
     fn drop(&mut self) {
         self.still_alive.set(false);
+        self.release_box_pool();
+        let _ = LIVE_GRAPHS.try_with(|live| live.borrow_mut().retain(|t| *t != self.graph_token));
+        #[cfg(feature = "checked")]
+        {
+            let all_nodes = self.all_nodes.borrow();
+            let _ = CHECKED_NODES.try_with(|nodes| {
+                let mut nodes = nodes.borrow_mut();
+                for ptr in all_nodes.iter() {
+                    nodes.remove(ptr);
+                }
+            });
+        }
     }
 }
 
 pub fn ensure_height_increases<'a>(
     child: NodeGuard<'a>,
     parent: NodeGuard<'a>,
-) -> Result<bool, ()> {
+) -> Result<bool, Error> {
     if height(child) < height(parent) {
         return Ok(true);
     }
     child.visited.set(true);
-    let res = set_min_height(parent, height(child) + 1);
+    let mut res = set_min_height(parent, height(child) + 1);
     child.visited.set(false);
-    res.map(|()| false)
+    close_cycle(&mut res, child);
+    match res {
+        Ok(()) => Ok(false),
+        Err(HeightError::Cycle { path, .. }) => Err(Error::CycleDetected { path }),
+        Err(HeightError::Other(err)) => Err(err),
+    }
+}
+
+#[derive(Debug)]
+enum HeightError<'a> {
+    /// A loop back to `start` was found. Nodes are added to `path` as the search unwinds, until
+    /// `start` itself is reached.
+    Cycle {
+        start: NodeGuard<'a>,
+        path: Vec<AnchorDebugInfo>,
+        closed: bool,
+    },
+    Other(Error),
 }
 
-fn set_min_height<'a>(node: NodeGuard<'a>, min_height: usize) -> Result<(), ()> {
+fn close_cycle<'a>(res: &mut Result<(), HeightError<'a>>, node: NodeGuard<'a>) {
+    if let Err(HeightError::Cycle {
+        start,
+        path,
+        closed: closed @ false,
+    }) = res
+    {
+        path.push(node.debug_info.get());
+        *closed = *start == node;
+    }
+}
+
+fn set_min_height<'a>(node: NodeGuard<'a>, min_height: usize) -> Result<(), HeightError<'a>> {
     if node.visited.get() {
-        return Err(());
+        return Err(HeightError::Cycle {
+            start: node,
+            path: vec![],
+            closed: false,
+        });
     }
     node.visited.set(true);
     if height(node) < min_height {
+        let graph = unsafe { &*node.ptrs.graph };
+        let max_height = graph.max_height();
+        if min_height >= max_height {
+            node.visited.set(false);
+            return Err(HeightError::Other(Error::MaxHeightExceeded { max_height }));
+        }
+        // move queued nodes to their new height, so they aren't popped early and skipped
+        let queued = node.ptrs.recalc_state.get() == RecalcState::Pending;
+        if queued {
+            dequeue_calc(graph, node);
+        }
         node.ptrs.height.set(min_height);
-        let mut did_err = false;
+        if queued {
+            enqueue_calc(graph, node);
+        }
+        let mut res = Ok(());
         for parent in node.clean_parents() {
-            if let Err(_loop_ids) = set_min_height(parent, min_height + 1) {
-                did_err = true;
+            if let Err(err) = set_min_height(parent, min_height + 1) {
+                res = res.and(Err(err));
             }
         }
-        if did_err {
-            return Err(());
+        if res.is_err() {
+            node.visited.set(false);
+            close_cycle(&mut res, node);
+            return res;
         }
     }
     node.visited.set(false);
     Ok(())
 }
 
+/// adds a node to the queue for its current height. the node should already be marked Pending
+fn enqueue_calc<'a>(graph: &Graph2, node: NodeGuard<'a>) {
+    let node_height = height(node);
+    let mut recalc_queues = graph.recalc_queues.borrow_mut();
+    let mut recalc_queue_tails = graph.recalc_queue_tails.borrow_mut();
+    if node_height >= recalc_queues.len() {
+        if node_height >= graph.max_height {
+            panic!("too large height error");
+        }
+        let len = (node_height + 1)
+            .max(recalc_queues.len() * 2)
+            .min(graph.max_height);
+        recalc_queues.resize(len, None);
+        recalc_queue_tails.resize(len, None);
+    }
+    let ptr = unsafe { node.0.make_ptr() };
+    if recalc_queues[node_height].is_none() {
+        if graph.recalc_min_height.get() > node_height {
+            graph.recalc_min_height.set(node_height);
+        }
+        if graph.recalc_max_height.get() < node_height {
+            graph.recalc_max_height.set(node_height);
+        }
+    }
+    // insert behind every node of the same or higher priority, so same-height nodes are
+    // recalculated by priority, then first-in-first-out. usually every node has the same
+    // priority, so this appends to the end of the queue without walking it
+    let mut prev = recalc_queue_tails[node_height];
+    while let Some(p) = prev {
+        let p = unsafe { deref_ptr(p) };
+        if p.priority.get() >= node.priority.get() {
+            break;
+        }
+        prev = p.ptrs.prev.get();
+    }
+    let next = match prev {
+        Some(prev) => unsafe { deref_ptr(prev) }.ptrs.next.replace(Some(ptr)),
+        None => recalc_queues[node_height].replace(ptr),
+    };
+    node.ptrs.prev.set(prev);
+    node.ptrs.next.set(next);
+    match next {
+        Some(next) => unsafe { deref_ptr(next) }.ptrs.prev.set(Some(ptr)),
+        None => recalc_queue_tails[node_height] = Some(ptr),
+    }
+}
+
 fn dequeue_calc<'a>(graph: &Graph2, node: NodeGuard<'a>) {
     if node.ptrs.recalc_state.get() != RecalcState::Pending {
         return;
     }
     if let Some(prev) = node.ptrs.prev.get() {
-        unsafe { prev.lookup_unchecked() }
+        unsafe { deref_ptr(prev) }
             .ptrs
             .next
             .set(node.ptrs.next.get());
@@ -502,17 +1239,21 @@ fn dequeue_calc<'a>(graph: &Graph2, node: NodeGuard<'a>) {
         let height = node.ptrs.height.get();
         let next = node.ptrs.next.get();
         assert_eq!(
-            recalc_queues[height].map(|ptr| unsafe { ptr.lookup_unchecked() }),
+            recalc_queues[height].map(|ptr| unsafe { deref_ptr(ptr) }),
             Some(node.0)
         );
         recalc_queues[height] = next;
     }
 
     if let Some(next) = node.ptrs.next.get() {
-        unsafe { next.lookup_unchecked() }
+        unsafe { deref_ptr(next) }
             .ptrs
-            .next
+            .prev
             .set(node.ptrs.prev.get());
+    } else {
+        // node was last in queue, need to set queue tail to prev
+        let height = node.ptrs.height.get();
+        graph.recalc_queue_tails.borrow_mut()[height] = node.ptrs.prev.get();
     }
 
     node.ptrs.prev.set(None);
@@ -520,9 +1261,17 @@ fn dequeue_calc<'a>(graph: &Graph2, node: NodeGuard<'a>) {
 }
 
 unsafe fn free(ptr: NodePtr) {
-    let guard = NodeGuard(ptr.lookup_unchecked());
+    let guard = NodeGuard(deref_ptr(ptr));
     let _ = guard.drain_necessary_children();
     let _ = guard.drain_clean_parents();
+    guard.keep_alive_until.set(None);
+    guard.output_cached.set(false);
+    guard.poison.replace(None);
+    guard.dirtied_children.borrow_mut().clear();
+    guard.inline_output.reset();
+    guard.output_type.set(None);
+    guard.cutoff_output.replace(None);
+    guard.generation.set(guard.generation.get().wrapping_add(1));
     let graph = &*(*guard).ptrs.graph;
     dequeue_calc(graph, guard);
     // TODO clear out this node with default empty data
@@ -530,13 +1279,17 @@ unsafe fn free(ptr: NodePtr) {
     let free_head = &graph.free_head;
     let old_free = free_head.get();
     if let Some(old_free) = old_free {
-        guard.0.lookup_ptr(old_free).ptrs.prev.set(Some(ptr));
+        deref_slot(old_free).ptrs.prev.set(Some(ptr));
     }
     guard.ptrs.next.set(old_free);
     free_head.set(Some(ptr));
+    register_node(ptr, false);
 
     // "SAFETY": this may cause other nodes to be dropped, so do with care
-    *guard.anchor.borrow_mut() = None;
+    let anchor = guard.anchor.borrow_mut().take();
+    if let Some(anchor) = anchor {
+        graph.recycle_box(anchor);
+    }
 }
 
 pub fn height<'a>(node: NodeGuard<'a>) -> usize {
@@ -724,15 +1477,60 @@ mod test {
             guard.queue_recalc(e2);
             guard.queue_recalc(e3);
 
-            assert_eq!(Some(e3), guard.recalc_pop_next().map(|(_, v)| v));
-            assert_eq!(Some(e2), guard.recalc_pop_next().map(|(_, v)| v));
             assert_eq!(Some(e), guard.recalc_pop_next().map(|(_, v)| v));
+            assert_eq!(Some(e2), guard.recalc_pop_next().map(|(_, v)| v));
+            assert_eq!(Some(e3), guard.recalc_pop_next().map(|(_, v)| v));
             assert_eq!(Some(b), guard.recalc_pop_next().map(|(_, v)| v));
 
             assert_eq!(None, guard.recalc_pop_next().map(|(_, v)| v));
         })
     }
 
+    #[test]
+    fn same_height_nodes_pop_in_queue_order() {
+        let graph = Graph2::new(10);
+        graph.with(|guard| {
+            let a = guard.insert_testing_guard();
+            let b = guard.insert_testing_guard();
+            let c = guard.insert_testing_guard();
+            let d = guard.insert_testing_guard();
+            guard.queue_recalc(a);
+            guard.queue_recalc(b);
+            guard.queue_recalc(c);
+
+            // removing the tail lets later nodes queue behind the new tail
+            dequeue_calc(&graph, c);
+            c.ptrs.recalc_state.set(RecalcState::Needed);
+            guard.queue_recalc(d);
+            guard.queue_recalc(c);
+
+            assert_eq!(Some(a), guard.recalc_pop_next().map(|(_, v)| v));
+            guard.queue_recalc(a);
+            assert_eq!(Some(b), guard.recalc_pop_next().map(|(_, v)| v));
+            assert_eq!(Some(d), guard.recalc_pop_next().map(|(_, v)| v));
+            assert_eq!(Some(c), guard.recalc_pop_next().map(|(_, v)| v));
+            assert_eq!(Some(a), guard.recalc_pop_next().map(|(_, v)| v));
+            assert_eq!(None, guard.recalc_pop_next().map(|(_, v)| v));
+        })
+    }
+
+    #[test]
+    fn queued_nodes_move_when_height_changes() {
+        let graph = Graph2::new(256);
+        graph.with(|guard| {
+            let a = guard.insert_testing_guard();
+            let b = guard.insert_testing_guard();
+            let c = guard.insert_testing_guard();
+            guard.queue_recalc(b);
+            guard.queue_recalc(c);
+            assert_eq!(Ok(false), ensure_height_increases(a, b));
+
+            assert_eq!(Some((0, c)), guard.recalc_pop_next());
+            assert_eq!(Some((1, b)), guard.recalc_pop_next());
+            assert_eq!(None, guard.recalc_pop_next());
+        })
+    }
+
     #[test]
     #[should_panic]
     fn test_insert_above_max_height() {
@@ -765,9 +1563,12 @@ mod test {
         let a = graph.insert_testing();
         let d = graph.insert_testing();
 
-        assert_eq!(a_token, a.token());
-        assert_eq!(b_token, b.token());
-        assert_eq!(c_token, c.token());
+        assert_eq!(a_token.ptr, a.token().ptr);
+        assert_ne!(a_token, a.token());
+        assert_eq!(b_token.ptr, b.token().ptr);
+        assert_ne!(b_token, b.token());
+        assert_eq!(c_token.ptr, c.token().ptr);
+        assert_ne!(c_token, c.token());
         let d_token = d.token();
 
         std::mem::drop(c);
@@ -780,9 +1581,65 @@ mod test {
         let a = graph.insert_testing();
         let c = graph.insert_testing();
 
-        assert_eq!(a_token, a.token());
-        assert_eq!(b_token, b.token());
-        assert_eq!(c_token, c.token());
-        assert_eq!(d_token, d.token());
+        assert_eq!(a_token.ptr, a.token().ptr);
+        assert_ne!(a_token, a.token());
+        assert_eq!(b_token.ptr, b.token().ptr);
+        assert_ne!(b_token, b.token());
+        assert_eq!(c_token.ptr, c.token().ptr);
+        assert_ne!(c_token, c.token());
+        assert_eq!(d_token.ptr, d.token().ptr);
+        assert_ne!(d_token, d.token());
+    }
+
+    #[cfg(feature = "checked")]
+    #[test]
+    #[should_panic(expected = "isn't a node of any live graph")]
+    fn checked_deref_of_dropped_graph_panics() {
+        let ptr = {
+            let graph = Graph2::new(10);
+            let handle = graph.insert_testing();
+            handle.num.ptr
+        };
+        unsafe { deref_ptr(ptr) };
+    }
+
+    #[cfg(feature = "checked")]
+    #[test]
+    #[should_panic(expected = "which has been freed")]
+    fn checked_deref_of_freed_node_panics() {
+        let graph = Graph2::new(10);
+        let handle = graph.insert_testing();
+        let ptr = handle.num.ptr;
+        std::mem::drop(handle);
+        unsafe { deref_ptr(ptr) };
+    }
+
+    #[cfg(feature = "checked")]
+    #[test]
+    fn checked_nodes_are_live_again_once_reused() {
+        let graph = Graph2::new(10);
+        let handle = graph.insert_testing();
+        let ptr = handle.num.ptr;
+        unsafe { deref_ptr(ptr) };
+        std::mem::drop(handle);
+        let reused = graph.insert_testing();
+        assert_eq!(reused.num.ptr, ptr);
+        unsafe { deref_ptr(ptr) };
+    }
+
+    #[test]
+    fn stale_keys_dont_alias_reused_nodes() {
+        use crate::expert::AnchorHandle;
+        let graph = Graph2::new(10);
+        let a = graph.insert_testing();
+        let stale = a.token();
+        std::mem::drop(a);
+        let b = graph.insert_testing();
+        assert_eq!(stale.ptr, b.token().ptr);
+        graph.with(|guard| {
+            assert!(guard.get(stale).is_none());
+            assert_eq!(guard.try_lookup(stale).unwrap_err(), Error::Freed);
+            assert!(guard.get(b.token()).is_some());
+        });
     }
 }
@@ -0,0 +1,127 @@
+//! Per-generation value history for debugging. Requires the `history` feature.
+
+use super::{graph2, Anchor, Engine, EngineContext, NodeKey, RecalcState, StabilizeReport};
+use std::any::Any;
+
+pub(super) type Histories = std::collections::HashMap<NodeKey, History>;
+
+pub(super) struct History {
+    // kept so the anchor isn't freed while its history is being tracked
+    _anchor: Box<dyn Any>,
+    clone_output: fn(&dyn Any) -> Box<dyn Any>,
+    // values in increasing generation order
+    values: Vec<(u64, Box<dyn Any>)>,
+}
+
+fn clone_any<O: Clone + 'static>(val: &dyn Any) -> Box<dyn Any> {
+    Box::new(val.downcast_ref::<O>().unwrap().clone())
+}
+
+impl Engine {
+    /// Marks `anchor` as observed, and records a copy of its value every time it changes for the
+    /// rest of the engine's lifetime. Use `value_at` and `history` to look up recorded values.
+    pub fn track_history<O: Clone + 'static>(&mut self, anchor: &Anchor<O>) {
+        let token = anchor.token();
+        if self.histories.contains_key(&token) {
+            return;
+        }
+        self.mark_observed(anchor);
+        let mut history = History {
+            _anchor: Box::new(anchor.clone()),
+            clone_output: clone_any::<O>,
+            values: Vec::new(),
+        };
+        // record the current value if it's already up-to-date, since it may not change again
+        self.graph.with(|graph| {
+            let node = graph.get(token).unwrap();
+            if graph2::recalc_state(node) == RecalcState::Ready {
+                let borrow = node.anchor.borrow();
+                let val = borrow
+                    .as_ref()
+                    .unwrap()
+                    .output(&mut EngineContext { engine: self });
+                history
+                    .values
+                    .push((self.generation.get(), (history.clone_output)(val)));
+            }
+        });
+        self.histories.insert(token, history);
+    }
+
+    /// Returns the value `anchor` had at the end of stabilization `generation`, or `None` if
+    /// no value was recorded at or before that generation. `anchor` must have been passed to
+    /// `track_history`.
+    pub fn value_at<O: Clone + 'static>(&self, anchor: &Anchor<O>, generation: u64) -> Option<O> {
+        let history = self.histories.get(&anchor.token())?;
+        let i = history
+            .values
+            .partition_point(|(recorded, _)| *recorded <= generation);
+        let (_, val) = history.values[..i].last()?;
+        Some(val.downcast_ref::<O>().unwrap().clone())
+    }
+
+    /// Returns every recorded value of `anchor`, along with the generation it was recorded in.
+    pub fn history<O: Clone + 'static>(&self, anchor: &Anchor<O>) -> Vec<(u64, O)> {
+        match self.histories.get(&anchor.token()) {
+            Some(history) => history
+                .values
+                .iter()
+                .map(|(generation, val)| (*generation, val.downcast_ref::<O>().unwrap().clone()))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub(super) fn record_history(&mut self, report: &StabilizeReport) {
+        if self.histories.is_empty() {
+            return;
+        }
+        let mut histories = std::mem::take(&mut self.histories);
+        self.graph.with(|graph| {
+            for token in &report.observed_updated {
+                let history = match histories.get_mut(token) {
+                    Some(history) => history,
+                    None => continue,
+                };
+                let node = graph.get(*token).unwrap();
+                let borrow = node.anchor.borrow();
+                let val = borrow
+                    .as_ref()
+                    .unwrap()
+                    .output(&mut EngineContext { engine: self });
+                history
+                    .values
+                    .push((report.generation, (history.clone_output)(val)));
+            }
+        });
+        self.histories = histories;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::singlethread::{Engine, Var};
+
+    #[test]
+    fn value_at_generation() {
+        let mut engine = Engine::new();
+        let var = Var::new(1);
+        let doubled = var.watch().map(|v| *v * 2);
+        engine.track_history(&doubled);
+        let first = engine.stabilize().generation;
+        var.set(2);
+        let second = engine.stabilize().generation;
+        engine.stabilize();
+        var.set(3);
+        let fourth = engine.stabilize().generation;
+
+        assert_eq!(engine.value_at(&doubled, first - 1), None);
+        assert_eq!(engine.value_at(&doubled, first), Some(2));
+        assert_eq!(engine.value_at(&doubled, second + 1), Some(4));
+        assert_eq!(engine.value_at(&doubled, fourth), Some(6));
+        assert_eq!(
+            engine.history(&doubled),
+            vec![(first, 2), (second, 4), (fourth, 6)]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Helpers for using Anchors as the state of an Iced application. Requires the `iced` feature.
+//!
+//! Keep an `IcedState` in your application state. In `update`, pass each message to
+//! `IcedState::update`, which sets any `Var`s bound to that message with `bind` and then
+//! stabilizes. In `view`, read Anchors registered with `observe` through `IcedState::get` or
+//! `IcedState::view`; these read the values from the last stabilization, so they only need `&self`.
+//!
+//! ```ignore
+//! struct App {
+//!     state: IcedState<Message>,
+//!     greeting: Anchor<String>,
+//! }
+//!
+//! fn update(app: &mut App, message: Message) {
+//!     app.state.update(&message);
+//! }
+//!
+//! fn view(app: &App) -> Element<Message> {
+//!     app.state.view(&app.greeting, |greeting| text(greeting).into())
+//! }
+//! ```
+
+use super::{Anchor, Engine, Var};
+use iced_core::Element;
+
+/// Application state backed by an Anchors engine, dispatching Iced messages to `Var`s.
+pub struct IcedState<M> {
+    engine: Engine,
+    bindings: Vec<Binding<M>>,
+}
+
+type Binding<M> = Box<dyn FnMut(&M)>;
+
+impl<M> IcedState<M> {
+    pub fn new(engine: Engine) -> Self {
+        Self {
+            engine,
+            bindings: Vec::new(),
+        }
+    }
+
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    pub fn engine_mut(&mut self) -> &mut Engine {
+        &mut self.engine
+    }
+
+    /// Sets `var` whenever `f` maps a message passed to `update` to `Some` value. Useful for
+    /// turning widget events like `TextInput::on_input` into `Var` sets.
+    pub fn bind<T: 'static, F: FnMut(&M) -> Option<T> + 'static>(
+        &mut self,
+        var: &Var<T>,
+        mut f: F,
+    ) {
+        let var = var.clone();
+        self.bindings.push(Box::new(move |message| {
+            if let Some(val) = f(message) {
+                var.set(val);
+            }
+        }));
+    }
+
+    /// Marks `anchor` as observed and stabilizes, so its value is available to `get` and `view`
+    /// and kept up-to-date by `update`.
+    pub fn observe<T: 'static>(&mut self, anchor: &Anchor<T>) {
+        self.engine.mark_observed(anchor);
+        self.engine.stabilize();
+    }
+
+    /// Sets every `Var` bound to `message`, then stabilizes. Returns true if any observed Anchor
+    /// changed, meaning the view should be rebuilt.
+    pub fn update(&mut self, message: &M) -> bool {
+        for binding in &mut self.bindings {
+            binding(message);
+        }
+        !self.engine.stabilize().observed_updated.is_empty()
+    }
+
+    /// Returns `anchor`'s value as of the last stabilization.
+    ///
+    /// Panics if `anchor` hasn't been calculated yet; pass it to `observe` first.
+    pub fn get<T: Clone + 'static>(&self, anchor: &Anchor<T>) -> T {
+        self.engine
+            .peek(anchor)
+            .expect("anchor hasn't been calculated yet; pass it to `IcedState::observe` first")
+    }
+
+    /// Builds an `Element` from `anchor`'s value as of the last stabilization.
+    ///
+    /// Panics if `anchor` hasn't been calculated yet; pass it to `observe` first.
+    pub fn view<'a, T: Clone + 'static, Theme, Renderer, F>(
+        &self,
+        anchor: &Anchor<T>,
+        f: F,
+    ) -> Element<'a, M, Theme, Renderer>
+    where
+        F: FnOnce(T) -> Element<'a, M, Theme, Renderer>,
+    {
+        f(self.get(anchor))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    enum Message {
+        NameChanged(String),
+        Other,
+    }
+
+    #[test]
+    fn update_sets_bound_vars() {
+        let mut state = IcedState::new(Engine::new());
+        let name = Var::new("world".to_string());
+        state.bind(&name, |message| match message {
+            Message::NameChanged(name) => Some(name.clone()),
+            Message::Other => None,
+        });
+        let greeting = name.watch().map(|name| format!("hello, {}", name));
+        state.observe(&greeting);
+        assert_eq!(state.get(&greeting), "hello, world");
+
+        assert!(!state.update(&Message::Other));
+        assert_eq!(state.get(&greeting), "hello, world");
+
+        assert!(state.update(&Message::NameChanged("iced".to_string())));
+        assert_eq!(state.get(&greeting), "hello, iced");
+    }
+
+    #[test]
+    #[should_panic(expected = "hasn't been calculated yet")]
+    fn get_before_observe_panics() {
+        let state = IcedState::<Message>::new(Engine::new());
+        let anchor = Var::new(1).watch();
+        state.get(&anchor);
+    }
+}
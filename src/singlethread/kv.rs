@@ -0,0 +1,285 @@
+//! Anchors backed by keys in a key-value store, for using Anchors as a live cache over persistent
+//! storage. Any store implementing `WatchStore` can be used; with the `sled` feature, `SledStore`
+//! wraps a sled `Tree` or `Db` to implement it.
+
+use super::{Anchor, DirtyHandle, Engine};
+use crate::expert::{AnchorHandle, AnchorInner, OutputContext, Poll, UpdateContext};
+use std::panic::Location;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A key-value store that can report writes to a key, including writes made outside the engine.
+pub trait WatchStore: 'static {
+    type Value: PartialEq + 'static;
+    type Error: 'static;
+    /// Keeps a key watched until dropped.
+    type Watch: 'static;
+
+    fn get(&self, key: &[u8]) -> Result<Option<Self::Value>, Self::Error>;
+
+    /// Starts watching `key`, calling `on_change` from any thread after each write to it.
+    /// Spurious calls are fine; the key is reread and compared with its previous value.
+    fn watch(&self, key: &[u8], on_change: Box<dyn Fn() + Send + Sync>) -> Self::Watch;
+}
+
+/// Creates an Anchor containing the value of `key` in `store`, or `None` if it isn't set. The
+/// key is watched from the first time the Anchor is polled until it's dropped, and reread on the
+/// next stabilization after each write.
+#[track_caller]
+pub fn watch_key<S: WatchStore, K: Into<Vec<u8>>>(
+    store: S,
+    key: K,
+) -> Anchor<Result<Option<S::Value>, S::Error>> {
+    <Engine as crate::expert::Engine>::mount(KeyWatch {
+        store,
+        key: key.into(),
+        watch: None,
+        changed: Arc::new(AtomicBool::new(true)),
+        output: None,
+        location: Location::caller(),
+    })
+}
+
+struct KeyWatch<S: WatchStore> {
+    store: S,
+    key: Vec<u8>,
+    watch: Option<S::Watch>,
+    /// set by the store whenever `key` is written
+    changed: Arc<AtomicBool>,
+    output: Option<Result<Option<S::Value>, S::Error>>,
+    location: &'static Location<'static>,
+}
+
+impl<S: WatchStore> AnchorInner<Engine> for KeyWatch<S> {
+    type Output = Result<Option<S::Value>, S::Error>;
+
+    fn dirty(&mut self, _child: &<super::AnchorHandle as AnchorHandle>::Token) {
+        panic!("somehow an input was dirtied on KeyWatch; it never has any inputs to dirty")
+    }
+
+    fn poll_updated<G: UpdateContext<Engine = Engine>>(&mut self, ctx: &mut G) -> Poll {
+        if self.watch.is_none() {
+            use crate::expert::DirtyHandle as _;
+            let dirty_handle: DirtyHandle = ctx.dirty_handle();
+            let dirty_handle = Mutex::new(dirty_handle.to_send());
+            let changed = self.changed.clone();
+            // start watching before the first read, so no writes are missed in between
+            self.watch = Some(self.store.watch(
+                &self.key,
+                Box::new(move || {
+                    changed.store(true, Ordering::SeqCst);
+                    dirty_handle.lock().unwrap().mark_dirty();
+                }),
+            ));
+        }
+        if !self.changed.swap(false, Ordering::SeqCst) {
+            return Poll::Unchanged;
+        }
+        let new_val = self.store.get(&self.key);
+        match (&self.output, &new_val) {
+            (Some(Ok(old)), Ok(new)) if old == new => Poll::Unchanged,
+            _ => {
+                self.output = Some(new_val);
+                Poll::Updated
+            }
+        }
+    }
+
+    fn output<'slf, 'out, G: OutputContext<'out, Engine = Engine>>(
+        &'slf self,
+        _ctx: &mut G,
+    ) -> &'out Self::Output
+    where
+        'slf: 'out,
+    {
+        self.output
+            .as_ref()
+            .expect("output called on KeyWatch before value was calculated")
+    }
+
+    fn debug_location(&self) -> Option<(&'static str, &'static Location<'static>)> {
+        Some(("watch_key", self.location))
+    }
+}
+
+#[cfg(feature = "sled")]
+mod sled_store {
+    use super::WatchStore;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex, Weak};
+    use std::time::Duration;
+
+    type Callback = Box<dyn Fn() + Send + Sync>;
+
+    #[derive(Default)]
+    struct Watchers {
+        next_id: u64,
+        /// whether the subscriber thread has been started
+        started: bool,
+        by_key: HashMap<Vec<u8>, Vec<(u64, Callback)>>,
+    }
+
+    /// A sled `Tree` (or `Db`) usable as a `WatchStore`. All keys watched through one `SledStore`
+    /// and its clones share a single subscriber thread, which dispatches each write to the
+    /// watchers of the written key, and exits once every clone is dropped.
+    #[derive(Clone)]
+    pub struct SledStore {
+        tree: sled::Tree,
+        watchers: Arc<Mutex<Watchers>>,
+    }
+
+    impl SledStore {
+        pub fn new(tree: &sled::Tree) -> Self {
+            Self {
+                tree: tree.clone(),
+                watchers: Default::default(),
+            }
+        }
+
+        pub fn tree(&self) -> &sled::Tree {
+            &self.tree
+        }
+
+        fn start_subscriber(&self) {
+            let mut subscriber = self.tree.watch_prefix(vec![]);
+            let watchers = Arc::downgrade(&self.watchers);
+            // subscribers can only be waited on, so check whether the store is gone between events
+            std::thread::spawn(move || loop {
+                let event = match subscriber.next_timeout(Duration::from_millis(100)) {
+                    Ok(event) => Some(event),
+                    Err(std::sync::mpsc::RecvTimeoutError::Timeout) => None,
+                    Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+                };
+                let watchers = match watchers.upgrade() {
+                    Some(watchers) => watchers,
+                    None => break,
+                };
+                if let Some(event) = event {
+                    let watchers = watchers.lock().unwrap();
+                    if let Some(callbacks) = watchers.by_key.get(event.key().as_ref()) {
+                        for (_, on_change) in callbacks {
+                            on_change();
+                        }
+                    }
+                }
+            });
+        }
+    }
+
+    /// Removes a key's watcher from its `SledStore` when dropped.
+    pub struct SledWatch {
+        watchers: Weak<Mutex<Watchers>>,
+        key: Vec<u8>,
+        id: u64,
+    }
+
+    impl Drop for SledWatch {
+        fn drop(&mut self) {
+            let watchers = match self.watchers.upgrade() {
+                Some(watchers) => watchers,
+                None => return,
+            };
+            let mut watchers = watchers.lock().unwrap();
+            if let Some(callbacks) = watchers.by_key.get_mut(&self.key) {
+                callbacks.retain(|(id, _)| *id != self.id);
+                if callbacks.is_empty() {
+                    watchers.by_key.remove(&self.key);
+                }
+            }
+        }
+    }
+
+    impl WatchStore for SledStore {
+        type Value = sled::IVec;
+        type Error = sled::Error;
+        type Watch = SledWatch;
+
+        fn get(&self, key: &[u8]) -> sled::Result<Option<sled::IVec>> {
+            self.tree.get(key)
+        }
+
+        fn watch(&self, key: &[u8], on_change: Callback) -> SledWatch {
+            let mut watchers = self.watchers.lock().unwrap();
+            if !watchers.started {
+                watchers.started = true;
+                self.start_subscriber();
+            }
+            let id = watchers.next_id;
+            watchers.next_id += 1;
+            watchers
+                .by_key
+                .entry(key.to_vec())
+                .or_default()
+                .push((id, on_change));
+            SledWatch {
+                watchers: Arc::downgrade(&self.watchers),
+                key: key.to_vec(),
+                id,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sled")]
+pub use sled_store::{SledStore, SledWatch};
+
+#[cfg(all(test, feature = "sled"))]
+mod test {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn watch_key_rereads_on_writes() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        db.insert("greeting", "hi").unwrap();
+        let store = SledStore::new(&db);
+
+        let mut engine = Engine::new();
+        let greeting = watch_key(store.clone(), "greeting")
+            .map(|res| res.as_ref().unwrap().as_ref().map(|val| val.to_vec()));
+        engine.mark_observed(&greeting);
+        assert_eq!(engine.get(&greeting), Some(b"hi".to_vec()));
+
+        // watchers of a key are called in the order they were added, so once this one is called,
+        // the Anchor has been marked dirty
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let _watch = store.watch(
+            b"greeting",
+            Box::new(move || sender.lock().unwrap().send(()).unwrap()),
+        );
+
+        db.insert("unrelated", "value").unwrap();
+        db.insert("greeting", "hello").unwrap();
+        receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(engine.get(&greeting), Some(b"hello".to_vec()));
+
+        db.remove("greeting").unwrap();
+        receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(engine.get(&greeting), None);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn dropped_watches_are_removed() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let store = SledStore::new(&db);
+        let watch = |sender: mpsc::Sender<()>| {
+            let sender = Mutex::new(sender);
+            store.watch(
+                b"key",
+                Box::new(move || sender.lock().unwrap().send(()).unwrap()),
+            )
+        };
+        let (dropped_sender, dropped_receiver) = mpsc::channel();
+        let (kept_sender, kept_receiver) = mpsc::channel();
+        let dropped = watch(dropped_sender);
+        let _kept = watch(kept_sender);
+        std::mem::drop(dropped);
+
+        db.insert("key", "value").unwrap();
+        kept_receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(dropped_receiver.try_recv().is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! Memoization that persists across engine restarts. Requires the `snapshot` feature.
+
+use super::Anchor;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+/// A cache of Anchor outputs, keyed by a user-provided identity and a hash of the Anchor's
+/// input. The cache can be saved with `snapshot` and restored with `from_snapshot`, so that a
+/// rebuilt graph can skip recalculating expensive Anchors whose inputs haven't changed.
+///
+/// Input hashes use the standard library's `DefaultHasher`, which is only guaranteed to be
+/// stable between runs of the same build.
+#[derive(Clone, Default)]
+pub struct PersistentMemo {
+    entries: Rc<RefCell<BTreeMap<String, MemoEntry>>>,
+}
+
+/// The saved contents of a `PersistentMemo`. Serialize this with any serde format to persist it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MemoSnapshot {
+    entries: BTreeMap<String, MemoEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct MemoEntry {
+    input_hash: u64,
+    output: serde_json::Value,
+}
+
+impl PersistentMemo {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a cache containing the entries saved by `snapshot`.
+    pub fn from_snapshot(snapshot: MemoSnapshot) -> Self {
+        Self {
+            entries: Rc::new(RefCell::new(snapshot.entries)),
+        }
+    }
+
+    /// Saves every entry in the cache.
+    pub fn snapshot(&self) -> MemoSnapshot {
+        MemoSnapshot {
+            entries: self.entries.borrow().clone(),
+        }
+    }
+
+    /// Like `Anchor::map`, but the output is cached under `key`. If the cache already contains an
+    /// output for `key` computed from an input with the same hash, that output is used instead of
+    /// calling `f`. Each memoized Anchor should use a different key.
+    ///
+    /// Unlike `Anchor::map`, the output doesn't need to implement `PartialEq`, so Anchors reading
+    /// it are recalculated whenever the input changes, even if the output is the same.
+    #[track_caller]
+    pub fn map<I, O, F>(&self, input: &Anchor<I>, key: &str, mut f: F) -> Anchor<O>
+    where
+        I: Hash + 'static,
+        O: Serialize + DeserializeOwned + 'static,
+        F: FnMut(&I) -> O + 'static,
+    {
+        let entries = self.entries.clone();
+        let key = key.to_string();
+        let memoized = input.map(move |input: &I| {
+            let mut hasher = DefaultHasher::new();
+            input.hash(&mut hasher);
+            let input_hash = hasher.finish();
+            let cached = entries
+                .borrow()
+                .get(&key)
+                .filter(|entry| entry.input_hash == input_hash)
+                .and_then(|entry| serde_json::from_value(entry.output.clone()).ok());
+            if let Some(output) = cached {
+                return Memoized(output);
+            }
+            let output = f(input);
+            if let Ok(json) = serde_json::to_value(&output) {
+                entries.borrow_mut().insert(
+                    key.clone(),
+                    MemoEntry {
+                        input_hash,
+                        output: json,
+                    },
+                );
+            }
+            Memoized(output)
+        });
+        memoized.refmap(|memoized: &Memoized<O>| &memoized.0)
+    }
+}
+
+/// An output of `PersistentMemo::map`, which is never equal to another, since `O` may not
+/// implement `PartialEq`.
+struct Memoized<O>(O);
+
+impl<O> PartialEq for Memoized<O> {
+    fn eq(&self, _other: &Self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::singlethread::{Engine, Var};
+    use std::cell::Cell;
+
+    #[test]
+    fn reuses_outputs_after_restart() {
+        let calls = Rc::new(Cell::new(0));
+        let expensive = {
+            let calls = calls.clone();
+            move |input: &String| {
+                calls.set(calls.get() + 1);
+                input.len()
+            }
+        };
+
+        let saved = {
+            let mut engine = Engine::new();
+            let memo = PersistentMemo::new();
+            let input = Var::new("abc".to_string());
+            let len = memo.map(&input.watch(), "len", expensive.clone());
+            assert_eq!(engine.get(&len), 3);
+            serde_json::to_string(&memo.snapshot()).unwrap()
+        };
+        assert_eq!(calls.get(), 1);
+
+        let mut engine = Engine::new();
+        let memo = PersistentMemo::from_snapshot(serde_json::from_str(&saved).unwrap());
+        let input = Var::new("abc".to_string());
+        let len = memo.map(&input.watch(), "len", expensive);
+        assert_eq!(engine.get(&len), 3);
+        assert_eq!(calls.get(), 1);
+
+        input.set("abcd".to_string());
+        assert_eq!(engine.get(&len), 4);
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn outputs_need_not_be_comparable() {
+        #[derive(Clone, Serialize, Deserialize)]
+        struct Unordered(f64);
+
+        let mut engine = Engine::new();
+        let memo = PersistentMemo::new();
+        let input = Var::new(2u32);
+        let half = memo.map(&input.watch(), "half", |input| {
+            Unordered(*input as f64 / 2.0)
+        });
+        assert_eq!(engine.get(&half).0, 1.0);
+        input.set(3);
+        assert_eq!(engine.get(&half).0, 1.5);
+    }
+}
@@ -0,0 +1,154 @@
+//! Memoized functions returning Anchors, for recursive incremental algorithms.
+
+use super::{Anchor, Engine};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::rc::Rc;
+
+type MemoBody<A, B> = Box<dyn Fn(&MemoFn<A, B>, A) -> Anchor<B>>;
+
+/// A function from arguments to Anchors, created with `Engine::memoize`. Calling it with an
+/// argument equal to a previous call's returns the same Anchor, so the subgraph behind it is only
+/// built once and keeps its cached output. Cloning it shares the cache.
+pub struct MemoFn<A, B> {
+    inner: Rc<MemoInner<A, B>>,
+}
+
+struct MemoInner<A, B> {
+    f: MemoBody<A, B>,
+    cache: RefCell<MemoCache<A, B>>,
+}
+
+struct MemoCache<A, B> {
+    /// each cached Anchor, with the tick it was last used
+    entries: HashMap<A, (Anchor<B>, u64)>,
+    /// arguments by the tick they were last used, least recent first
+    recency: BTreeMap<u64, A>,
+    tick: u64,
+    capacity: Option<usize>,
+}
+
+impl<A, B> Clone for MemoFn<A, B> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl Engine {
+    /// Creates a memoized function from `f`, which builds the Anchor for an argument. `f` is
+    /// passed the `MemoFn` itself, so it can call it recursively, and calls with an argument seen
+    /// before reuse the Anchor built for it. By default every Anchor is cached; use `set_capacity`
+    /// to evict the least recently used ones instead.
+    ///
+    /// ```
+    /// use anchors::singlethread::*;
+    /// let mut engine = Engine::new();
+    /// let fib = engine.memoize(|fib, n: u64| {
+    ///     if n < 2 {
+    ///         Anchor::constant(n)
+    ///     } else {
+    ///         (&fib.call(n - 1), &fib.call(n - 2)).map(|a, b| a + b)
+    ///     }
+    /// });
+    /// // only 91 Anchors are built, rather than one per call of a naive recursion
+    /// assert_eq!(engine.get(&fib.call(90)), 2880067194370816120);
+    /// assert_eq!(fib.len(), 91);
+    /// ```
+    ///
+    /// Cached Anchors are kept alive by the `MemoFn`, so Anchors built by `f` shouldn't capture
+    /// the `MemoFn` in their closures, or neither will ever be freed. Call it from `f` directly,
+    /// as above, instead.
+    pub fn memoize<A, B, F>(&self, f: F) -> MemoFn<A, B>
+    where
+        A: Hash + Eq + Clone + 'static,
+        B: 'static,
+        F: Fn(&MemoFn<A, B>, A) -> Anchor<B> + 'static,
+    {
+        MemoFn {
+            inner: Rc::new(MemoInner {
+                f: Box::new(f),
+                cache: RefCell::new(MemoCache {
+                    entries: HashMap::new(),
+                    recency: BTreeMap::new(),
+                    tick: 0,
+                    capacity: None,
+                }),
+            }),
+        }
+    }
+}
+
+impl<A: Hash + Eq + Clone + 'static, B: 'static> MemoFn<A, B> {
+    /// Returns the Anchor for `arg`, building it if it isn't cached.
+    pub fn call(&self, arg: A) -> Anchor<B> {
+        if let Some(anchor) = self.inner.cache.borrow_mut().touch(&arg) {
+            return anchor;
+        }
+        // the cache isn't borrowed while building, since `f` may call this recursively
+        let anchor = (self.inner.f)(self, arg.clone());
+        let mut cache = self.inner.cache.borrow_mut();
+        // a recursive call with the same argument may have cached it already
+        if let Some(anchor) = cache.touch(&arg) {
+            return anchor;
+        }
+        cache.insert(arg, anchor.clone());
+        anchor
+    }
+
+    /// Limits the cache to `capacity` Anchors, evicting the least recently used ones beyond that.
+    /// `None` removes the limit.
+    pub fn set_capacity(&self, capacity: Option<usize>) {
+        let mut cache = self.inner.cache.borrow_mut();
+        cache.capacity = capacity;
+        cache.evict();
+    }
+
+    /// Returns the number of cached Anchors.
+    pub fn len(&self) -> usize {
+        self.inner.cache.borrow().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every cached Anchor, so later calls build them again.
+    pub fn clear(&self) {
+        let mut cache = self.inner.cache.borrow_mut();
+        cache.entries.clear();
+        cache.recency.clear();
+    }
+}
+
+impl<A: Hash + Eq + Clone, B> MemoCache<A, B> {
+    /// returns the cached Anchor for `arg`, marking it as the most recently used
+    fn touch(&mut self, arg: &A) -> Option<Anchor<B>> {
+        self.tick += 1;
+        let (anchor, last_used) = self.entries.get_mut(arg)?;
+        let arg = self.recency.remove(last_used).unwrap();
+        *last_used = self.tick;
+        self.recency.insert(self.tick, arg);
+        Some(anchor.clone())
+    }
+
+    fn insert(&mut self, arg: A, anchor: Anchor<B>) {
+        self.tick += 1;
+        self.recency.insert(self.tick, arg.clone());
+        self.entries.insert(arg, (anchor, self.tick));
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        let capacity = match self.capacity {
+            Some(capacity) => capacity,
+            None => return,
+        };
+        while self.entries.len() > capacity {
+            let (_, arg) = self.recency.pop_first().unwrap();
+            self.entries.remove(&arg);
+        }
+    }
+}
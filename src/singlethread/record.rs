@@ -0,0 +1,239 @@
+use super::{Engine, StabilizeReport, Var};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A single recorded change to a Var, as logged by a `Recorder`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecordedSet {
+    /// The generation of the stabilization that first saw this value.
+    pub generation: u64,
+
+    /// The name the Var was registered with.
+    pub var: String,
+
+    /// The encoded value.
+    pub value: String,
+}
+
+/// Logs changes to a set of registered Vars, along with the generation of the stabilization
+/// that saw each change. The log can be fed to a `Replayer` to reproduce the same sequence of
+/// changes in a fresh engine.
+///
+/// Vars are checked at the start of each stabilization, so a Var set several times between
+/// stabilizations is only recorded once, with the value the stabilization saw.
+pub struct Recorder {
+    state: Rc<RefCell<RecorderState>>,
+}
+
+struct RecorderState {
+    vars: Vec<Box<dyn RecordVar>>,
+    entries: Vec<RecordedSet>,
+    next_generation: u64,
+}
+
+impl Recorder {
+    /// Creates a Recorder that records changes seen by `engine`'s stabilizations.
+    pub fn new(engine: &mut Engine) -> Self {
+        let state = Rc::new(RefCell::new(RecorderState {
+            vars: Vec::new(),
+            entries: Vec::new(),
+            next_generation: engine.generation() + 1,
+        }));
+        {
+            let state = state.clone();
+            engine.add_pre_stabilize_hook(move || state.borrow_mut().record_changes());
+        }
+        {
+            let state = state.clone();
+            engine.add_post_stabilize_hook(move |report| {
+                state.borrow_mut().next_generation = report.generation + 1;
+            });
+        }
+        Self { state }
+    }
+
+    /// Starts recording changes to `var`, encoding its values with `encode`. The current value is
+    /// recorded immediately. `name` is used to find the matching Var when replaying.
+    pub fn register<T: 'static, F: Fn(&T) -> String + 'static>(
+        &self,
+        name: &str,
+        var: &Var<T>,
+        encode: F,
+    ) {
+        let mut state = self.state.borrow_mut();
+        let last = var.get();
+        let entry = RecordedSet {
+            generation: state.next_generation,
+            var: name.to_string(),
+            value: encode(&last),
+        };
+        state.entries.push(entry);
+        state.vars.push(Box::new(TrackedVar {
+            name: name.to_string(),
+            var: var.clone(),
+            last,
+            encode,
+        }));
+    }
+
+    /// Like `register`, but encodes values as JSON. Requires the `serde_json` feature.
+    #[cfg(feature = "serde_json")]
+    pub fn register_json<T: serde::Serialize + 'static>(&self, name: &str, var: &Var<T>) {
+        self.register(name, var, |val| {
+            serde_json::to_string(val).expect("failed to encode recorded value")
+        })
+    }
+
+    /// Returns every change recorded so far.
+    pub fn entries(&self) -> Vec<RecordedSet> {
+        self.state.borrow().entries.clone()
+    }
+}
+
+impl RecorderState {
+    fn record_changes(&mut self) {
+        for var in &mut self.vars {
+            if let Some((name, value)) = var.changed_value() {
+                self.entries.push(RecordedSet {
+                    generation: self.next_generation,
+                    var: name,
+                    value,
+                });
+            }
+        }
+    }
+}
+
+trait RecordVar {
+    /// returns the var's name and encoded value if it's changed since the last call
+    fn changed_value(&mut self) -> Option<(String, String)>;
+}
+
+struct TrackedVar<T, F> {
+    name: String,
+    var: Var<T>,
+    last: Rc<T>,
+    encode: F,
+}
+
+impl<T: 'static, F: Fn(&T) -> String> RecordVar for TrackedVar<T, F> {
+    fn changed_value(&mut self) -> Option<(String, String)> {
+        let current = self.var.get();
+        if Rc::ptr_eq(&current, &self.last) {
+            return None;
+        }
+        self.last = current;
+        Some((self.name.clone(), (self.encode)(&self.last)))
+    }
+}
+
+type ApplyFn = Box<dyn Fn(&str)>;
+
+/// Feeds changes logged by a `Recorder` back into Vars, one recorded generation at a time.
+pub struct Replayer {
+    entries: std::collections::VecDeque<RecordedSet>,
+    vars: Vec<(String, ApplyFn)>,
+}
+
+impl Replayer {
+    /// Creates a Replayer for a log previously returned by `Recorder::entries`.
+    pub fn new(entries: Vec<RecordedSet>) -> Self {
+        Self {
+            entries: entries.into(),
+            vars: Vec::new(),
+        }
+    }
+
+    /// Registers the Var that should receive changes recorded under `name`, decoding values with
+    /// `decode`.
+    pub fn register<T: 'static, F: Fn(&str) -> T + 'static>(
+        &mut self,
+        name: &str,
+        var: &Var<T>,
+        decode: F,
+    ) {
+        let var = var.clone();
+        self.vars
+            .push((name.to_string(), Box::new(move |val| var.set(decode(val)))));
+    }
+
+    /// Like `register`, but decodes values as JSON. Requires the `serde_json` feature.
+    #[cfg(feature = "serde_json")]
+    pub fn register_json<T: serde::de::DeserializeOwned + 'static>(
+        &mut self,
+        name: &str,
+        var: &Var<T>,
+    ) {
+        self.register(name, var, |val| {
+            serde_json::from_str(val).expect("failed to decode recorded value")
+        })
+    }
+
+    /// Applies every change recorded for the next generation, then stabilizes. Returns `None` if
+    /// the log has been fully replayed.
+    ///
+    /// Panics if a change was recorded for a Var that hasn't been registered.
+    pub fn replay_next(&mut self, engine: &mut Engine) -> Option<StabilizeReport> {
+        let generation = self.entries.front()?.generation;
+        while let Some(entry) = self.entries.front() {
+            if entry.generation != generation {
+                break;
+            }
+            let entry = self.entries.pop_front().unwrap();
+            let apply = self
+                .vars
+                .iter()
+                .find(|(name, _)| *name == entry.var)
+                .map(|(_, apply)| apply)
+                .unwrap_or_else(|| panic!("no var registered for recorded name {:?}", entry.var));
+            apply(&entry.value);
+        }
+        Some(engine.stabilize())
+    }
+
+    /// Replays the entire log, stabilizing once per recorded generation.
+    pub fn replay_all(&mut self, engine: &mut Engine) {
+        while self.replay_next(engine).is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn record_and_replay() {
+        let entries = {
+            let mut engine = Engine::new();
+            let recorder = Recorder::new(&mut engine);
+            let var = Var::new(1);
+            recorder.register("var", &var, |v| v.to_string());
+            engine.stabilize();
+            var.set(2);
+            var.set(3);
+            engine.stabilize();
+            engine.stabilize();
+            var.set(4);
+            engine.stabilize();
+            recorder.entries()
+        };
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.generation, entry.value.as_str()))
+            .collect();
+        assert_eq!(summary, vec![(2, "1"), (3, "3"), (5, "4")]);
+
+        let mut engine = Engine::new();
+        let var = Var::new(0);
+        let seen = Rc::new(RefCell::new(vec![]));
+        {
+            let seen = seen.clone();
+            engine.subscribe(&var.watch(), move |v| seen.borrow_mut().push(*v));
+        }
+        let mut replayer = Replayer::new(entries);
+        replayer.register("var", &var, |v| v.parse().unwrap());
+        replayer.replay_all(&mut engine);
+        assert_eq!(*seen.borrow(), vec![1, 3, 4]);
+    }
+}
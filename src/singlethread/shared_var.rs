@@ -0,0 +1,189 @@
+use super::{Anchor, DirtyHandle, Engine, SendDirtyHandle, Subscription};
+use crate::expert::{AnchorInner, DirtyHandle as _, OutputContext, Poll, UpdateContext};
+use std::panic::Location;
+use std::sync::{Arc, Mutex};
+
+/// An Anchor input like `Var`, but whose value can be set from any thread by a
+/// `SharedVarSetter`. Useful for publishing results from background workers.
+pub struct SharedVar<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+    anchor: Anchor<T>,
+}
+
+/// A `Send + Sync` setter for a `SharedVar`, created by `SharedVar::setter`.
+pub struct SharedVarSetter<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+struct Shared<T> {
+    dirty_handle: Option<SendDirtyHandle>,
+    /// a value set since the last time the anchor was polled
+    pending: Option<T>,
+}
+
+impl<T> Clone for SharedVar<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            anchor: self.anchor.clone(),
+        }
+    }
+}
+
+impl<T> Clone for SharedVarSetter<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: 'static> SharedVar<T> {
+    /// Creates a new SharedVar. Like other Anchors, this must be called on the engine's thread.
+    #[track_caller]
+    pub fn new(val: T) -> Self {
+        let shared = Arc::new(Mutex::new(Shared {
+            dirty_handle: None,
+            pending: None,
+        }));
+        let anchor = <Engine as crate::expert::Engine>::mount(SharedVarAnchor {
+            shared: shared.clone(),
+            val,
+            location: Location::caller(),
+        });
+        Self { shared, anchor }
+    }
+
+    /// Returns a setter for this SharedVar, which may be sent to other threads.
+    pub fn setter(&self) -> SharedVarSetter<T> {
+        SharedVarSetter {
+            shared: self.shared.clone(),
+        }
+    }
+
+    pub fn watch(&self) -> Anchor<T> {
+        self.anchor.clone()
+    }
+}
+
+impl<T> SharedVarSetter<T> {
+    /// Updates the value inside the SharedVar, and indicates to the recomputation graph that the
+    /// value has changed. The new value is seen by the next stabilization.
+    pub fn set(&self, val: T) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.pending = Some(val);
+        if let Some(dirty_handle) = &shared.dirty_handle {
+            dirty_handle.mark_dirty();
+        }
+    }
+}
+
+impl<T: Clone + 'static> SharedVarSetter<T> {
+    /// Subscribes to `anchor` in `engine`, setting the SharedVar to a clone of `anchor`'s value
+    /// whenever it changes. `engine` may run on a different thread than the SharedVar's engine,
+    /// letting a background engine feed another.
+    pub fn mirror(self, engine: &mut Engine, anchor: &Anchor<T>) -> Subscription {
+        engine.subscribe(anchor, move |val: &T| self.set(val.clone()))
+    }
+}
+
+/// Creates an Anchor in the current Engine that mirrors an Anchor in some other Engine, possibly
+/// on another thread. Send the returned setter to the other Engine's thread and call
+/// `SharedVarSetter::mirror` there.
+#[track_caller]
+pub fn bridge<T: 'static>(initial: T) -> (SharedVarSetter<T>, Anchor<T>) {
+    let var = SharedVar::new(initial);
+    (var.setter(), var.watch())
+}
+
+struct SharedVarAnchor<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+    val: T,
+    location: &'static Location<'static>,
+}
+
+impl<T: 'static> AnchorInner<Engine> for SharedVarAnchor<T> {
+    type Output = T;
+
+    fn dirty(&mut self, _child: &<super::AnchorHandle as crate::expert::AnchorHandle>::Token) {
+        panic!("somehow an input was dirtied on SharedVarAnchor; it never has any inputs to dirty")
+    }
+
+    fn poll_updated<G: UpdateContext<Engine = Engine>>(&mut self, ctx: &mut G) -> Poll {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.dirty_handle.is_none() {
+            let dirty_handle: DirtyHandle = ctx.dirty_handle();
+            shared.dirty_handle = Some(dirty_handle.to_send());
+            if let Some(val) = shared.pending.take() {
+                self.val = val;
+            }
+            return Poll::Updated;
+        }
+        match shared.pending.take() {
+            Some(val) => {
+                self.val = val;
+                Poll::Updated
+            }
+            None => Poll::Unchanged,
+        }
+    }
+
+    fn output<'slf, 'out, G: OutputContext<'out, Engine = Engine>>(
+        &'slf self,
+        _ctx: &mut G,
+    ) -> &'out Self::Output
+    where
+        'slf: 'out,
+    {
+        &self.val
+    }
+
+    fn debug_location(&self) -> Option<(&'static str, &'static Location<'static>)> {
+        Some(("shared_var", self.location))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::singlethread::Var;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn set_from_other_thread() {
+        assert_send_sync::<SharedVarSetter<String>>();
+
+        let mut engine = Engine::new();
+        let var = SharedVar::new(1);
+        let doubled = var.watch().map(|v| *v * 2);
+        engine.mark_observed(&doubled);
+        assert_eq!(engine.get(&doubled), 2);
+
+        let setter = var.setter();
+        std::thread::spawn(move || setter.set(5)).join().unwrap();
+        assert!(engine.needs_stabilize());
+        assert_eq!(engine.get(&doubled), 10);
+    }
+
+    #[test]
+    fn bridge_between_threads() {
+        let mut engine = Engine::new();
+        let (setter, mirrored) = bridge(0);
+        engine.mark_observed(&mirrored);
+        assert_eq!(engine.get(&mirrored), 0);
+
+        std::thread::spawn(move || {
+            let mut worker = Engine::new();
+            let input = Var::new(2);
+            let squared = input.watch().map(|v| *v * *v);
+            setter.mirror(&mut worker, &squared);
+            worker.stabilize();
+            input.set(3);
+            worker.stabilize();
+        })
+        .join()
+        .unwrap();
+        assert_eq!(engine.get(&mirrored), 9);
+    }
+}
@@ -0,0 +1,210 @@
+//! Conversions between Anchors and `futures-signals`. Requires the `futures-signals` feature.
+//!
+//! Signals become Anchors that are marked dirty whenever the signal wakes its task, so they're
+//! repolled on the next stabilization. Anchors become `Mutable`s that are updated at the end of
+//! each stabilization in which the Anchor's value changed.
+
+use super::{Anchor, DirtyHandle, Engine, SendDirtyHandle, Subscription};
+use crate::expert::{AnchorInner, OutputContext, Poll, UpdateContext};
+use futures_signals::signal::{Mutable, MutableSignalCloned, Signal};
+use std::panic::Location;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{self, Context, Wake, Waker};
+
+/// Creates an Anchor following `signal`'s latest value. The signal is polled the first time the
+/// Anchor is, and again on the stabilization after each time it wakes its task. Once the signal
+/// ends, the Anchor keeps its last value.
+///
+/// Panics when first polled if the signal doesn't produce its initial value right away, which
+/// every signal built from a `Mutable` does.
+#[track_caller]
+pub fn from_signal<S: Signal + 'static>(signal: S) -> Anchor<S::Item>
+where
+    S::Item: 'static,
+{
+    <Engine as crate::expert::Engine>::mount(SignalAnchor {
+        signal: Box::pin(signal),
+        waker: None,
+        ended: false,
+        output: None,
+        location: Location::caller(),
+    })
+}
+
+/// Creates an Anchor following `mutable`'s value.
+#[track_caller]
+pub fn from_mutable<A: Clone + 'static>(mutable: &Mutable<A>) -> Anchor<A> {
+    from_signal(mutable.signal_cloned())
+}
+
+/// Returns a `Mutable` that's set to `anchor`'s value at the end of every stabilization in which
+/// it changed. `anchor` is stabilized first to get the initial value, and is kept observed until
+/// the returned `Subscription` is passed to `Engine::unsubscribe`.
+///
+/// The `Mutable` is set once more with the same value on the next stabilization.
+pub fn to_mutable<O: Clone + 'static>(
+    engine: &mut Engine,
+    anchor: &Anchor<O>,
+) -> (Mutable<O>, Subscription) {
+    let mutable = Mutable::new(engine.get(anchor));
+    let subscription = engine.subscribe(anchor, {
+        let mutable = mutable.clone();
+        move |val: &O| mutable.set(val.clone())
+    });
+    (mutable, subscription)
+}
+
+/// Like `to_mutable`, but returns a signal of `anchor`'s value.
+pub fn to_signal<O: Clone + 'static>(
+    engine: &mut Engine,
+    anchor: &Anchor<O>,
+) -> (MutableSignalCloned<O>, Subscription) {
+    let (mutable, subscription) = to_mutable(engine, anchor);
+    (mutable.signal_cloned(), subscription)
+}
+
+/// Wakes the signal's task by marking its Anchor dirty.
+struct DirtyWaker(Mutex<SendDirtyHandle>);
+
+impl Wake for DirtyWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        use crate::expert::DirtyHandle as _;
+        self.0.lock().unwrap().mark_dirty();
+    }
+}
+
+struct SignalAnchor<S: Signal> {
+    signal: Pin<Box<S>>,
+    waker: Option<Waker>,
+    ended: bool,
+    output: Option<S::Item>,
+    location: &'static Location<'static>,
+}
+
+impl<S: Signal + 'static> AnchorInner<Engine> for SignalAnchor<S>
+where
+    S::Item: 'static,
+{
+    type Output = S::Item;
+
+    fn dirty(&mut self, _child: &<super::AnchorHandle as crate::expert::AnchorHandle>::Token) {
+        panic!("somehow an input was dirtied on a signal Anchor; it never has any inputs to dirty")
+    }
+
+    fn poll_updated<G: UpdateContext<Engine = Engine>>(&mut self, ctx: &mut G) -> Poll {
+        if self.ended {
+            return Poll::Unchanged;
+        }
+        let waker = self.waker.get_or_insert_with(|| {
+            let dirty_handle: DirtyHandle = ctx.dirty_handle();
+            Waker::from(Arc::new(DirtyWaker(Mutex::new(dirty_handle.to_send()))))
+        });
+        let mut cx = Context::from_waker(waker);
+        let mut updated = false;
+        loop {
+            match self.signal.as_mut().poll_change(&mut cx) {
+                task::Poll::Ready(Some(val)) => {
+                    self.output = Some(val);
+                    updated = true;
+                }
+                task::Poll::Ready(None) => {
+                    self.ended = true;
+                    break;
+                }
+                task::Poll::Pending => break,
+            }
+        }
+        assert!(
+            self.output.is_some(),
+            "signal didn't produce an initial value when first polled"
+        );
+        if updated {
+            Poll::Updated
+        } else {
+            Poll::Unchanged
+        }
+    }
+
+    fn output<'slf, 'out, G: OutputContext<'out, Engine = Engine>>(
+        &'slf self,
+        _ctx: &mut G,
+    ) -> &'out Self::Output
+    where
+        'slf: 'out,
+    {
+        self.output
+            .as_ref()
+            .expect("output called on a signal Anchor before value was calculated")
+    }
+
+    fn debug_location(&self) -> Option<(&'static str, &'static Location<'static>)> {
+        Some(("signal", self.location))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::singlethread::Var;
+
+    fn poll_once<S: Signal + Unpin>(signal: &mut S) -> task::Poll<Option<S::Item>> {
+        Pin::new(signal).poll_change(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn from_mutable_follows_sets() {
+        let mut engine = Engine::new();
+        let mutable = Mutable::new(1);
+        let doubled = from_mutable(&mutable).map(|v| *v * 2);
+        engine.mark_observed(&doubled);
+        assert_eq!(engine.get(&doubled), 2);
+
+        mutable.set(5);
+        assert_eq!(engine.get(&doubled), 10);
+        mutable.set(6);
+        mutable.set(7);
+        assert_eq!(engine.get(&doubled), 14);
+    }
+
+    #[test]
+    fn to_mutable_updates_after_stabilize() {
+        let mut engine = Engine::new();
+        let var = Var::new(1);
+        let (mutable, subscription) = to_mutable(&mut engine, &var.watch());
+        assert_eq!(mutable.get_cloned(), 1);
+
+        var.set(2);
+        assert_eq!(mutable.get_cloned(), 1);
+        engine.stabilize();
+        assert_eq!(mutable.get_cloned(), 2);
+
+        engine.unsubscribe(subscription);
+        var.set(3);
+        engine.stabilize();
+        assert_eq!(mutable.get_cloned(), 2);
+    }
+
+    #[test]
+    fn to_signal_yields_changes() {
+        let mut engine = Engine::new();
+        let var = Var::new("a".to_string());
+        let (mut signal, _subscription) = to_signal(&mut engine, &var.watch());
+        assert_eq!(
+            poll_once(&mut signal),
+            task::Poll::Ready(Some("a".to_string()))
+        );
+        assert_eq!(poll_once(&mut signal), task::Poll::Pending);
+
+        var.set("b".to_string());
+        engine.stabilize();
+        assert_eq!(
+            poll_once(&mut signal),
+            task::Poll::Ready(Some("b".to_string()))
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Saving and restoring Var state with serde. Requires the `snapshot` feature.
+
+use super::{Engine, Transaction, Var};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The values of every Var registered with `Engine::register_var`, as returned by
+/// `Engine::snapshot_vars`. Serialize this with any serde format to persist it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VarSnapshot {
+    vars: BTreeMap<String, serde_json::Value>,
+}
+
+impl Engine {
+    /// Registers `var` under `name` so its value is included in `snapshot_vars` and
+    /// `restore_vars`. Registering a second Var with the same name replaces the first.
+    pub fn register_var<T>(&mut self, name: &str, var: &Var<T>)
+    where
+        T: Serialize + DeserializeOwned + 'static,
+    {
+        self.snapshot_vars.retain(|(existing, _)| existing != name);
+        self.snapshot_vars
+            .push((name.to_string(), Box::new(var.clone())));
+    }
+
+    /// Returns the current value of every registered Var.
+    pub fn snapshot_vars(&self) -> Result<VarSnapshot, serde_json::Error> {
+        let mut vars = BTreeMap::new();
+        for (name, var) in &self.snapshot_vars {
+            vars.insert(name.clone(), var.save()?);
+        }
+        Ok(VarSnapshot { vars })
+    }
+
+    /// Sets every registered Var to its value in `snapshot`, in a single transaction. Vars
+    /// missing from the snapshot are left unchanged, and values for unregistered names are
+    /// ignored. If any value fails to deserialize, no Vars are changed.
+    pub fn restore_vars(&mut self, snapshot: &VarSnapshot) -> Result<(), serde_json::Error> {
+        let mut tx = Transaction { ops: Vec::new() };
+        for (name, var) in &self.snapshot_vars {
+            if let Some(val) = snapshot.vars.get(name) {
+                var.load(&mut tx, val.clone())?;
+            }
+        }
+        self.transaction(|outer| outer.ops.append(&mut tx.ops));
+        Ok(())
+    }
+}
+
+pub(super) trait SnapshotVar {
+    fn save(&self) -> Result<serde_json::Value, serde_json::Error>;
+    fn load(&self, tx: &mut Transaction, val: serde_json::Value) -> Result<(), serde_json::Error>;
+}
+
+impl<T: Serialize + DeserializeOwned + 'static> SnapshotVar for Var<T> {
+    fn save(&self) -> Result<serde_json::Value, serde_json::Error> {
+        serde_json::to_value(&*self.get())
+    }
+
+    fn load(&self, tx: &mut Transaction, val: serde_json::Value) -> Result<(), serde_json::Error> {
+        tx.set(self, serde_json::from_value(val)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::singlethread::MultiAnchor;
+
+    #[test]
+    fn snapshot_and_restore() {
+        let mut engine = Engine::new();
+        let name = Var::new("a".to_string());
+        let count = Var::new(1u32);
+        engine.register_var("name", &name);
+        engine.register_var("count", &count);
+        let snapshot = engine.snapshot_vars().unwrap();
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(json, r#"{"vars":{"count":1,"name":"a"}}"#);
+
+        name.set("b".to_string());
+        count.set(2);
+        let summary = (&name.watch(), &count.watch()).map(|n, c| format!("{}{}", n, c));
+        assert_eq!(engine.get(&summary), "b2");
+
+        let snapshot: VarSnapshot = serde_json::from_str(&json).unwrap();
+        engine.restore_vars(&snapshot).unwrap();
+        assert_eq!(engine.get(&summary), "a1");
+
+        // a bad value leaves every var unchanged
+        let bad: VarSnapshot =
+            serde_json::from_str(r#"{"vars":{"count":"x","name":"c"}}"#).unwrap();
+        assert!(engine.restore_vars(&bad).is_err());
+        assert_eq!(engine.get(&summary), "a1");
+    }
+}
@@ -187,6 +187,38 @@ fn test_garbage_collection_wont_panic() {
     engine.stabilize();
 }
 
+#[test]
+fn test_dropped_anchors_are_freed() {
+    use crate::singlethread::{Engine, Var};
+    use std::rc::Rc;
+
+    let mut engine = Engine::new();
+    let var = Var::new(1);
+    let captured = Rc::new(());
+    let mapped = {
+        let captured = captured.clone();
+        var.watch().map(move |v| {
+            let _ = &captured;
+            *v + 1
+        })
+    };
+    engine.mark_observed(&mapped);
+    assert_eq!(engine.get(&mapped), 2);
+    assert_eq!(Rc::strong_count(&captured), 2);
+
+    // dropping the last handle frees the node and its AnchorInner, even while observed
+    let token = mapped.token();
+    std::mem::drop(mapped);
+    assert_eq!(Rc::strong_count(&captured), 1);
+
+    // the freed slot is reused by the next Anchor, but the old token doesn't refer to it
+    let reused = var.watch().map(|v| *v * 10);
+    assert_eq!(reused.token().ptr, token.ptr);
+    assert_ne!(reused.token(), token);
+    var.set(2);
+    assert_eq!(engine.get(&reused), 20);
+}
+
 #[test]
 fn test_readme_example() {
     // example
@@ -245,3 +277,2205 @@ fn test_readme_example() {
     my_unread_updater.set(50);
     assert_eq!(engine.get(&dynamic_name), "Robo");
 }
+
+/// An AnchorInner whose output is read from some shared cell, and which hands its
+/// dirty handle out to the test.
+struct ExternalCell {
+    val: std::rc::Rc<std::cell::Cell<usize>>,
+    handle: std::rc::Rc<std::cell::RefCell<Option<crate::singlethread::DirtyHandle>>>,
+    output: usize,
+}
+
+impl crate::expert::AnchorInner<crate::singlethread::Engine> for ExternalCell {
+    type Output = usize;
+    fn dirty(&mut self, _child: &crate::singlethread::AnchorToken) {}
+    fn poll_updated<G: crate::expert::UpdateContext<Engine = crate::singlethread::Engine>>(
+        &mut self,
+        ctx: &mut G,
+    ) -> crate::expert::Poll {
+        let mut handle = self.handle.borrow_mut();
+        if handle.is_none() {
+            *handle = Some(ctx.dirty_handle());
+        }
+        self.output = self.val.get();
+        crate::expert::Poll::Updated
+    }
+    fn output<
+        'slf,
+        'out,
+        G: crate::expert::OutputContext<'out, Engine = crate::singlethread::Engine>,
+    >(
+        &'slf self,
+        _ctx: &mut G,
+    ) -> &'out Self::Output
+    where
+        'slf: 'out,
+    {
+        &self.output
+    }
+}
+
+#[test]
+fn test_send_dirty_handle() {
+    use crate::expert::DirtyHandle;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let mut engine = crate::singlethread::Engine::new();
+    let val = std::rc::Rc::new(std::cell::Cell::new(1));
+    let handle = std::rc::Rc::new(std::cell::RefCell::new(None));
+    let anchor = engine.mount(ExternalCell {
+        val: val.clone(),
+        handle: handle.clone(),
+        output: 0,
+    });
+    let wakes = Arc::new(AtomicUsize::new(0));
+    {
+        let wakes = wakes.clone();
+        engine.set_send_wake_callback(move || {
+            wakes.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    assert_eq!(engine.get(&anchor), 1);
+
+    val.set(2);
+    assert_eq!(engine.get(&anchor), 1);
+
+    let send_handle = handle.borrow().as_ref().unwrap().to_send();
+    std::thread::spawn(move || send_handle.mark_dirty())
+        .join()
+        .unwrap();
+    assert_eq!(wakes.load(Ordering::SeqCst), 1);
+    assert_eq!(engine.get(&anchor), 2);
+}
+
+#[test]
+fn test_now() {
+    let mut engine = crate::singlethread::Engine::new();
+    let coarse = engine.now(std::time::Duration::from_secs(3600));
+    assert!(coarse == engine.now(std::time::Duration::from_secs(3600)));
+    let fine = engine.now(std::time::Duration::from_millis(1));
+    let coarse_calcs = std::rc::Rc::new(std::cell::Cell::new(0));
+    let coarse_mapped = {
+        let coarse_calcs = coarse_calcs.clone();
+        coarse.map(move |_| coarse_calcs.set(coarse_calcs.get() + 1))
+    };
+    engine.mark_observed(&coarse_mapped);
+    let first = engine.get(&fine);
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    assert!(engine.get(&fine) > first);
+    assert_eq!(coarse_calcs.get(), 1);
+
+    // clocks are created in the engine they're requested from, even if it isn't the default
+    let _newer = crate::singlethread::Engine::new();
+    let clock = engine.now(std::time::Duration::from_secs(60));
+    assert!(engine.get(&clock) >= first);
+}
+
+#[test]
+fn test_wake_callback() {
+    let mut engine = crate::singlethread::Engine::new();
+    let wakes = std::rc::Rc::new(std::cell::Cell::new(0));
+    {
+        let wakes = wakes.clone();
+        engine.set_wake_callback(move || wakes.set(wakes.get() + 1));
+    }
+    let observed_var = crate::singlethread::Var::new(1);
+    let unobserved_var = crate::singlethread::Var::new(1);
+    let observed = observed_var.watch().map(|v| *v + 1);
+    let unobserved = unobserved_var.watch().map(|v| *v + 1);
+    engine.mark_observed(&observed);
+    engine.get(&observed);
+    engine.get(&unobserved);
+    assert_eq!(wakes.get(), 0);
+
+    unobserved_var.set(2);
+    assert_eq!(wakes.get(), 0);
+
+    observed_var.set(2);
+    observed_var.set(3);
+    assert_eq!(wakes.get(), 1);
+
+    engine.stabilize();
+    observed_var.set(4);
+    assert_eq!(wakes.get(), 2);
+
+    // the callback can read the Var that woke it
+    let seen = std::rc::Rc::new(std::cell::Cell::new(0));
+    {
+        let seen = seen.clone();
+        let observed_var = observed_var.clone();
+        engine.set_wake_callback(move || seen.set(*observed_var.get()));
+    }
+    engine.stabilize();
+    observed_var.set(5);
+    assert_eq!(seen.get(), 5);
+}
+
+#[test]
+fn test_needs_stabilize() {
+    let mut engine = crate::singlethread::Engine::new();
+    let var = crate::singlethread::Var::new(1);
+    let mapped = var.watch().map(|v| *v + 1);
+    assert!(!engine.needs_stabilize());
+    engine.mark_observed(&mapped);
+    assert!(engine.needs_stabilize());
+    engine.stabilize();
+    assert!(!engine.needs_stabilize());
+    var.set(2);
+    assert!(engine.needs_stabilize());
+    assert_eq!(engine.get(&mapped), 3);
+    assert!(!engine.needs_stabilize());
+}
+
+#[test]
+fn test_stabilize_report() {
+    let mut engine = crate::singlethread::Engine::new();
+    let var = crate::singlethread::Var::new(1);
+    let a = var.watch().map(|v| *v + 1);
+    let b = var.watch().map(|v| *v > 100);
+    engine.mark_observed(&a);
+    engine.mark_observed(&b);
+
+    let report = engine.stabilize();
+    assert_eq!(report.nodes_recalculated, 3);
+    assert_eq!(report.observed_updated.len(), 2);
+
+    var.set(2);
+    let report2 = engine.stabilize();
+    assert_eq!(report2.generation, report.generation + 1);
+    assert_eq!(report2.nodes_recalculated, 3);
+    assert_eq!(report2.observed_updated, vec![a.token()]);
+
+    let report3 = engine.stabilize();
+    assert_eq!(report3.nodes_recalculated, 0);
+    assert!(report3.observed_updated.is_empty());
+}
+
+#[test]
+fn test_subscribe() {
+    let mut engine = crate::singlethread::Engine::new();
+    let var = crate::singlethread::Var::new(1);
+    let doubled = var.watch().map(|v| *v * 2);
+    let seen = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+    let subscription = {
+        let seen = seen.clone();
+        engine.subscribe(&doubled, move |v| seen.borrow_mut().push(*v))
+    };
+    engine.stabilize();
+    engine.stabilize();
+    var.set(2);
+    engine.stabilize();
+    var.set(2);
+    engine.stabilize();
+    assert_eq!(*seen.borrow(), vec![2, 4]);
+
+    engine.unsubscribe(subscription);
+    assert_eq!(
+        engine.check_observed(&doubled),
+        crate::singlethread::ObservedState::Unnecessary
+    );
+    var.set(3);
+    engine.stabilize();
+    assert_eq!(*seen.borrow(), vec![2, 4]);
+
+    // Anchors observed explicitly stay observed, whether before or after subscribing
+    let first = engine.subscribe(&doubled, |_| {});
+    let second = engine.subscribe(&doubled, |_| {});
+    engine.unsubscribe(first);
+    assert_eq!(
+        engine.check_observed(&doubled),
+        crate::singlethread::ObservedState::Observed
+    );
+    engine.mark_observed(&doubled);
+    engine.unsubscribe(second);
+    assert_eq!(
+        engine.check_observed(&doubled),
+        crate::singlethread::ObservedState::Observed
+    );
+    let third = engine.subscribe(&doubled, |_| {});
+    engine.unsubscribe(third);
+    assert_eq!(
+        engine.check_observed(&doubled),
+        crate::singlethread::ObservedState::Observed
+    );
+}
+
+#[test]
+fn test_take_updated_observed() {
+    let mut engine = crate::singlethread::Engine::new();
+    let var_a = crate::singlethread::Var::new(1);
+    let var_b = crate::singlethread::Var::new(1);
+    let a = var_a.watch().map(|v| *v + 1);
+    let b = var_b.watch().map(|v| *v + 1);
+    engine.mark_observed(&a);
+    engine.mark_observed(&b);
+    engine.stabilize();
+    assert_eq!(engine.take_updated_observed().len(), 2);
+    assert!(engine.take_updated_observed().is_empty());
+
+    var_b.set(5);
+    engine.get(&a);
+    var_b.set(6);
+    engine.stabilize();
+    assert_eq!(engine.take_updated_observed(), vec![b.token()]);
+}
+
+#[test]
+fn test_stabilize_budgeted() {
+    use crate::singlethread::StabilizeBudget;
+
+    let mut engine = crate::singlethread::Engine::new();
+    let var = crate::singlethread::Var::new(0);
+    let mut node = var.watch();
+    for _ in 0..10 {
+        node = node.map(|v| *v + 1);
+    }
+    engine.mark_observed(&node);
+    let seen = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+    {
+        let seen = seen.clone();
+        engine.subscribe(&node, move |v| seen.borrow_mut().push(*v));
+    }
+
+    let mut calls = 0;
+    let report = loop {
+        calls += 1;
+        if let Some(report) = engine.stabilize_budgeted(StabilizeBudget::Nodes(4)) {
+            break report;
+        }
+        assert!(seen.borrow().is_empty());
+    };
+    assert_eq!(calls, 3);
+    assert_eq!(report.nodes_recalculated, 11);
+    assert_eq!(*seen.borrow(), vec![10]);
+
+    // a partial stabilization is finished by a regular stabilize
+    var.set(1);
+    assert!(engine
+        .stabilize_budgeted(StabilizeBudget::Nodes(1))
+        .is_none());
+    var.set(2);
+    engine.stabilize();
+    assert_eq!(*seen.borrow(), vec![10, 11, 12]);
+    assert_eq!(engine.get(&node), 12);
+}
+
+#[test]
+fn test_step() {
+    use crate::expert::Poll;
+
+    let mut engine = crate::singlethread::Engine::new();
+    let var = crate::singlethread::Var::new(1);
+    let watched = var.watch();
+    let node = watched.map(|v| *v + 1);
+    engine.mark_observed(&node);
+
+    let steps: Vec<_> = std::iter::from_fn(|| engine.step()).collect();
+    let summary: Vec<_> = steps.iter().map(|step| (step.token, step.poll)).collect();
+    assert_eq!(
+        summary,
+        vec![
+            (node.token(), Poll::Pending),
+            (watched.token(), Poll::Updated),
+            (node.token(), Poll::Updated),
+        ]
+    );
+    assert!(steps[2].height > steps[1].height);
+    assert_eq!(engine.get(&node), 2);
+
+    // nothing to do until an input changes
+    assert!(engine.step().is_none());
+    var.set(5);
+    let summary: Vec<_> = std::iter::from_fn(|| engine.step())
+        .map(|step| (step.token, step.poll))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (watched.token(), Poll::Updated),
+            (node.token(), Poll::Updated)
+        ]
+    );
+    assert_eq!(engine.get(&node), 6);
+}
+
+#[test]
+fn test_stabilize_hooks() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let mut engine = crate::singlethread::Engine::new();
+    let var = crate::singlethread::Var::new(0);
+    let node = var.watch().map(|v| *v * 10);
+    engine.mark_observed(&node);
+
+    let events = Rc::new(RefCell::new(vec![]));
+    let log = Rc::new(RefCell::new(vec![]));
+    {
+        let events = events.clone();
+        let var = var.clone();
+        engine.add_pre_stabilize_hook(move || {
+            if let Some(v) = events.borrow_mut().pop() {
+                var.set(v);
+            }
+        });
+    }
+    {
+        let log = log.clone();
+        engine.add_post_stabilize_hook(move |report| {
+            log.borrow_mut().push(report.observed_updated.len())
+        });
+    }
+
+    engine.stabilize();
+    events.borrow_mut().push(3);
+    assert_eq!(engine.get(&node), 30);
+    assert_eq!(*log.borrow(), vec![1, 1]);
+    engine.stabilize();
+    assert_eq!(*log.borrow(), vec![1, 1, 0]);
+}
+
+#[test]
+fn test_transaction() {
+    use crate::singlethread::StabilizeBudget;
+
+    let mut engine = crate::singlethread::Engine::new();
+    let a = crate::singlethread::Var::new(1);
+    let b = crate::singlethread::Var::new(1);
+    let sum = (&a.watch(), &b.watch()).map(|a, b| *a + *b);
+    let seen = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+    {
+        let seen = seen.clone();
+        engine.subscribe(&sum, move |v| seen.borrow_mut().push(*v));
+    }
+    engine.stabilize();
+
+    // start a stabilization that's still in progress when the transaction begins
+    a.set(5);
+    assert!(engine
+        .stabilize_budgeted(StabilizeBudget::Nodes(1))
+        .is_none());
+    let report = engine.transaction(|tx| {
+        tx.set(&a, 10);
+        tx.set(&b, 20);
+        assert_eq!(*a.get(), 5);
+    });
+    assert_eq!(report.observed_updated, vec![sum.token()]);
+    assert_eq!(*seen.borrow(), vec![2, 6, 30]);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        engine.transaction(|tx| {
+            tx.set(&a, 100);
+            panic!("abort");
+        })
+    }));
+    assert!(result.is_err());
+    assert_eq!(*a.get(), 10);
+}
+
+#[test]
+fn test_var_modify() {
+    let mut engine = crate::singlethread::Engine::new();
+    let var = crate::singlethread::Var::new(vec![1, 2]);
+    let len = var.watch().map(|v| v.len());
+    engine.mark_observed(&len);
+    assert_eq!(engine.get(&len), 2);
+
+    var.modify(|v| v.push(3));
+    assert_eq!(*var.get(), vec![1, 2, 3]);
+    assert_eq!(engine.get(&len), 3);
+    assert_eq!(engine.get(&var.watch()), vec![1, 2, 3]);
+
+    var.modify(|v| v.push(4));
+    assert_eq!(engine.get(&var.watch()), vec![1, 2, 3, 4]);
+}
+
+#[test]
+fn test_var_modify_in_place() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct Counted(Rc<Cell<usize>>, i32);
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            self.0.set(self.0.get() + 1);
+            Counted(self.0.clone(), self.1)
+        }
+    }
+
+    let mut engine = crate::singlethread::Engine::new();
+    let clones = Rc::new(Cell::new(0));
+    let var = crate::singlethread::Var::new(Counted(clones.clone(), 1));
+    let val = var.watch().map(|v| v.1);
+    engine.mark_observed(&val);
+    assert_eq!(engine.get(&val), 1);
+
+    // the engine holds the current value, so the first modification clones it
+    var.modify(|v| v.1 += 1);
+    var.modify(|v| v.1 += 1);
+    assert_eq!(clones.get(), 1);
+    assert_eq!(engine.get(&val), 3);
+
+    // the engine hasn't seen the value `replace` returns, so it's moved out
+    var.set(Counted(clones.clone(), 4));
+    assert_eq!(var.replace(Counted(clones.clone(), 5)).1, 4);
+    assert_eq!(clones.get(), 1);
+    assert_eq!(engine.get(&val), 5);
+    assert_eq!(var.replace(Counted(clones.clone(), 6)).1, 5);
+    assert_eq!(clones.get(), 2);
+}
+
+#[test]
+fn test_var_replace() {
+    let mut engine = crate::singlethread::Engine::new();
+    let var = crate::singlethread::Var::new("a".to_string());
+    let len = var.watch().map(|v| v.len());
+    engine.mark_observed(&len);
+    assert_eq!(engine.get(&len), 1);
+
+    assert_eq!(var.replace("bc".to_string()), "a");
+    assert_eq!(engine.get(&len), 2);
+    assert_eq!(var.take(), "bc");
+    assert_eq!(engine.get(&len), 0);
+    assert_eq!(var.replace("def".to_string()), "");
+    assert_eq!(engine.get(&len), 3);
+
+    // callbacks run by replacing the value can read and write the Var
+    let seen = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+    {
+        let seen = seen.clone();
+        let var = var.clone();
+        engine.set_wake_callback(move || {
+            seen.borrow_mut().push(var.get().to_string());
+            if var.get().is_empty() {
+                var.replace("filled".to_string());
+            }
+        });
+    }
+    assert_eq!(var.replace("gh".to_string()), "def");
+    engine.stabilize();
+    assert_eq!(var.take(), "gh");
+    assert_eq!(*seen.borrow(), vec!["gh", ""]);
+    assert_eq!(engine.get(&len), 6);
+}
+
+#[test]
+fn test_var_set_if_changed() {
+    let mut engine = crate::singlethread::Engine::new();
+    let var = crate::singlethread::Var::new(1);
+    let calcs = std::rc::Rc::new(std::cell::Cell::new(0));
+    let node = {
+        let calcs = calcs.clone();
+        var.watch().map(move |v| {
+            calcs.set(calcs.get() + 1);
+            *v
+        })
+    };
+    engine.mark_observed(&node);
+    engine.stabilize();
+    assert_eq!(calcs.get(), 1);
+
+    assert!(!var.set_if_changed(1));
+    assert!(!engine.needs_stabilize());
+    engine.stabilize();
+    assert_eq!(calcs.get(), 1);
+
+    assert!(var.set_if_changed(2));
+    assert_eq!(engine.get(&node), 2);
+    assert_eq!(calcs.get(), 2);
+}
+
+#[test]
+fn test_clear() {
+    let mut engine = crate::singlethread::Engine::new();
+    let old_var = crate::singlethread::Var::new(1);
+    let old_node = old_var.watch().map(|v| *v + 1);
+    let wakes = std::rc::Rc::new(std::cell::Cell::new(0));
+    {
+        let wakes = wakes.clone();
+        engine.set_wake_callback(move || wakes.set(wakes.get() + 1));
+    }
+    engine.subscribe(&old_node, |_| {});
+    engine.stabilize();
+    let generation = engine.generation();
+
+    engine.clear();
+    assert!(engine.generation() < generation);
+    assert!(!engine.needs_stabilize());
+    // setting vars from the old graph does nothing
+    old_var.set(5);
+    assert!(!engine.needs_stabilize());
+    drop(old_node);
+
+    let var = crate::singlethread::Var::new(2);
+    let node = var.watch().map(|v| *v * 3);
+    engine.mark_observed(&node);
+    assert_eq!(engine.get(&node), 6);
+    var.set(3);
+    assert_eq!(wakes.get(), 1);
+    assert_eq!(engine.get(&node), 9);
+}
+
+#[test]
+fn test_clear_keeps_default_engine() {
+    let mut first = crate::singlethread::Engine::new();
+    let mut second = crate::singlethread::Engine::new();
+    second.clear();
+    // `first` is the default, so clearing `second` shouldn't make it the default
+    let a = crate::singlethread::Var::new(1).watch();
+    assert_eq!(first.get(&a), 1);
+
+    first.clear();
+    let b = crate::singlethread::Var::new(2).watch();
+    assert_eq!(first.get(&b), 2);
+    let c = second.enter(|| crate::singlethread::Var::new(3).watch());
+    assert_eq!(second.get(&c), 3);
+}
+
+#[test]
+fn test_multiple_engines() {
+    let mut first = crate::singlethread::Engine::new();
+    let mut second = crate::singlethread::Engine::new();
+    let a = first.enter(|| crate::singlethread::Var::new(1).watch().map(|v| *v + 1));
+    let b = second.enter(|| crate::singlethread::Var::new(10).watch().map(|v| *v + 1));
+    // without `enter`, anchors are created in the first engine that's still alive
+    let c = crate::singlethread::Var::new(100).watch();
+    assert_eq!(first.get(&a), 2);
+    assert_eq!(second.get(&b), 11);
+    assert_eq!(first.get(&c), 100);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| first.get(&b)));
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_default_engine_without_enter() {
+    use crate::singlethread::{Engine, Var};
+
+    let mut first = Engine::new();
+    let a = Var::new(1).watch().map(|v| *v + 1);
+    // creating another engine doesn't take over from one that's still alive
+    let mut second = Engine::new();
+    let b = Var::new(2).watch().map(|v| *v + 1);
+    assert_eq!(first.get(&a), 2);
+    assert_eq!(first.get(&b), 3);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| second.get(&b)));
+    assert!(result.is_err());
+
+    // once it's dropped, the next engine created becomes the default
+    std::mem::drop(first);
+    let mut third = Engine::new();
+    let c = Var::new(3).watch();
+    assert_eq!(third.get(&c), 3);
+    let d = second.enter(|| Var::new(4).watch());
+    assert_eq!(second.get(&d), 4);
+}
+
+#[test]
+fn test_engine_constructors() {
+    let mut first = crate::singlethread::Engine::new();
+    let mut second = crate::singlethread::Engine::new();
+    let var = first.var(1);
+    let constant = first.constant(2);
+    let sum = first.enter(|| (&var.watch(), &constant).map(|a, b| *a + *b));
+    let other = second.constant(5);
+    assert_eq!(first.get(&sum), 3);
+    assert_eq!(second.get(&other), 5);
+    var.set(10);
+    assert_eq!(first.get(&sum), 12);
+}
+
+#[test]
+fn test_peek() {
+    let mut engine = crate::singlethread::Engine::new();
+    let var = crate::singlethread::Var::new(1);
+    let node = var.watch().map(|v| *v + 1);
+    assert_eq!(engine.peek(&node), None);
+    assert_eq!(engine.get(&node), 2);
+
+    var.set(5);
+    let shared = &engine;
+    assert_eq!(shared.peek(&node), Some(2));
+    assert_eq!(engine.get(&node), 6);
+    assert_eq!(engine.peek(&node), Some(6));
+}
+
+#[test]
+fn test_read() {
+    struct NotClone(Vec<usize>);
+    impl PartialEq for NotClone {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    let mut engine = crate::singlethread::Engine::new();
+    let var = crate::singlethread::Var::new(3);
+    let node = var.watch().map(|n| NotClone((0..*n).collect()));
+    assert_eq!(engine.read(&node, |v| v.0.len()), 3);
+    var.set(5);
+    assert_eq!(engine.read(&node, |v| v.0.iter().sum::<usize>()), 10);
+}
+
+#[test]
+fn test_get_rc() {
+    use std::rc::Rc;
+
+    let mut engine = crate::singlethread::Engine::new();
+    let var = crate::singlethread::Var::new(vec![1, 2, 3]);
+    let watched = var.watch();
+    assert!(Rc::ptr_eq(&engine.get_rc(&watched), &var.get()));
+    var.set(vec![4]);
+    assert!(Rc::ptr_eq(&engine.get_rc(&watched), &var.get()));
+
+    let doubled = watched.map_rc(|v| v.iter().map(|x| x * 2).collect::<Vec<_>>());
+    let first = engine.get(&doubled);
+    assert_eq!(*first, vec![8]);
+    assert!(Rc::ptr_eq(&first, &engine.get(&doubled)));
+
+    let len = watched.map(|v| v.len());
+    assert_eq!(*engine.get_rc(&len), 1);
+}
+
+#[test]
+fn test_try_get() {
+    use crate::singlethread::{Anchor, Engine};
+    use crate::Error;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let mut engine = Engine::new();
+    let other = Engine::new();
+    let foreign = other.constant(1);
+    assert_eq!(engine.try_get(&foreign), Err(Error::WrongEngine));
+    drop(other);
+    assert_eq!(engine.try_get(&foreign), Err(Error::Freed));
+
+    let switch = engine.var(false);
+    let fine = engine.enter(|| switch.watch().map(|v| !*v));
+    assert_eq!(engine.try_get(&fine), Ok(true));
+
+    // once `switch` is set, `looped` depends on itself through `then`
+    let holder: Rc<RefCell<Option<Anchor<i32>>>> = Rc::new(RefCell::new(None));
+    let looped = engine.enter(|| {
+        let holder = holder.clone();
+        let constant = Anchor::constant(1);
+        switch
+            .watch()
+            .then(move |on| match *on {
+                true => holder.borrow().clone().unwrap(),
+                false => constant.clone(),
+            })
+            .map(|v| *v + 1)
+    });
+    *holder.borrow_mut() = Some(looped.clone());
+    engine.mark_observed(&looped);
+    assert_eq!(engine.try_get(&looped), Ok(2));
+    switch.set(true);
+    let path = match engine.try_get(&looped) {
+        Err(Error::CycleDetected { path }) => path,
+        other => panic!("expected a cycle, got {:?}", other),
+    };
+    let names: Vec<_> = path
+        .iter()
+        .map(|info| info.location.map(|(name, _)| name))
+        .collect();
+    assert_eq!(names, vec![Some("then"), Some("map")]);
+
+    // breaking the loop lets stabilization continue
+    switch.set(false);
+    assert_eq!(engine.try_get(&looped), Ok(2));
+    assert_eq!(engine.try_get(&fine), Ok(true));
+}
+
+#[test]
+fn test_get_isolated() {
+    let mut engine = crate::singlethread::Engine::new();
+    let var = crate::singlethread::Var::new(1);
+    let observed_calcs = std::rc::Rc::new(std::cell::Cell::new(0));
+    let observed = {
+        let observed_calcs = observed_calcs.clone();
+        var.watch().map(move |v| {
+            observed_calcs.set(observed_calcs.get() + 1);
+            *v + 1
+        })
+    };
+    let probe = var.watch().map(|v| *v * 100);
+    engine.mark_observed(&observed);
+    engine.stabilize();
+    assert_eq!(observed_calcs.get(), 1);
+
+    var.set(2);
+    assert_eq!(engine.get_isolated(&probe), 200);
+    assert_eq!(observed_calcs.get(), 1);
+    assert!(engine.needs_stabilize());
+
+    engine.stabilize();
+    assert_eq!(observed_calcs.get(), 2);
+    assert_eq!(engine.get(&observed), 3);
+}
+
+#[test]
+fn test_get_isolated_shared_inputs() {
+    use crate::singlethread::{Anchor, Engine, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(10);
+    let shared = var.watch().map(|v| *v + 1);
+    let observed = shared.map(|v| *v * 2);
+    let probe = shared.map(|v| *v * 100);
+    let switched = {
+        let shared = shared.clone();
+        Anchor::constant(())
+            .then(move |_| shared.clone())
+            .map(|v| *v * 2)
+    };
+    engine.mark_observed(&observed);
+    assert_eq!(engine.get(&observed), 22);
+    assert_eq!(engine.get_isolated(&probe), 1100);
+    assert_eq!(engine.get_isolated(&switched), 22);
+
+    // `shared` is queued because `observed` reads it, but `probe` still looks up-to-date
+    var.set(20);
+    assert_eq!(engine.get_isolated(&probe), 2100);
+    var.set(30);
+    assert_eq!(engine.get_isolated(&switched), 62);
+    assert_eq!(engine.get(&observed), 62);
+}
+
+#[test]
+fn test_get_isolated_frees_deferred() {
+    use crate::singlethread::{Engine, Var};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let mut engine = Engine::new();
+    let var = Var::new(1);
+    let other = Var::new(1);
+    let doomed = other.watch().map(|v| *v + 1);
+    engine.mark_observed(&doomed);
+    engine.stabilize();
+    let doomed = Rc::new(RefCell::new(Some(doomed)));
+    let probe = {
+        let doomed = doomed.clone();
+        var.watch().map(move |v| {
+            doomed.borrow_mut().take();
+            *v * 100
+        })
+    };
+
+    // `doomed` is deferred, then freed while `probe` is recalculated
+    other.set(2);
+    var.set(2);
+    assert_eq!(engine.get_isolated(&probe), 200);
+    assert!(doomed.borrow().is_none());
+    engine.stabilize();
+}
+
+#[test]
+fn test_try_get_max_height() {
+    use crate::singlethread::Engine;
+    use crate::Error;
+
+    let mut engine = Engine::builder().max_height(4).build();
+    let var = engine.var(1);
+    let short = engine.enter(|| var.watch().map(|v| *v + 1));
+    assert_eq!(engine.try_get(&short), Ok(2));
+
+    let mut long = short;
+    for _ in 0..4 {
+        long = engine.enter(|| long.map(|v| *v + 1));
+    }
+    assert_eq!(
+        engine.try_get(&long),
+        Err(Error::MaxHeightExceeded { max_height: 4 })
+    );
+}
+
+#[test]
+fn test_deep_chain() {
+    use crate::singlethread::{Engine, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(0);
+    let mut anchor = var.watch();
+    for _ in 0..1000 {
+        anchor = anchor.map(|v| *v + 1);
+    }
+    assert_eq!(engine.get(&anchor), 1000);
+    var.set(1);
+    assert_eq!(engine.get(&anchor), 1001);
+}
+
+#[test]
+fn test_with_observed() {
+    use crate::singlethread::{Engine, ObservedState, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(vec![1, 2, 3]);
+    let sum = var.watch().map(|v| v.iter().sum::<i32>());
+    assert_eq!(engine.with_observed(&sum, |sum| *sum), 6);
+    assert_eq!(ObservedState::Unnecessary, engine.check_observed(&sum));
+
+    engine.mark_observed(&sum);
+    var.set(vec![4]);
+    assert_eq!(engine.with_observed(&sum, |sum| *sum), 4);
+    assert_eq!(ObservedState::Observed, engine.check_observed(&sum));
+}
+
+#[test]
+fn test_mark_observed_many() {
+    use crate::singlethread::{Engine, ObservedState, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(1);
+    let shared = var.watch().map(|v| *v * 10);
+    let rows: Vec<_> = (0..5).map(|i| shared.map(move |v| *v + i)).collect();
+
+    engine.mark_observed_many(&rows);
+    engine.stabilize();
+    for row in &rows {
+        assert_eq!(ObservedState::Observed, engine.check_observed(row));
+    }
+    assert_eq!(ObservedState::Necessary, engine.check_observed(&shared));
+
+    engine.mark_unobserved_many(&rows[1..]);
+    assert_eq!(ObservedState::Unnecessary, engine.check_observed(&rows[1]));
+    assert_eq!(ObservedState::Necessary, engine.check_observed(&shared));
+
+    engine.mark_unobserved_many(&rows[..1]);
+    assert_eq!(ObservedState::Unnecessary, engine.check_observed(&shared));
+
+    // like `mark_observed`, outlives subscriptions that observed the Anchor first
+    let subscription = engine.subscribe(&rows[0], |_| {});
+    engine.mark_observed_many(&rows[..1]);
+    engine.unsubscribe(subscription);
+    assert_eq!(ObservedState::Observed, engine.check_observed(&rows[0]));
+}
+
+#[test]
+fn test_keep_alive() {
+    use crate::singlethread::{Engine, ObservedState, Var};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let mut engine = Engine::new();
+    engine.set_keep_alive(2);
+    let var = Var::new(1);
+    let calcs = Rc::new(Cell::new(0));
+    let doubled = {
+        let calcs = calcs.clone();
+        var.watch().map(move |v| {
+            calcs.set(calcs.get() + 1);
+            *v * 2
+        })
+    };
+    engine.mark_observed(&doubled);
+    engine.stabilize();
+    assert_eq!(calcs.get(), 1);
+
+    // still kept up-to-date for two stabilizations after being unobserved
+    engine.mark_unobserved(&doubled);
+    var.set(2);
+    engine.stabilize();
+    assert_eq!(calcs.get(), 2);
+    assert_eq!(ObservedState::Necessary, engine.check_observed(&doubled));
+    engine.stabilize();
+    assert_eq!(ObservedState::Necessary, engine.check_observed(&doubled));
+
+    engine.stabilize();
+    assert_eq!(ObservedState::Unnecessary, engine.check_observed(&doubled));
+    var.set(3);
+    engine.stabilize();
+    assert_eq!(calcs.get(), 2);
+    assert_eq!(engine.get(&doubled), 6);
+    assert_eq!(calcs.get(), 3);
+}
+
+#[test]
+fn test_cached_output_limit() {
+    use crate::singlethread::{Anchor, Engine, Var};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let mut engine = Engine::new();
+    engine.set_cached_output_limit(Some(1));
+    let var = Var::new(1);
+    let counted = |calcs: &Rc<Cell<usize>>, n: i32| -> Anchor<i32> {
+        let calcs = calcs.clone();
+        var.watch().map(move |v| {
+            calcs.set(calcs.get() + 1);
+            *v + n
+        })
+    };
+    let (a_calcs, b_calcs) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+    let a = counted(&a_calcs, 10);
+    let b = counted(&b_calcs, 20);
+    let a_len = a.refmap(|a| a);
+
+    assert_eq!(engine.get(&a_len), 11);
+    assert_eq!(engine.get(&b), 21);
+    assert_eq!((a_calcs.get(), b_calcs.get()), (1, 1));
+
+    // `a` was least recently calculated, so its output was dropped
+    assert_eq!(engine.get(&a), 11);
+    assert_eq!((a_calcs.get(), b_calcs.get()), (2, 1));
+    assert_eq!(engine.peek(&a_len), None);
+    assert_eq!(engine.get(&a_len), 11);
+
+    assert_eq!(engine.get(&b), 21);
+    assert_eq!((a_calcs.get(), b_calcs.get()), (2, 2));
+
+    // observed Anchors are never evicted
+    engine.mark_observed(&a);
+    engine.mark_observed(&b);
+    engine.stabilize();
+    engine.stabilize();
+    assert_eq!(engine.get(&a) + engine.get(&b), 32);
+    assert_eq!((a_calcs.get(), b_calcs.get()), (3, 2));
+}
+
+#[test]
+fn test_prune_unobserved() {
+    use crate::singlethread::{Engine, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(2);
+    let observed = var.watch().map(|v| *v + 1);
+    let doubled = var.watch().map(|v| *v * 2);
+    let quadrupled = doubled.map(|v| *v * 2);
+    engine.mark_observed(&observed);
+    assert_eq!(engine.get(&quadrupled), 8);
+
+    assert_eq!(engine.prune_unobserved(), 2);
+    assert_eq!(engine.peek(&quadrupled), None);
+    assert_eq!(engine.peek(&observed), Some(3));
+    assert_eq!(engine.prune_unobserved(), 0);
+
+    var.set(3);
+    assert_eq!(engine.get(&quadrupled), 12);
+    assert_eq!(engine.get(&observed), 4);
+}
+
+#[test]
+fn test_shrink_to_fit() {
+    use crate::singlethread::{Engine, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(1);
+    let mut deep = var.watch();
+    for _ in 0..200 {
+        deep = deep.map(|v| *v + 1);
+    }
+    engine.mark_observed(&deep);
+    assert_eq!(engine.get(&deep), 201);
+    std::mem::drop(deep);
+    engine.shrink_to_fit();
+
+    // freed nodes are reused, and the queue grows again as needed
+    let mut deep = var.watch();
+    for _ in 0..300 {
+        deep = deep.map(|v| *v + 1);
+    }
+    var.set(2);
+    assert_eq!(engine.get(&deep), 302);
+}
+
+#[test]
+fn test_stats() {
+    use crate::singlethread::{Engine, Var};
+
+    let mut engine = Engine::new();
+    let before = engine.stats();
+    let var = Var::new(1);
+    let a = var.watch().map(|v| *v + 1);
+    let b = a.map(|v| *v * 2);
+    engine.mark_observed(&b);
+    engine.stabilize();
+
+    let stats = engine.stats();
+    assert_eq!(stats.nodes, before.nodes + 3);
+    assert_eq!(stats.necessary_edges, 2);
+    assert_eq!(stats.clean_parent_edges, 2);
+    assert!(stats.approx_bytes > before.approx_bytes);
+
+    std::mem::drop((a, b));
+    let stats = engine.stats();
+    assert_eq!(stats.nodes, before.nodes + 1);
+    assert_eq!(stats.free_nodes, before.free_nodes + 2);
+    assert_eq!(stats.necessary_edges, 0);
+}
+
+#[test]
+fn test_builder() {
+    use crate::singlethread::{Engine, GcPolicy};
+
+    let policy = GcPolicy {
+        keep_alive: 3,
+        cached_output_limit: Some(10),
+    };
+    let mut engine = Engine::builder()
+        .max_height(100)
+        .initial_capacity(50)
+        .gc_policy(policy)
+        .build();
+    assert_eq!(engine.gc_policy(), policy);
+    let anchor = engine.constant(1).map(|v| *v + 1);
+    assert_eq!(engine.get(&anchor), 2);
+
+    engine.clear();
+    assert_eq!(engine.gc_policy(), policy);
+}
+
+#[test]
+fn test_topology() {
+    use crate::singlethread::{Engine, MultiAnchor, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(1);
+    let a = var.watch().map(|v| *v + 1);
+    let b = var.watch().map(|v| *v + 2);
+    let sum = (&a, &b).map(|a, b| *a + *b);
+    engine.mark_observed(&sum);
+    engine.stabilize();
+
+    let topology = engine.topology();
+    // var at height 0, a and b at 1, sum at 2
+    assert_eq!(topology.height_histogram[..3], [1, 2, 1]);
+    // var has no inputs, a and b have one, sum has two
+    assert_eq!(topology.fan_in[1..], [2, 1]);
+    // sum has no dependents, a and b have one, var has two
+    assert_eq!(topology.fan_out[1..], [2, 1]);
+}
+
+#[test]
+fn test_node_info() {
+    use crate::singlethread::{Engine, ObservedState, RecalcState, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(1);
+    let a = var.watch().map(|v| *v + 1);
+    let b = a.map(|v| *v > 0);
+    engine.mark_observed(&b);
+    engine.stabilize();
+
+    let info = engine.node_info(&a);
+    assert_eq!(info.height, 1);
+    assert_eq!(info.observed, ObservedState::Necessary);
+    assert_eq!(info.recalc_state, RecalcState::Ready);
+    assert_eq!(info.last_ready, Some(engine.generation()));
+    assert_eq!(info.last_update, Some(engine.generation()));
+    assert_eq!(info.necessary_count, 1);
+    assert_eq!(info.debug_info.location.map(|(name, _)| name), Some("map"));
+
+    // b's output doesn't change, so only its last_ready moves forward
+    let updated = engine.generation();
+    var.set(5);
+    engine.stabilize();
+    let info = engine.node_info(&b);
+    assert_eq!(info.last_ready, Some(engine.generation()));
+    assert_eq!(info.last_update, Some(updated));
+}
+
+#[test]
+fn test_dependencies_and_dependents() {
+    use crate::singlethread::{Engine, MultiAnchor, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(1);
+    let a = var.watch().map(|v| *v + 1);
+    let b = var.watch().map(|v| *v + 2);
+    let sum = (&a, &b).map(|a, b| *a + *b);
+    assert_eq!(engine.get(&sum), 5);
+
+    let tokens = |links: Vec<(_, _)>| {
+        links
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect::<Vec<_>>()
+    };
+    let mut deps = tokens(engine.dependencies(&sum));
+    deps.sort_by_key(|token| *token != a.token());
+    assert_eq!(deps, vec![a.token(), b.token()]);
+    assert_eq!(tokens(engine.dependents(&a)), vec![sum.token()]);
+    assert_eq!(engine.dependents(&sum), vec![]);
+
+    let (_, info) = engine.dependents(&b)[0];
+    assert_eq!(info.location.map(|(name, _)| name), Some("map"));
+}
+
+#[test]
+fn test_debug_state() {
+    use crate::singlethread::{Engine, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(1);
+    let a = var.watch().map(|v| *v + 1);
+    let b = var.watch().map(|v| *v + 2);
+    engine.mark_observed(&a);
+    engine.stabilize();
+
+    let state = engine.debug_state();
+    let line = |anchor: &crate::singlethread::Anchor<i32>| {
+        let location = engine.node_info(anchor).debug_info.to_string();
+        state
+            .lines()
+            .find(|line| line.trim_start().starts_with(&location))
+            .unwrap()
+            .to_string()
+    };
+    assert!(line(&a).ends_with("   1  necessary  observed  Ready  "));
+    assert!(line(&b).ends_with("   --         --     Needed "));
+}
+
+#[test]
+fn test_hotspots() {
+    use crate::singlethread::{Engine, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(1);
+    let hot = var.watch().map(|v| *v + 1);
+    let cold = Var::new(1).watch().map(|v| *v + 1);
+    engine.mark_observed(&hot);
+    engine.mark_observed(&cold);
+    engine.stabilize();
+    let initial = engine.hotspots(10);
+    let initial = initial.iter().find(|h| h.token == hot.token()).unwrap();
+    for i in 2..5 {
+        var.set(i);
+        engine.stabilize();
+    }
+
+    let hotspots = engine.hotspots(1);
+    assert_eq!(hotspots.len(), 1);
+    assert_eq!(hotspots[0].token, hot.token());
+    assert_eq!(hotspots[0].recalculations, initial.recalculations + 3);
+    assert_eq!(hotspots[0].recalc_time, std::time::Duration::ZERO);
+
+    std::mem::drop(engine);
+    let mut engine = Engine::builder().time_recalculations(true).build();
+    let slow = engine.constant(1).map(|v| {
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        *v
+    });
+    assert_eq!(engine.get(&slow), 1);
+    let hotspots = engine.hotspots(10);
+    assert_eq!(hotspots[0].token, slow.token());
+    assert!(hotspots[0].recalc_time >= std::time::Duration::from_millis(5));
+}
+
+#[test]
+fn test_named_anchors() {
+    use crate::singlethread::{Engine, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(1);
+    let inbox_count = var.watch().map(|v| *v + 1).named("inbox_count");
+    let unnamed = var.watch().map(|v| *v + 2);
+    engine.mark_observed(&inbox_count);
+    engine.stabilize();
+
+    let info = engine.node_info(&inbox_count).debug_info;
+    assert_eq!(info.name, Some("inbox_count"));
+    assert!(info.to_string().starts_with("inbox_count: "));
+    assert!(info.to_string().ends_with(" (map)"));
+    assert_eq!(engine.node_info(&unnamed).debug_info.name, None);
+    assert!(engine.debug_state().contains("inbox_count: "));
+}
+
+#[test]
+fn test_find_anchors() {
+    use crate::singlethread::{Engine, Var};
+
+    let engine = Engine::new();
+    let var = Var::new(1);
+    let a = var.watch().map(|v| *v + 1).named("a");
+    let b = var.watch().map(|v| *v + 2);
+
+    assert_eq!(
+        engine.find_anchors(|info| info.name == Some("a")),
+        vec![a.token()]
+    );
+    let location = engine.node_info(&b).debug_info.location;
+    assert_eq!(
+        engine.find_anchors(|info| info.location == location),
+        vec![b.token()]
+    );
+    assert_eq!(engine.find_anchors(|info| info.name == Some("c")), vec![]);
+}
+
+#[test]
+fn test_event_stream() {
+    use crate::expert::Poll;
+    use crate::singlethread::{Engine, EngineEvent, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(1);
+    let a = var.watch().map(|v| *v + 1);
+    engine.mark_observed(&a);
+    engine.stabilize();
+
+    let events = engine.event_stream();
+    var.set(2);
+    engine.stabilize();
+    let generation = engine.generation();
+    let received = events.try_iter().collect::<Vec<_>>();
+    let position = |event| received.iter().position(|e| *e == event).unwrap();
+    let dirty = position(EngineEvent::DirtyMarked {
+        id: var.watch().token().id(),
+    });
+    let start = position(EngineEvent::StabilizeStart { generation });
+    assert!(dirty < start);
+    assert_eq!(
+        received[received.len() - 1],
+        EngineEvent::StabilizeEnd { generation }
+    );
+    assert!(received.contains(&EngineEvent::NodeQueued {
+        id: a.token().id(),
+        height: 1,
+    }));
+    assert!(received.contains(&EngineEvent::NodeRecalculated {
+        id: a.token().id(),
+        height: 1,
+        poll: Poll::Updated,
+    }));
+
+    // dropped receivers are forgotten
+    drop(events);
+    engine.stabilize();
+    assert!(engine.graph.event_senders.borrow().is_empty());
+
+    // events can be handled on another thread
+    let events = engine.event_stream();
+    var.set(3);
+    engine.stabilize();
+    let id = a.token().id();
+    let handler = std::thread::spawn(move || {
+        events.try_iter().any(
+            |event| matches!(event, EngineEvent::NodeRecalculated { id: other, .. } if other == id),
+        )
+    });
+    assert!(handler.join().unwrap());
+}
+
+#[test]
+fn test_catch_panics_poisons_anchors() {
+    use crate::singlethread::{Engine, Var};
+    use crate::Error;
+
+    let mut engine = Engine::builder().catch_panics(true).build();
+    let var = Var::new(1);
+    let a = var.watch().map(|v| {
+        if *v == 2 {
+            panic!("two is not allowed");
+        }
+        *v + 1
+    });
+    let b = a.map(|v| *v * 10);
+    let c = var.watch().map(|v| *v * 2);
+    engine.mark_observed(&b);
+    engine.mark_observed(&c);
+    assert_eq!(engine.get(&b), 20);
+
+    var.set(2);
+    let expected = Error::Poisoned {
+        anchor: engine.node_info(&a).debug_info,
+        message: "two is not allowed".to_string(),
+    };
+    assert_eq!(engine.try_get(&b), Err(expected.clone()));
+    assert_eq!(engine.try_get(&a), Err(expected));
+    assert_eq!(engine.get(&c), 4);
+
+    // other ways of reading don't return stale values either
+    assert_eq!(engine.peek(&b), None);
+    assert_eq!(engine.peek(&c), Some(4));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        engine.get_many((&c, &b));
+    }));
+    assert!(result.is_err());
+
+    var.set(3);
+    assert_eq!(engine.get(&b), 40);
+    assert_eq!(engine.get(&a), 4);
+
+    // anchors poisoned on their first recalculation recover too
+    var.set(2);
+    let d = a.map(|v| *v + 1);
+    assert!(engine.try_get(&d).is_err());
+    var.set(5);
+    assert_eq!(engine.get(&d), 7);
+}
+
+#[test]
+fn test_pull_strategy() {
+    use crate::singlethread::{Engine, ObservedState, Strategy, Var};
+
+    let mut engine = Engine::builder().strategy(Strategy::Pull).build();
+    let var = Var::new(1);
+    let a = var.watch().map(|v| *v + 1);
+    let b = a.map(|v| *v * 10);
+    let unobserved = var.watch().map(|v| *v - 1);
+    engine.mark_observed(&b);
+    assert_eq!(engine.get(&b), 20);
+    assert_eq!(engine.check_observed(&a), ObservedState::Unnecessary);
+    assert_eq!(engine.node_info(&a).necessary_count, 0);
+
+    // observed anchors are still kept up-to-date
+    let report = engine.transaction(|tx| tx.set(&var, 2));
+    assert_eq!(report.observed_updated, vec![b.token()]);
+    assert_eq!(engine.get(&b), 30);
+    assert_eq!(engine.get(&unobserved), 1);
+    var.set(5);
+    assert_eq!(engine.get(&unobserved), 4);
+    assert_eq!(engine.get(&b), 60);
+}
+
+#[test]
+fn test_pull_strategy_cached_output_limit() {
+    use crate::singlethread::{Engine, Strategy, Var};
+
+    let mut engine = Engine::builder().strategy(Strategy::Pull).build();
+    engine.set_cached_output_limit(Some(0));
+    let var = Var::new(1);
+    let a = var.watch().map(|v| *v + 1);
+    let b = a.map(|v| *v * 10);
+    let c = b.map(|v| *v + 5);
+    engine.mark_observed(&c);
+    engine.stabilize();
+
+    // inputs of observed Anchors look unnecessary under Pull, but aren't evicted
+    assert!(!engine.needs_stabilize());
+    assert_eq!(engine.peek(&c), Some(25));
+    var.set(2);
+    engine.stabilize();
+    assert!(!engine.needs_stabilize());
+    assert_eq!(engine.peek(&c), Some(35));
+
+    // once nothing observes them, they are
+    engine.mark_unobserved(&c);
+    engine.stabilize();
+    assert_eq!(engine.peek(&a), None);
+    assert_eq!(engine.get(&c), 35);
+}
+
+#[test]
+fn test_priorities() {
+    use crate::singlethread::{Engine, StabilizeBudget, Var};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let mut engine = Engine::new();
+    let log = Rc::new(RefCell::new(vec![]));
+    let var = Var::new(1);
+    let logged = |name: &'static str| {
+        let log = log.clone();
+        var.watch().map(move |v| {
+            log.borrow_mut().push(name);
+            *v
+        })
+    };
+    let background = logged("background");
+    let normal = logged("normal");
+    let interactive = logged("interactive").with_priority(10);
+    engine.mark_observed(&background);
+    engine.mark_observed(&normal);
+    engine.mark_observed(&interactive);
+    engine.stabilize();
+    log.borrow_mut().clear();
+
+    // the first node recalculated is the var itself
+    var.set(2);
+    assert!(engine
+        .stabilize_budgeted(StabilizeBudget::Nodes(3))
+        .is_none());
+    assert_eq!(*log.borrow(), vec!["interactive", "background"]);
+
+    engine.stabilize();
+
+    // priorities can change while an anchor is queued
+    log.borrow_mut().clear();
+    var.set(3);
+    assert_eq!(engine.step().unwrap().height, 0);
+    assert_eq!(engine.step().unwrap().token, interactive.token());
+    let background = background.with_priority(20);
+    engine.stabilize();
+    assert_eq!(*log.borrow(), vec!["interactive", "background", "normal"]);
+    assert_eq!(engine.get(&background), 3);
+}
+
+#[test]
+fn test_map_fused() {
+    use crate::singlethread::{Engine, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(1);
+    let mut chain = var.watch().map_fused(|v| *v + 1);
+    for _ in 0..99 {
+        chain = chain.map(|v| *v + 1);
+    }
+    let out = chain.map(|v| v.to_string()).build();
+    engine.mark_observed(&out);
+    assert_eq!(engine.get(&out), "101");
+    assert_eq!(engine.stats().nodes, 2);
+
+    var.set(5);
+    assert_eq!(engine.stabilize().nodes_recalculated, 2);
+    assert_eq!(engine.get(&out), "105");
+}
+
+#[test]
+#[cfg_attr(miri, ignore = "inline outputs are disabled under Miri")]
+fn test_get_copy() {
+    use crate::singlethread::{Engine, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(1u32);
+    let small = var.watch().map(|v| (*v as u64, *v as u8));
+    let large = var.watch().map(|v| [*v as u64; 4]);
+    let unread = var.watch().map(|v| *v as u64);
+    engine.mark_observed(&unread);
+    assert_eq!(engine.get(&unread), 1);
+    assert_eq!(engine.get_copy(&small), (1, 1));
+    assert_eq!(engine.get_copy(&large), [1; 4]);
+    assert!(engine.graph.with(|graph| graph
+        .lookup(small.token())
+        .inline_output
+        .load::<(u64, u8)>()
+        .is_some()));
+
+    var.set(2);
+    assert_eq!(engine.get_copy(&small), (2, 2));
+    assert_eq!(engine.get_copy(&large), [2; 4]);
+    assert_eq!(engine.get_copy(&var.watch()), 2);
+
+    // outputs are only copied for anchors that have been read with get_copy
+    assert_eq!(engine.get(&unread), 2);
+    assert!(engine.graph.with(|graph| graph
+        .lookup(unread.token())
+        .inline_output
+        .load::<u64>()
+        .is_none()));
+}
+
+#[test]
+#[cfg_attr(miri, ignore = "freed boxes aren't pooled under Miri")]
+fn test_freed_anchor_boxes_are_reused() {
+    use crate::singlethread::{Anchor, Engine, Var};
+
+    let engine = Engine::new();
+    let var = Var::new(1);
+    let address = |anchor: &Anchor<i32>| {
+        engine.graph.with(|graph| {
+            let node = graph.lookup(anchor.token());
+            let borrow = node.anchor.borrow();
+            &**borrow.as_ref().unwrap() as *const _ as *const u8 as usize
+        })
+    };
+    let a = var.watch().map(|v| *v + 1);
+    let first = address(&a);
+    drop(a);
+    let b = var.watch().map(|v| *v + 2);
+    assert_eq!(address(&b), first);
+}
+
+#[test]
+fn test_initial_capacity_preallocates_nodes() {
+    use crate::singlethread::{Engine, Var};
+
+    let mut engine = Engine::builder().initial_capacity(10).build();
+    assert_eq!(engine.stats().free_nodes, 10);
+    let var = Var::new(1);
+    let mut anchors = vec![];
+    for i in 0..9 {
+        anchors.push(var.watch().map(move |v| *v + i));
+    }
+    let stats = engine.stats();
+    assert_eq!((stats.nodes, stats.free_nodes), (10, 0));
+    assert_eq!(engine.get(&anchors[8]), 9);
+
+    // steady-state churn reuses the same nodes
+    for _ in 0..5 {
+        anchors.pop();
+        anchors.push(var.watch().map(|v| *v * 2));
+        assert_eq!(engine.get(&anchors[8]), 2);
+    }
+    let stats = engine.stats();
+    assert_eq!(stats.nodes + stats.free_nodes, 10);
+}
+
+#[test]
+fn test_upgrade_token() {
+    use crate::singlethread::{Engine, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(1);
+    let doubled = var.watch().map(|v| *v * 2);
+    let token = doubled.token();
+    assert!(engine.upgrade::<String>(token).is_none());
+
+    // the upgraded Anchor keeps the node alive after the original is dropped
+    let upgraded = engine.upgrade::<i32>(token).unwrap();
+    drop(doubled);
+    var.set(5);
+    assert_eq!(engine.get(&upgraded), 10);
+
+    drop(upgraded);
+    assert!(engine.upgrade::<i32>(token).is_none());
+    let reused = var.watch().map(|v| *v + 1);
+    assert!(engine.upgrade::<i32>(token).is_none());
+    assert_eq!(engine.get(&reused), 6);
+
+    let other = Engine::new();
+    let foreign = other.constant(1);
+    assert!(engine.upgrade::<i32>(foreign.token()).is_none());
+}
+
+#[test]
+fn test_expert_types_default_to_singlethread_engine() {
+    use crate::expert::{Anchor, Var};
+    use crate::singlethread::Engine;
+
+    fn doubled(anchor: &Anchor<i32>) -> Anchor<i32> {
+        anchor.map(|v| *v * 2)
+    }
+
+    let mut engine = Engine::new();
+    let var: Var<i32> = Var::new(2);
+    assert_eq!(engine.get(&doubled(&var.watch())), 4);
+}
+
+#[test]
+fn test_request_many() {
+    use crate::expert::{AnchorInner, OutputContext, Poll, UpdateContext};
+    use crate::singlethread::{Anchor, Engine, Var};
+
+    struct Sum {
+        anchors: Vec<Anchor<i32>>,
+        sum: i32,
+    }
+
+    impl AnchorInner<Engine> for Sum {
+        type Output = i32;
+        fn dirty(&mut self, _child: &<super::AnchorHandle as crate::expert::AnchorHandle>::Token) {}
+        fn poll_updated<G: UpdateContext<Engine = Engine>>(&mut self, ctx: &mut G) -> Poll {
+            if ctx.request_many(&self.anchors, true) == Poll::Pending {
+                return Poll::Pending;
+            }
+            self.sum = self.anchors.iter().map(|anchor| *ctx.get(anchor)).sum();
+            Poll::Updated
+        }
+        fn output<'slf, 'out, G: OutputContext<'out, Engine = Engine>>(
+            &'slf self,
+            _ctx: &mut G,
+        ) -> &'out i32
+        where
+            'slf: 'out,
+        {
+            &self.sum
+        }
+    }
+
+    let mut engine = Engine::new();
+    let vars: Vec<_> = (0..100).map(Var::new).collect();
+    let anchors: Vec<_> = vars.iter().map(|var| var.watch().map(|v| *v)).collect();
+    // each input is requested twice, which must only count once towards its necessary count
+    let sum = <Engine as crate::expert::Engine>::mount(Sum {
+        anchors: anchors.iter().chain(&anchors).cloned().collect(),
+        sum: 0,
+    });
+    engine.mark_observed(&sum);
+    assert_eq!(engine.get(&sum), 2 * 4950);
+    engine.graph.with(|graph| {
+        let node = graph.lookup(sum.token());
+        assert_eq!(node.necessary_children().count(), 100);
+        assert_eq!(graph.lookup(anchors[0].token()).necessary_count.get(), 1);
+    });
+
+    vars[10].set(1000);
+    assert_eq!(engine.get(&sum), 2 * (4950 - 10 + 1000));
+}
+
+#[test]
+fn test_dirtied_children() {
+    use crate::expert::{AnchorInner, OutputContext, Poll, UpdateContext};
+    use crate::singlethread::{Anchor, AnchorToken, Engine, Var};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// sums its inputs, only rereading the ones that changed
+    struct Sum {
+        anchors: Vec<Anchor<i32>>,
+        vals: Vec<i32>,
+        sum: i32,
+        reread: Rc<RefCell<Vec<usize>>>,
+    }
+
+    impl AnchorInner<Engine> for Sum {
+        type Output = i32;
+        fn dirty(&mut self, _child: &AnchorToken) {}
+        fn poll_updated<G: UpdateContext<Engine = Engine>>(&mut self, ctx: &mut G) -> Poll {
+            if ctx.request_many(&self.anchors, true) == Poll::Pending {
+                return Poll::Pending;
+            }
+            let dirtied = ctx.dirtied_children().unwrap().to_vec();
+            for (i, anchor) in self.anchors.iter().enumerate() {
+                if self.vals.len() <= i || dirtied.contains(&anchor.token()) {
+                    let val = *ctx.get(anchor);
+                    if i < self.vals.len() {
+                        self.sum -= self.vals[i];
+                        self.vals[i] = val;
+                    } else {
+                        self.vals.push(val);
+                    }
+                    self.sum += val;
+                    self.reread.borrow_mut().push(i);
+                }
+            }
+            Poll::Updated
+        }
+        fn output<'slf, 'out, G: OutputContext<'out, Engine = Engine>>(
+            &'slf self,
+            _ctx: &mut G,
+        ) -> &'out i32
+        where
+            'slf: 'out,
+        {
+            &self.sum
+        }
+    }
+
+    let mut engine = Engine::new();
+    let vars: Vec<_> = (0..10).map(Var::new).collect();
+    let anchors: Vec<_> = vars.iter().map(|var| var.watch()).collect();
+    let reread = Rc::new(RefCell::new(vec![]));
+    let sum = <Engine as crate::expert::Engine>::mount(Sum {
+        anchors,
+        vals: vec![],
+        sum: 0,
+        reread: reread.clone(),
+    });
+    engine.mark_observed(&sum);
+    assert_eq!(engine.get(&sum), 45);
+
+    assert_eq!(*reread.borrow(), (0..10).collect::<Vec<_>>());
+
+    vars[3].set(100);
+    vars[7].set(100);
+    assert_eq!(engine.get(&sum), 45 - 3 - 7 + 200);
+    assert_eq!(reread.borrow()[10..], [3, 7]);
+}
+
+#[test]
+fn test_dirty_payloads() {
+    use crate::expert::{AnchorInner, DirtyHandle as _, OutputContext, Poll, UpdateContext};
+    use crate::singlethread::{AnchorToken, DirtyHandle, Engine};
+    use std::any::Any;
+    use std::cell::{Cell, RefCell};
+    use std::ops::Range;
+    use std::rc::Rc;
+
+    /// squares of some external rows, recomputing only the rows reported as changed
+    struct Squares {
+        rows: Rc<RefCell<Vec<i32>>>,
+        handle: Rc<RefCell<Option<DirtyHandle>>>,
+        changed: Vec<Range<usize>>,
+        squares: Vec<i32>,
+        recomputed: Rc<RefCell<usize>>,
+    }
+
+    impl AnchorInner<Engine> for Squares {
+        type Output = Vec<i32>;
+        fn dirty(&mut self, _child: &AnchorToken) {}
+        fn dirty_payload(&mut self, payload: Box<dyn Any>) {
+            self.changed
+                .push(*payload.downcast::<Range<usize>>().unwrap());
+        }
+        fn poll_updated<G: UpdateContext<Engine = Engine>>(&mut self, ctx: &mut G) -> Poll {
+            let rows = self.rows.borrow();
+            if self.handle.borrow().is_none() {
+                *self.handle.borrow_mut() = Some(ctx.dirty_handle());
+                self.changed.push(0..rows.len());
+            }
+            self.squares.resize(rows.len(), 0);
+            for range in self.changed.drain(..) {
+                for i in range {
+                    self.squares[i] = rows[i] * rows[i];
+                    *self.recomputed.borrow_mut() += 1;
+                }
+            }
+            Poll::Updated
+        }
+        fn output<'slf, 'out, G: OutputContext<'out, Engine = Engine>>(
+            &'slf self,
+            _ctx: &mut G,
+        ) -> &'out Vec<i32>
+        where
+            'slf: 'out,
+        {
+            &self.squares
+        }
+    }
+
+    let mut engine = Engine::new();
+    let rows = Rc::new(RefCell::new((0..10).collect::<Vec<_>>()));
+    let handle = Rc::new(RefCell::new(None));
+    let recomputed = Rc::new(RefCell::new(0));
+    let squares = <Engine as crate::expert::Engine>::mount(Squares {
+        rows: rows.clone(),
+        handle: handle.clone(),
+        changed: vec![],
+        squares: vec![],
+        recomputed: recomputed.clone(),
+    });
+    engine.mark_observed(&squares);
+    assert_eq!(engine.get(&squares)[9], 81);
+    assert_eq!(*recomputed.borrow(), 10);
+
+    rows.borrow_mut()[2] = 20;
+    rows.borrow_mut()[3] = 30;
+    rows.borrow_mut()[8] = 80;
+    let handle = handle.borrow().clone().unwrap();
+    handle.mark_dirty_with(2..4usize);
+    handle.to_send().mark_dirty_with(8..9usize);
+    let squares = engine.get(&squares);
+    assert_eq!(squares[2..4], [400, 900]);
+    assert_eq!(squares[8], 6400);
+    assert_eq!(*recomputed.borrow(), 13);
+
+    // handles without payload support still work, including as trait objects
+    struct Counter(Cell<usize>);
+    impl crate::expert::DirtyHandle for Counter {
+        fn mark_dirty(&self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+    let counter = Counter(Cell::new(0));
+    counter.mark_dirty_with(1..2usize);
+    let dyn_handle: &dyn crate::expert::DirtyHandle = &counter;
+    dyn_handle.mark_dirty();
+    assert_eq!(counter.0.get(), 2);
+}
+
+#[test]
+fn test_polled() {
+    use crate::expert::external::Polled;
+    use crate::expert::DirtyHandle as _;
+    use crate::singlethread::{DirtyHandle, Engine};
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    let mut engine = Engine::new();
+    let source = Rc::new(Cell::new(1));
+    let polls = Rc::new(Cell::new(0));
+    let handle: Rc<RefCell<Option<DirtyHandle>>> = Rc::new(RefCell::new(None));
+    let polled = Polled::new({
+        let source = source.clone();
+        let polls = polls.clone();
+        move || {
+            polls.set(polls.get() + 1);
+            source.get()
+        }
+    })
+    .on_dirty_handle({
+        let handle = handle.clone();
+        move |dirty_handle| *handle.borrow_mut() = Some(dirty_handle)
+    })
+    .mount();
+    let maps = Rc::new(Cell::new(0));
+    let doubled = polled.map({
+        let maps = maps.clone();
+        move |v| {
+            maps.set(maps.get() + 1);
+            v * 2
+        }
+    });
+    engine.mark_observed(&doubled);
+    assert_eq!(engine.get(&doubled), 2);
+    assert!(handle.borrow().is_some());
+
+    // not repolled until it's marked dirty, and repeated marks only cause one poll
+    source.set(2);
+    engine.stabilize();
+    assert_eq!(engine.get(&doubled), 2);
+    handle.borrow().as_ref().unwrap().mark_dirty();
+    handle.borrow().as_ref().unwrap().mark_dirty();
+    assert_eq!(engine.get(&doubled), 4);
+    assert_eq!((polls.get(), maps.get()), (2, 2));
+
+    // equal values don't recalculate dependents
+    handle.borrow().as_ref().unwrap().mark_dirty();
+    assert_eq!(engine.get(&doubled), 4);
+    assert_eq!((polls.get(), maps.get()), (3, 2));
+}
+
+#[test]
+fn test_dirty_marks_are_deduplicated() {
+    use crate::expert::external::Polled;
+    use crate::expert::DirtyHandle as _;
+    use crate::singlethread::{DirtyHandle, Engine, EngineEvent, SendDirtyHandle};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let mut engine = Engine::new();
+    let handles: Rc<RefCell<Vec<DirtyHandle>>> = Rc::new(RefCell::new(vec![]));
+    let anchors: Vec<_> = (0..3)
+        .map(|i| {
+            let handles = handles.clone();
+            Polled::new(move || i)
+                .on_dirty_handle(move |handle| handles.borrow_mut().push(handle))
+                .mount()
+        })
+        .collect();
+    for anchor in &anchors {
+        engine.mark_observed(anchor);
+    }
+    engine.stabilize();
+    let send_wakes = Arc::new(AtomicUsize::new(0));
+    {
+        let send_wakes = send_wakes.clone();
+        engine.set_send_wake_callback(move || {
+            send_wakes.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    let events = engine.event_stream();
+
+    let handles = handles.borrow().clone();
+    let send_handles: Vec<_> = handles.iter().map(DirtyHandle::to_send).collect();
+    handles[0].mark_dirty();
+    handles[0].mark_dirty();
+    DirtyHandle::mark_dirty_batch(&handles[..2]);
+    SendDirtyHandle::mark_dirty_batch(&send_handles);
+    SendDirtyHandle::mark_dirty_batch(&send_handles);
+    send_handles[2].mark_dirty();
+    assert_eq!(send_wakes.load(Ordering::SeqCst), 1);
+    engine.stabilize();
+
+    let marked: Vec<_> = events
+        .try_iter()
+        .filter_map(|event| match event {
+            EngineEvent::DirtyMarked { id } => Some(id),
+            _ => None,
+        })
+        .collect();
+    let ids: Vec<_> = anchors.iter().map(|anchor| anchor.token().id()).collect();
+    assert_eq!(marked, ids);
+}
+
+#[test]
+fn test_lens() {
+    use crate::singlethread::{Engine, Var};
+
+    #[derive(Clone)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+    #[derive(Clone)]
+    struct Shape {
+        origin: Point,
+        name: String,
+    }
+
+    let mut engine = Engine::new();
+    let shape = Var::new(Shape {
+        origin: Point { x: 1, y: 2 },
+        name: "square".to_string(),
+    });
+    let origin = shape.lens(|s| &s.origin, |s| &mut s.origin);
+    let x = origin.lens(|p| &p.x, |p| &mut p.x);
+    let name = shape.lens(|s| &s.name, |s| &mut s.name);
+    let sum = origin.watch().map(|p| p.x + p.y);
+    engine.mark_observed(&sum);
+    assert_eq!(engine.get(&sum), 3);
+    assert_eq!(engine.get(&name.watch()), "square");
+
+    x.set(10);
+    assert_eq!(x.get(), 10);
+    assert_eq!(engine.get(&sum), 12);
+    origin.modify(|p| p.y = 5);
+    assert_eq!(engine.get(&sum), 15);
+    let previous = x.get();
+    x.modify(|val| *val += previous);
+    assert_eq!(engine.get(&sum), 25);
+    let set_name = name.setter();
+    set_name("circle".to_string());
+    assert_eq!(engine.get(&name.watch()), "circle");
+    assert_eq!(shape.get().origin.x, 20);
+}
+
+#[test]
+fn test_dirty_called_once_per_change() {
+    use crate::expert::{AnchorInner, OutputContext, Poll, UpdateContext};
+    use crate::singlethread::{Anchor, AnchorToken, Engine, Var};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// adds its inputs, recording each call to `dirty`
+    struct Add {
+        a: Anchor<i32>,
+        b: Anchor<i32>,
+        sum: i32,
+        dirtied: Rc<RefCell<Vec<AnchorToken>>>,
+    }
+
+    impl AnchorInner<Engine> for Add {
+        type Output = i32;
+        fn dirty(&mut self, child: &AnchorToken) {
+            self.dirtied.borrow_mut().push(*child);
+        }
+        fn poll_updated<G: UpdateContext<Engine = Engine>>(&mut self, ctx: &mut G) -> Poll {
+            // requesting `a` again after `b` was pending used to subscribe to `a` twice
+            let a = ctx.request(&self.a, true);
+            let b = ctx.request(&self.b, true);
+            if a == Poll::Pending || b == Poll::Pending {
+                return Poll::Pending;
+            }
+            self.sum = *ctx.get(&self.a) + *ctx.get(&self.b);
+            Poll::Updated
+        }
+        fn output<'slf, 'out, G: OutputContext<'out, Engine = Engine>>(
+            &'slf self,
+            _ctx: &mut G,
+        ) -> &'out i32
+        where
+            'slf: 'out,
+        {
+            &self.sum
+        }
+    }
+
+    for observed in [false, true] {
+        let mut engine = Engine::new();
+        let a = Var::new(1);
+        let b = Var::new(2);
+        let b_plus_one = b.watch().map(|b| *b + 1);
+        let dirtied = Rc::new(RefCell::new(vec![]));
+        let sum = <Engine as crate::expert::Engine>::mount(Add {
+            a: a.watch(),
+            b: b_plus_one.clone(),
+            sum: 0,
+            dirtied: dirtied.clone(),
+        });
+        if observed {
+            engine.mark_observed(&sum);
+        }
+        assert_eq!(engine.get(&sum), 4);
+        for i in 10..13 {
+            a.set(i);
+            assert_eq!(engine.get(&sum), i + *b.get() + 1);
+            assert_eq!(*dirtied.borrow(), vec![a.watch().token()]);
+            dirtied.borrow_mut().clear();
+
+            b.set(i);
+            assert_eq!(engine.get(&sum), 2 * i + 1);
+            assert_eq!(*dirtied.borrow(), vec![b_plus_one.token()]);
+            dirtied.borrow_mut().clear();
+        }
+    }
+}
+
+#[test]
+fn test_get_skips_stabilize_when_clean() {
+    use crate::singlethread::{Engine, Var};
+
+    let mut engine = Engine::new();
+    let var = Var::new(1);
+    let observed = var.watch().map(|v| *v + 1);
+    let unobserved = var.watch().map(|v| *v * 10);
+    engine.mark_observed(&observed);
+    assert_eq!(engine.get(&observed), 2);
+    assert_eq!(engine.get(&unobserved), 10);
+
+    let generation = engine.generation();
+    for _ in 0..10 {
+        assert_eq!(engine.get(&observed), 2);
+        assert_eq!(engine.get(&unobserved), 10);
+    }
+    assert_eq!(engine.generation(), generation);
+
+    // changes still go through a stabilization
+    var.set(2);
+    assert_eq!(engine.get(&unobserved), 20);
+    assert_eq!(engine.get(&observed), 3);
+    assert_eq!(engine.generation(), generation + 1);
+
+    // as long as a hook could see it, every get stabilizes
+    engine.add_post_stabilize_hook(|_| {});
+    engine.get(&observed);
+    assert_eq!(engine.generation(), generation + 2);
+}
+
+#[test]
+fn test_then_memo() {
+    use crate::singlethread::{Engine, Var};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let mut engine = Engine::new();
+    let selected = Var::new(1);
+    let input = Var::new(100);
+    let calcs = Rc::new(Cell::new(0));
+    let picked = {
+        let calcs = calcs.clone();
+        let input = input.watch();
+        selected.watch().then_memo(
+            |selected| *selected % 2,
+            move |selected| {
+                let calcs = calcs.clone();
+                let offset = *selected;
+                input.map(move |input| {
+                    calcs.set(calcs.get() + 1);
+                    input + offset
+                })
+            },
+        )
+    };
+    engine.mark_observed(&picked);
+    assert_eq!(engine.get(&picked), 101);
+    selected.set(2);
+    assert_eq!(engine.get(&picked), 102);
+    assert_eq!(calcs.get(), 2);
+
+    // the key 1 is reused, along with its already-calculated output
+    selected.set(3);
+    assert_eq!(engine.get(&picked), 101);
+    assert_eq!(calcs.get(), 2);
+
+    input.set(200);
+    assert_eq!(engine.get(&picked), 201);
+    assert_eq!(calcs.get(), 3);
+}
+
+#[test]
+fn test_memoize() {
+    use crate::singlethread::{Anchor, Engine, Var};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let mut engine = Engine::new();
+    let scale = Var::new(10);
+    let builds = Rc::new(Cell::new(0));
+    let scaled = {
+        let builds = builds.clone();
+        let scale = scale.watch();
+        engine.memoize(move |_, n: u32| -> Anchor<u32> {
+            builds.set(builds.get() + 1);
+            scale.map(move |scale| n * scale)
+        })
+    };
+    assert_eq!(scaled.call(1).token(), scaled.call(1).token());
+    assert_ne!(scaled.call(1).token(), scaled.call(2).token());
+    assert_eq!(builds.get(), 2);
+    assert_eq!(engine.get(&scaled.call(2)), 20);
+    scale.set(3);
+    assert_eq!(engine.get(&scaled.call(2)), 6);
+
+    // the least recently used argument is evicted first
+    scaled.set_capacity(Some(2));
+    scaled.call(1);
+    scaled.call(3);
+    assert_eq!(scaled.len(), 2);
+    assert_eq!(builds.get(), 3);
+    scaled.call(1);
+    assert_eq!(builds.get(), 3);
+    scaled.call(2);
+    assert_eq!(builds.get(), 4);
+
+    scaled.clear();
+    assert!(scaled.is_empty());
+}
+
+#[test]
+fn test_constant_interned() {
+    use crate::singlethread::Engine;
+
+    let mut engine = Engine::new();
+    let a = engine.constant_interned(vec![1, 2, 3]);
+    let b = engine.constant_interned(vec![1, 2, 3]);
+    let c = engine.constant_interned(vec![4]);
+    assert_eq!(a.token(), b.token());
+    assert_ne!(a.token(), c.token());
+    assert_eq!(engine.get(&b), vec![1, 2, 3]);
+
+    // equal values of different types aren't shared
+    let small = engine.constant_interned(1u32);
+    let big = engine.constant_interned(1u64);
+    assert_eq!(engine.get(&small), 1);
+    assert_eq!(engine.get(&big), 1);
+
+    // freed constants are created again
+    let token = a.token();
+    drop(a);
+    drop(b);
+    let d = engine.constant_interned(vec![1, 2, 3]);
+    assert_ne!(d.token(), token);
+    assert_eq!(engine.get(&d), vec![1, 2, 3]);
+    for i in 0..200 {
+        engine.constant_interned(i);
+    }
+    assert_eq!(d.token(), engine.constant_interned(vec![1, 2, 3]).token());
+}
+
+#[test]
+fn test_equality_cutoff() {
+    use crate::singlethread::{Anchor, Engine, Var};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let mut engine = Engine::builder().equality_cutoff::<Vec<i32>>().build();
+    let nums = Var::new(vec![1, 2, 3]);
+    let evens = nums.watch().then(|nums| {
+        Anchor::constant(
+            nums.iter()
+                .copied()
+                .filter(|n| n % 2 == 0)
+                .collect::<Vec<_>>(),
+        )
+    });
+    let runs = Rc::new(Cell::new(0));
+    let total = {
+        let runs = runs.clone();
+        evens.map(move |evens| {
+            runs.set(runs.get() + 1);
+            evens.iter().sum::<i32>()
+        })
+    };
+    engine.mark_observed(&total);
+    assert_eq!(engine.get(&total), 2);
+    assert_eq!(runs.get(), 1);
+
+    // the evens are the same, so `total` isn't recalculated
+    nums.set(vec![1, 2, 5]);
+    engine.stabilize();
+    assert_eq!(runs.get(), 1);
+
+    nums.set(vec![2, 4]);
+    assert_eq!(engine.get(&total), 6);
+    assert_eq!(runs.get(), 2);
+
+    // Vars set to an equal value are cut off too, even after `clear`
+    engine.clear();
+    let nums = Var::new(vec![1]);
+    let count = Var::new(1);
+    let runs = Rc::new(Cell::new(0));
+    let both = {
+        let runs = runs.clone();
+        (&nums.watch(), &count.watch()).map(move |nums, count| {
+            runs.set(runs.get() + 1);
+            nums.len() + *count as usize
+        })
+    };
+    engine.mark_observed(&both);
+    engine.stabilize();
+    nums.set(vec![1]);
+    engine.stabilize();
+    assert_eq!(runs.get(), 1);
+
+    // but types without a cutoff aren't compared
+    count.set(1);
+    engine.stabilize();
+    assert_eq!(runs.get(), 2);
+}
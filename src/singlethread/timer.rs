@@ -0,0 +1,159 @@
+//! Time-based Anchors driven by tokio timers. Requires the `tokio` feature.
+//!
+//! These must be created from within a tokio runtime, since the underlying timer task is
+//! spawned onto the current runtime. The runtime must keep running for the timers to fire.
+
+use super::{Anchor, DirtyHandle, Engine, SendDirtyHandle};
+use crate::expert::{AnchorInner, OutputContext, Poll, UpdateContext};
+use std::panic::Location;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// Creates an Anchor counting how many times `period` has elapsed since it was created.
+/// The output starts at `0`.
+///
+/// Panics if called outside of a tokio runtime.
+#[track_caller]
+pub fn interval(period: Duration) -> Anchor<u64> {
+    <Engine as crate::expert::Engine>::mount(Timer {
+        runtime: Handle::current(),
+        start: Instant::now(),
+        period,
+        repeat: true,
+        ticks: Arc::new(AtomicU64::new(0)),
+        task: None,
+        output: 0,
+        location: Location::caller(),
+    })
+}
+
+/// Creates an Anchor that outputs `false` until `duration` has elapsed since it was created,
+/// and `true` afterwards.
+///
+/// Panics if called outside of a tokio runtime.
+#[track_caller]
+pub fn timeout(duration: Duration) -> Anchor<bool> {
+    interval_once(duration).map(|ticks| *ticks > 0)
+}
+
+#[track_caller]
+fn interval_once(duration: Duration) -> Anchor<u64> {
+    <Engine as crate::expert::Engine>::mount(Timer {
+        runtime: Handle::current(),
+        start: Instant::now(),
+        period: duration,
+        repeat: false,
+        ticks: Arc::new(AtomicU64::new(0)),
+        task: None,
+        output: 0,
+        location: Location::caller(),
+    })
+}
+
+struct Timer {
+    runtime: Handle,
+    start: Instant,
+    period: Duration,
+    repeat: bool,
+    /// incremented by the timer task every time the timer fires
+    ticks: Arc<AtomicU64>,
+    task: Option<JoinHandle<()>>,
+    output: u64,
+    location: &'static Location<'static>,
+}
+
+impl Timer {
+    fn spawn(&self, dirty_handle: SendDirtyHandle) -> JoinHandle<()> {
+        use crate::expert::DirtyHandle as _;
+        let ticks = self.ticks.clone();
+        let start = self.start + self.period;
+        let period = self.period;
+        let repeat = self.repeat;
+        self.runtime.spawn(async move {
+            if repeat {
+                let mut interval = tokio::time::interval_at(start, period);
+                loop {
+                    interval.tick().await;
+                    ticks.fetch_add(1, Ordering::SeqCst);
+                    dirty_handle.mark_dirty();
+                }
+            } else {
+                tokio::time::sleep_until(start).await;
+                ticks.fetch_add(1, Ordering::SeqCst);
+                dirty_handle.mark_dirty();
+            }
+        })
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+impl AnchorInner<Engine> for Timer {
+    type Output = u64;
+
+    fn dirty(&mut self, _child: &<super::AnchorHandle as crate::expert::AnchorHandle>::Token) {
+        panic!("somehow an input was dirtied on Timer; it never has any inputs to dirty")
+    }
+
+    fn poll_updated<G: UpdateContext<Engine = Engine>>(&mut self, ctx: &mut G) -> Poll {
+        if self.task.is_none() {
+            let dirty_handle: DirtyHandle = ctx.dirty_handle();
+            self.task = Some(self.spawn(dirty_handle.to_send()));
+            return Poll::Updated;
+        }
+        let ticks = self.ticks.load(Ordering::SeqCst);
+        if ticks == self.output {
+            Poll::Unchanged
+        } else {
+            self.output = ticks;
+            Poll::Updated
+        }
+    }
+
+    fn output<'slf, 'out, G: OutputContext<'out, Engine = Engine>>(
+        &'slf self,
+        _ctx: &mut G,
+    ) -> &'out Self::Output
+    where
+        'slf: 'out,
+    {
+        &self.output
+    }
+
+    fn debug_location(&self) -> Option<(&'static str, &'static Location<'static>)> {
+        Some(("timer", self.location))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timeout_fires() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let _guard = runtime.enter();
+        let mut engine = Engine::new();
+        let fired = timeout(Duration::from_millis(10));
+        let ticks = interval(Duration::from_millis(5));
+        engine.mark_observed(&fired);
+        assert!(!engine.get(&fired));
+        assert_eq!(engine.get(&ticks), 0);
+        runtime.block_on(tokio::time::sleep(Duration::from_millis(30)));
+        assert!(engine.get(&fired));
+        assert!(engine.get(&ticks) >= 1);
+    }
+}
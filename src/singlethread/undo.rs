@@ -0,0 +1,151 @@
+use super::{Engine, StabilizeReport, Transaction, Var};
+use std::any::Any;
+use std::rc::Rc;
+
+/// Records the values of a set of registered Vars before each transaction, allowing those
+/// transactions to be undone and redone. Vars changed outside of `UndoGroup::transaction`
+/// are not tracked, but will still be restored by `undo` and `redo`.
+#[derive(Default)]
+pub struct UndoGroup {
+    vars: Vec<Box<dyn UndoVar>>,
+    undo_stack: Vec<Snapshot>,
+    redo_stack: Vec<Snapshot>,
+}
+
+/// the values of every registered var, in registration order
+type Snapshot = Vec<Box<dyn Any>>;
+
+impl UndoGroup {
+    /// Creates an UndoGroup with no registered Vars.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `var` to the set of Vars recorded by this group. Registering a Var clears the undo
+    /// and redo history, since earlier snapshots don't contain its value.
+    pub fn register<T: 'static>(&mut self, var: &Var<T>) {
+        self.vars.push(Box::new(var.clone()));
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    /// Records the current value of every registered Var, then runs `Engine::transaction`.
+    /// Clears the redo history.
+    pub fn transaction<F: FnOnce(&mut Transaction)>(
+        &mut self,
+        engine: &mut Engine,
+        f: F,
+    ) -> StabilizeReport {
+        self.undo_stack.push(self.snapshot());
+        self.redo_stack.clear();
+        engine.transaction(f)
+    }
+
+    /// Restores every registered Var to its value before the most recent transaction, in a
+    /// single transaction. Returns false if there was nothing to undo.
+    pub fn undo(&mut self, engine: &mut Engine) -> bool {
+        let snapshot = match self.undo_stack.pop() {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+        self.redo_stack.push(self.snapshot());
+        self.restore(engine, snapshot);
+        true
+    }
+
+    /// Reapplies the most recently undone transaction. Returns false if there was nothing to
+    /// redo.
+    pub fn redo(&mut self, engine: &mut Engine) -> bool {
+        let snapshot = match self.redo_stack.pop() {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+        self.undo_stack.push(self.snapshot());
+        self.restore(engine, snapshot);
+        true
+    }
+
+    /// Returns true if there's a transaction to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// Returns true if there's an undone transaction to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        self.vars.iter().map(|var| var.snapshot()).collect()
+    }
+
+    fn restore(&self, engine: &mut Engine, snapshot: Snapshot) {
+        engine.transaction(|tx| {
+            for (var, val) in self.vars.iter().zip(snapshot) {
+                var.restore(tx, val);
+            }
+        });
+    }
+}
+
+trait UndoVar {
+    fn snapshot(&self) -> Box<dyn Any>;
+    fn restore(&self, tx: &mut Transaction, val: Box<dyn Any>);
+}
+
+impl<T: 'static> UndoVar for Var<T> {
+    fn snapshot(&self) -> Box<dyn Any> {
+        Box::new(self.get())
+    }
+
+    fn restore(&self, tx: &mut Transaction, val: Box<dyn Any>) {
+        let val: Rc<T> = *val.downcast().unwrap();
+        // skip vars that haven't changed, so their dependents aren't recalculated
+        if Rc::ptr_eq(&val, &self.get()) {
+            return;
+        }
+        let var = self.clone();
+        tx.ops.push(Box::new(move || var.set_rc(val)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::singlethread::MultiAnchor;
+
+    #[test]
+    fn undo_redo() {
+        let mut engine = Engine::new();
+        let a = Var::new(1);
+        let b = Var::new(10);
+        let sum = (&a.watch(), &b.watch()).map(|a, b| *a + *b);
+        let mut undo = UndoGroup::new();
+        undo.register(&a);
+        undo.register(&b);
+        assert!(!undo.undo(&mut engine));
+
+        undo.transaction(&mut engine, |tx| tx.set(&a, 2));
+        undo.transaction(&mut engine, |tx| {
+            tx.set(&a, 3);
+            tx.set(&b, 30);
+        });
+        assert_eq!(engine.get(&sum), 33);
+
+        assert!(undo.undo(&mut engine));
+        assert_eq!(engine.get(&sum), 12);
+        assert!(undo.undo(&mut engine));
+        assert_eq!(engine.get(&sum), 11);
+        assert!(!undo.can_undo());
+
+        assert!(undo.redo(&mut engine));
+        assert_eq!(engine.get(&sum), 12);
+
+        // a new transaction discards the redo history
+        undo.transaction(&mut engine, |tx| tx.set(&b, 20));
+        assert!(!undo.can_redo());
+        assert_eq!(engine.get(&sum), 22);
+        assert!(undo.undo(&mut engine));
+        assert_eq!(engine.get(&sum), 12);
+    }
+}
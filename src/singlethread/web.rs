@@ -0,0 +1,154 @@
+//! Helpers for running an Engine in the browser. Requires the `web` feature.
+//!
+//! `event_var` and `input_value` bridge DOM events into `Var`s, and `animation_frame_loop`
+//! stabilizes the engine from `requestAnimationFrame` whenever some observed Anchor is out of
+//! date, so the page only does work on frames where something changed.
+
+use super::{Engine, StabilizeBudget, StabilizeReport, Var};
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use std::time::Duration;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{Event, EventTarget, HtmlInputElement};
+
+/// A DOM event listener feeding a `Var`. The listener is removed when this is dropped.
+#[must_use = "the event listener is removed when this is dropped"]
+pub struct EventListener {
+    target: EventTarget,
+    event: String,
+    callback: Closure<dyn FnMut(Event)>,
+}
+
+impl Drop for EventListener {
+    fn drop(&mut self) {
+        let _ = self.target.remove_event_listener_with_callback(
+            &self.event,
+            self.callback.as_ref().unchecked_ref(),
+        );
+    }
+}
+
+/// Creates a `Var` that starts as `initial`, and is set to `f(&event)` every time `target`
+/// receives an `event` event.
+pub fn event_var<T: 'static, F: FnMut(&Event) -> T + 'static>(
+    target: &EventTarget,
+    event: &str,
+    initial: T,
+    mut f: F,
+) -> (Var<T>, EventListener) {
+    let var = Var::new(initial);
+    let setter = var.clone();
+    let callback =
+        Closure::wrap(Box::new(move |event: Event| setter.set(f(&event))) as Box<dyn FnMut(Event)>);
+    target
+        .add_event_listener_with_callback(event, callback.as_ref().unchecked_ref())
+        .expect("couldn't add event listener");
+    let listener = EventListener {
+        target: target.clone(),
+        event: event.to_string(),
+        callback,
+    };
+    (var, listener)
+}
+
+/// Creates a `Var` that tracks the current value of a text input.
+pub fn input_value(input: &HtmlInputElement) -> (Var<String>, EventListener) {
+    let read = input.clone();
+    event_var(input, "input", input.value(), move |_| read.value())
+}
+
+type FrameCallback = Box<dyn FnMut(&mut Engine, &StabilizeReport)>;
+type RafClosure = Closure<dyn FnMut(f64)>;
+
+struct FrameState {
+    engine: Rc<RefCell<Engine>>,
+    budget: Duration,
+    on_frame: RefCell<FrameCallback>,
+    pending: Cell<Option<i32>>,
+    callback: RefCell<Option<RafClosure>>,
+}
+
+/// Stabilizes an engine from `requestAnimationFrame`. Stops when dropped.
+#[must_use = "the loop stops when this is dropped"]
+pub struct AnimationFrameLoop {
+    state: Rc<FrameState>,
+}
+
+impl Drop for AnimationFrameLoop {
+    fn drop(&mut self) {
+        if let Some(id) = self.state.pending.take() {
+            let _ = window().cancel_animation_frame(id);
+        }
+        self.state.callback.borrow_mut().take();
+    }
+}
+
+/// Stabilizes `engine` on the next animation frame whenever some observed Anchor becomes out of
+/// date, calling `on_frame` with each completed stabilization's report so the page can be
+/// updated. Each frame spends at most about `budget` recalculating, as in
+/// `Engine::stabilize_budgeted`; larger stabilizations are spread over several frames.
+///
+/// This replaces any callback set with `Engine::set_wake_callback`.
+pub fn animation_frame_loop<F: FnMut(&mut Engine, &StabilizeReport) + 'static>(
+    engine: Rc<RefCell<Engine>>,
+    budget: Duration,
+    on_frame: F,
+) -> AnimationFrameLoop {
+    let state = Rc::new(FrameState {
+        engine,
+        budget,
+        on_frame: RefCell::new(Box::new(on_frame)),
+        pending: Cell::new(None),
+        callback: RefCell::new(None),
+    });
+    let weak = Rc::downgrade(&state);
+    *state.callback.borrow_mut() = Some(Closure::wrap(Box::new(move |_timestamp: f64| {
+        if let Some(state) = weak.upgrade() {
+            run_frame(&state);
+        }
+    }) as Box<dyn FnMut(f64)>));
+
+    let weak = Rc::downgrade(&state);
+    let mut engine = state.engine.borrow_mut();
+    engine.set_wake_callback(move || {
+        if let Some(state) = Weak::upgrade(&weak) {
+            request_frame(&state);
+        }
+    });
+    let needs_stabilize = engine.needs_stabilize();
+    std::mem::drop(engine);
+    if needs_stabilize {
+        request_frame(&state);
+    }
+    AnimationFrameLoop { state }
+}
+
+fn run_frame(state: &FrameState) {
+    state.pending.set(None);
+    let mut engine = state.engine.borrow_mut();
+    match engine.stabilize_budgeted(StabilizeBudget::Duration(state.budget)) {
+        Some(report) => (state.on_frame.borrow_mut())(&mut engine, &report),
+        None => {
+            // work remains, so pick up where this frame left off on the next one
+            std::mem::drop(engine);
+            request_frame(state);
+        }
+    }
+}
+
+fn request_frame(state: &FrameState) {
+    if state.pending.get().is_some() {
+        return;
+    }
+    if let Some(callback) = &*state.callback.borrow() {
+        let id = window()
+            .request_animation_frame(callback.as_ref().unchecked_ref())
+            .expect("couldn't request animation frame");
+        state.pending.set(Some(id));
+    }
+}
+
+fn window() -> web_sys::Window {
+    web_sys::window().expect("no global `window` exists")
+}
@@ -0,0 +1,612 @@
+//! Reusable pieces for building your own Anchors engine.
+//!
+//! `singlethread` keeps its graph in an intrusive, pointer-based arena tuned for that one engine.
+//! The types here implement the same algorithms safely and generically, so an alternative engine
+//! (multithreaded, deterministic-replay, ...) can start from them instead of reimplementing the
+//! graph bookkeeping:
+//!
+//! - `Dag` stores one value per node and tracks edges between nodes. Each node's height is kept
+//!   strictly greater than the height of everything it depends on, and edges that would create a
+//!   cycle are rejected.
+//! - `RecalcQueue` pops keys lowest height first, and in insertion order within a height.
+//!   `Dag::queue_recalc` and `Dag::pop_recalc` wrap one that follows the graph's heights.
+//! - Necessity: a node is necessary while it's observed, or while some necessary node depends on
+//!   it. `Dag` keeps this up to date as edges and observations change, and records which nodes
+//!   flipped so the engine can react to them.
+//!
+//! Nothing here knows about `AnchorInner`; wiring polling, dirty marking and output storage
+//! together is left to the engine.
+
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+
+/// Identifies a node in a `Dag`. Ids are never reused: once a node is removed, its id stays
+/// dead even if its slot is handed out to a new node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+/// Returned by `Dag::add_edge` when the new edge would make a node depend on itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    /// The nodes in the cycle. Each node depends on the next one, and the last depends on the
+    /// first.
+    pub path: Vec<NodeId>,
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "loop detected in graph!")?;
+        for id in &self.path {
+            write!(f, "\n  {:?}", id)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CycleError {}
+
+/// A queue of keys ordered by height. `pop` returns the lowest-height key first, and keys of
+/// equal height in the order they were pushed. Duplicates aren't filtered out.
+#[derive(Debug, Clone)]
+pub struct RecalcQueue<K> {
+    queues: Vec<VecDeque<K>>,
+    min_height: usize,
+    len: usize,
+}
+
+impl<K> Default for RecalcQueue<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K> RecalcQueue<K> {
+    pub fn new() -> Self {
+        RecalcQueue {
+            queues: vec![],
+            min_height: 0,
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, height: usize, key: K) {
+        if height >= self.queues.len() {
+            self.queues.resize_with(height + 1, VecDeque::new);
+        }
+        self.queues[height].push_back(key);
+        self.min_height = if self.len == 0 {
+            height
+        } else {
+            self.min_height.min(height)
+        };
+        self.len += 1;
+    }
+
+    /// Removes and returns the lowest-height key, along with the height it was pushed at.
+    pub fn pop(&mut self) -> Option<(usize, K)> {
+        if self.len == 0 {
+            return None;
+        }
+        while self.queues[self.min_height].is_empty() {
+            self.min_height += 1;
+        }
+        let key = self.queues[self.min_height].pop_front()?;
+        self.len -= 1;
+        Some((self.min_height, key))
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        for queue in &mut self.queues {
+            queue.clear();
+        }
+        self.min_height = 0;
+        self.len = 0;
+    }
+}
+
+#[derive(Debug)]
+struct Slot<T> {
+    generation: u32,
+    entry: Option<Entry<T>>,
+}
+
+#[derive(Debug)]
+struct Entry<T> {
+    value: T,
+    height: usize,
+    /// nodes this one depends on
+    children: Vec<NodeId>,
+    /// nodes that depend on this one
+    parents: Vec<NodeId>,
+    observed: bool,
+    /// number of necessary parents
+    necessary_parents: usize,
+    queued: bool,
+    visited: bool,
+}
+
+impl<T> Entry<T> {
+    fn is_necessary(&self) -> bool {
+        self.observed || self.necessary_parents > 0
+    }
+}
+
+/// A directed acyclic graph of nodes holding `T`s, with heights, a recalculation queue and
+/// necessity tracking. See the module docs for an overview.
+///
+/// Edges point from a child to a parent that depends on it. Methods taking a `NodeId` panic if
+/// the node has been removed, unless they say otherwise.
+#[derive(Debug)]
+pub struct Dag<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    queue: RecalcQueue<NodeId>,
+    necessity_changes: Vec<NodeId>,
+    /// number of live nodes with `queued` set
+    queued: usize,
+}
+
+impl<T> Default for Dag<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Dag<T> {
+    pub fn new() -> Self {
+        Dag {
+            slots: vec![],
+            free: vec![],
+            queue: RecalcQueue::new(),
+            necessity_changes: vec![],
+            queued: 0,
+        }
+    }
+
+    /// Adds a node with no edges at height 0.
+    pub fn insert(&mut self, value: T) -> NodeId {
+        let entry = Some(Entry {
+            value,
+            height: 0,
+            children: vec![],
+            parents: vec![],
+            observed: false,
+            necessary_parents: 0,
+            queued: false,
+            visited: false,
+        });
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.entry = entry;
+                NodeId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                let index = u32::try_from(self.slots.len()).expect("too many nodes in graph");
+                self.slots.push(Slot {
+                    generation: 0,
+                    entry,
+                });
+                NodeId {
+                    index,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    /// Removes a node and all of its edges, returning its value. Returns `None` if the node was
+    /// already removed. Children that were only necessary because of this node become
+    /// unnecessary.
+    pub fn remove(&mut self, id: NodeId) -> Option<T> {
+        if !self.contains(id) {
+            return None;
+        }
+        let necessary = self.entry(id).is_necessary();
+        let children = core::mem::take(&mut self.entry_mut(id).children);
+        for child in children {
+            self.entry_mut(child).parents.retain(|p| *p != id);
+            if necessary {
+                self.remove_necessary_parent(child);
+            }
+        }
+        let parents = core::mem::take(&mut self.entry_mut(id).parents);
+        for parent in parents {
+            self.entry_mut(parent).children.retain(|c| *c != id);
+        }
+        let slot = &mut self.slots[id.index as usize];
+        let entry = slot.entry.take()?;
+        if entry.queued {
+            self.queued -= 1;
+        }
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index);
+        Some(entry.value)
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.try_entry(id).is_some()
+    }
+
+    /// Returns the number of nodes in the graph.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the node's value, or `None` if it's been removed.
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        self.try_entry(id).map(|entry| &entry.value)
+    }
+
+    /// Returns the node's value, or `None` if it's been removed.
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut T> {
+        self.try_entry_mut(id).map(|entry| &mut entry.value)
+    }
+
+    pub fn height(&self, id: NodeId) -> usize {
+        self.entry(id).height
+    }
+
+    /// Returns the nodes `id` depends on.
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.entry(id).children
+    }
+
+    /// Returns the nodes that depend on `id`.
+    pub fn parents(&self, id: NodeId) -> &[NodeId] {
+        &self.entry(id).parents
+    }
+
+    /// Makes `parent` depend on `child`, raising the heights of `parent` and everything above
+    /// it as needed. Adding an edge that already exists does nothing.
+    ///
+    /// If the edge would create a cycle, it isn't added. Some heights may still have been
+    /// raised, which is harmless: heights only need to be large enough, not as small as possible.
+    pub fn add_edge(&mut self, child: NodeId, parent: NodeId) -> Result<(), CycleError> {
+        if self.entry(child).parents.contains(&parent) {
+            return Ok(());
+        }
+        if child == parent {
+            return Err(CycleError { path: vec![child] });
+        }
+        if self.height(child) >= self.height(parent) {
+            self.entry_mut(child).visited = true;
+            let res = self.set_min_height(parent, self.height(child) + 1);
+            self.entry_mut(child).visited = false;
+            if let Err(mut cycle) = res {
+                cycle.path.push(child);
+                return Err(cycle);
+            }
+        }
+        self.entry_mut(child).parents.push(parent);
+        self.entry_mut(parent).children.push(child);
+        if self.entry(parent).is_necessary() {
+            self.add_necessary_parent(child);
+        }
+        Ok(())
+    }
+
+    /// Removes the edge between `child` and `parent`, if there is one. Heights are left as they
+    /// are.
+    pub fn remove_edge(&mut self, child: NodeId, parent: NodeId) {
+        let parents = &mut self.entry_mut(child).parents;
+        let pos = match parents.iter().position(|p| *p == parent) {
+            Some(pos) => pos,
+            None => return,
+        };
+        parents.swap_remove(pos);
+        self.entry_mut(parent).children.retain(|c| *c != child);
+        if self.entry(parent).is_necessary() {
+            self.remove_necessary_parent(child);
+        }
+    }
+
+    /// Marks whether the engine's user is observing the node. Observed nodes, and everything
+    /// they transitively depend on, are necessary.
+    pub fn set_observed(&mut self, id: NodeId, observed: bool) {
+        let entry = self.entry_mut(id);
+        if entry.observed == observed {
+            return;
+        }
+        let was_necessary = entry.is_necessary();
+        entry.observed = observed;
+        if was_necessary != entry.is_necessary() {
+            self.necessity_changed(id);
+        }
+    }
+
+    pub fn is_observed(&self, id: NodeId) -> bool {
+        self.entry(id).observed
+    }
+
+    pub fn is_necessary(&self, id: NodeId) -> bool {
+        self.entry(id).is_necessary()
+    }
+
+    /// Returns the nodes whose necessity has flipped since the last call, in the order they
+    /// flipped. A node that flipped and flipped back is listed twice, so check `is_necessary`
+    /// for its current state. Nodes removed since then are skipped.
+    pub fn drain_necessity_changes(&mut self) -> Vec<NodeId> {
+        let mut changes = core::mem::take(&mut self.necessity_changes);
+        changes.retain(|id| self.contains(*id));
+        changes
+    }
+
+    /// Queues the node for recalculation. Queueing an already-queued node does nothing.
+    pub fn queue_recalc(&mut self, id: NodeId) {
+        let entry = self.entry_mut(id);
+        if entry.queued {
+            return;
+        }
+        entry.queued = true;
+        let height = entry.height;
+        self.queued += 1;
+        self.queue.push(height, id);
+    }
+
+    pub fn is_queued(&self, id: NodeId) -> bool {
+        self.entry(id).queued
+    }
+
+    /// Removes and returns the queued node with the lowest height. Nodes of the same height are
+    /// returned in the order they were queued. Removed nodes are skipped.
+    pub fn pop_recalc(&mut self) -> Option<NodeId> {
+        while let Some((height, id)) = self.queue.pop() {
+            let entry = match self.try_entry_mut(id) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            if !entry.queued {
+                continue;
+            }
+            if entry.height > height {
+                // the node moved up since it was queued; wait until its new height comes up
+                let height = entry.height;
+                self.queue.push(height, id);
+                continue;
+            }
+            entry.queued = false;
+            self.queued -= 1;
+            return Some(id);
+        }
+        None
+    }
+
+    /// Returns true if any nodes are queued for recalculation.
+    pub fn recalc_queued(&self) -> bool {
+        self.queued > 0
+    }
+
+    fn try_entry(&self, id: NodeId) -> Option<&Entry<T>> {
+        let slot = self.slots.get(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.entry.as_ref()
+    }
+
+    fn try_entry_mut(&mut self, id: NodeId) -> Option<&mut Entry<T>> {
+        let slot = self.slots.get_mut(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.entry.as_mut()
+    }
+
+    fn entry(&self, id: NodeId) -> &Entry<T> {
+        self.try_entry(id)
+            .unwrap_or_else(|| panic!("{:?} has been removed from the graph", id))
+    }
+
+    fn entry_mut(&mut self, id: NodeId) -> &mut Entry<T> {
+        self.try_entry_mut(id)
+            .unwrap_or_else(|| panic!("{:?} has been removed from the graph", id))
+    }
+
+    /// On a cycle, returns the path from the node that closed the loop down to `id`, so each
+    /// node in it depends on the next.
+    fn set_min_height(&mut self, id: NodeId, min_height: usize) -> Result<(), CycleError> {
+        let entry = self.entry_mut(id);
+        if entry.visited {
+            return Err(CycleError { path: vec![] });
+        }
+        if entry.height >= min_height {
+            return Ok(());
+        }
+        entry.visited = true;
+        entry.height = min_height;
+        let mut res = Ok(());
+        for i in 0..self.entry(id).parents.len() {
+            let parent = self.entry(id).parents[i];
+            if let Err(err) = self.set_min_height(parent, min_height + 1) {
+                res = Err(err);
+                break;
+            }
+        }
+        self.entry_mut(id).visited = false;
+        res.map_err(|mut err| {
+            err.path.push(id);
+            err
+        })
+    }
+
+    fn add_necessary_parent(&mut self, id: NodeId) {
+        let entry = self.entry_mut(id);
+        entry.necessary_parents += 1;
+        if entry.necessary_parents == 1 && !entry.observed {
+            self.necessity_changed(id);
+        }
+    }
+
+    fn remove_necessary_parent(&mut self, id: NodeId) {
+        let entry = self.entry_mut(id);
+        entry.necessary_parents -= 1;
+        if entry.necessary_parents == 0 && !entry.observed {
+            self.necessity_changed(id);
+        }
+    }
+
+    /// Records that `id` flipped, and passes the change down to its children.
+    fn necessity_changed(&mut self, id: NodeId) {
+        self.necessity_changes.push(id);
+        let necessary = self.entry(id).is_necessary();
+        let mut stack = vec![id];
+        while let Some(node) = stack.pop() {
+            for i in 0..self.entry(node).children.len() {
+                let child = self.entry(node).children[i];
+                let entry = self.entry_mut(child);
+                let was_necessary = entry.is_necessary();
+                if necessary {
+                    entry.necessary_parents += 1;
+                } else {
+                    entry.necessary_parents -= 1;
+                }
+                if was_necessary != entry.is_necessary() {
+                    self.necessity_changes.push(child);
+                    stack.push(child);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn heights_rise_with_edges() {
+        let mut dag = Dag::new();
+        let a = dag.insert("a");
+        let b = dag.insert("b");
+        let c = dag.insert("c");
+        dag.add_edge(b, c).unwrap();
+        assert_eq!((0, 0, 1), (dag.height(a), dag.height(b), dag.height(c)));
+        dag.add_edge(a, b).unwrap();
+        assert_eq!((0, 1, 2), (dag.height(a), dag.height(b), dag.height(c)));
+        assert_eq!(&[b], dag.parents(a));
+        assert_eq!(&[b], dag.children(c));
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let mut dag = Dag::new();
+        let a = dag.insert(());
+        let b = dag.insert(());
+        let c = dag.insert(());
+        dag.add_edge(a, b).unwrap();
+        dag.add_edge(b, c).unwrap();
+        assert_eq!(
+            Err(CycleError {
+                path: vec![b, a, c]
+            }),
+            dag.add_edge(c, a)
+        );
+        assert_eq!(Err(CycleError { path: vec![a] }), dag.add_edge(a, a));
+        assert!(dag.parents(c).is_empty());
+
+        // the graph is still usable afterwards
+        let d = dag.insert(());
+        dag.add_edge(c, d).unwrap();
+        assert!(dag.height(d) > dag.height(c));
+    }
+
+    #[test]
+    fn recalc_pops_lowest_height_first() {
+        let mut dag = Dag::new();
+        let a = dag.insert(());
+        let b = dag.insert(());
+        let c = dag.insert(());
+        let d = dag.insert(());
+        dag.add_edge(a, b).unwrap();
+        dag.queue_recalc(b);
+        dag.queue_recalc(c);
+        dag.queue_recalc(c);
+        dag.queue_recalc(d);
+        assert_eq!(Some(c), dag.pop_recalc());
+        assert_eq!(Some(d), dag.pop_recalc());
+        assert_eq!(Some(b), dag.pop_recalc());
+        assert_eq!(None, dag.pop_recalc());
+
+        // queued nodes that move up wait for their new height
+        dag.queue_recalc(a);
+        dag.queue_recalc(c);
+        dag.add_edge(b, c).unwrap();
+        dag.queue_recalc(b);
+        assert_eq!(Some(a), dag.pop_recalc());
+        assert_eq!(Some(b), dag.pop_recalc());
+        assert_eq!(Some(c), dag.pop_recalc());
+        assert!(!dag.recalc_queued());
+    }
+
+    #[test]
+    fn necessity_follows_observed_parents() {
+        let mut dag = Dag::new();
+        let a = dag.insert(());
+        let b = dag.insert(());
+        let c = dag.insert(());
+        dag.add_edge(a, b).unwrap();
+        dag.add_edge(a, c).unwrap();
+        dag.set_observed(b, true);
+        assert_eq!(vec![b, a], dag.drain_necessity_changes());
+        dag.set_observed(c, true);
+        assert_eq!(vec![c], dag.drain_necessity_changes());
+        assert!(dag.is_necessary(a));
+
+        dag.set_observed(b, false);
+        assert_eq!(vec![b], dag.drain_necessity_changes());
+        assert!(dag.is_necessary(a));
+        dag.remove_edge(a, c);
+        assert_eq!(vec![a], dag.drain_necessity_changes());
+        assert!(!dag.is_necessary(a));
+
+        // edges from necessary parents make the whole subtree necessary
+        let d = dag.insert(());
+        dag.add_edge(d, a).unwrap();
+        dag.add_edge(a, c).unwrap();
+        assert_eq!(vec![a, d], dag.drain_necessity_changes());
+        assert!(dag.is_necessary(d));
+    }
+
+    #[test]
+    fn removed_ids_stay_dead() {
+        let mut dag = Dag::new();
+        let a = dag.insert(1);
+        let b = dag.insert(2);
+        dag.add_edge(a, b).unwrap();
+        dag.set_observed(b, true);
+        dag.queue_recalc(a);
+        assert_eq!(Some(1), dag.remove(a));
+        assert_eq!(None, dag.remove(a));
+        assert!(dag.children(b).is_empty());
+        assert_eq!(None, dag.pop_recalc());
+
+        let c = dag.insert(3);
+        assert_ne!(a, c);
+        assert_eq!(None, dag.get(a));
+        assert_eq!(Some(&3), dag.get(c));
+        assert_eq!(2, dag.len());
+    }
+}